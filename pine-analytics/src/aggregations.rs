@@ -7,6 +7,9 @@ use std::collections::BTreeMap;
 
 use crate::state::{ApplicationId, CapturedEvent, MetricValue, Timestamp};
 
+/// Values equal to this many significant digits count as equal in `mode`
+pub const MODE_SIGNIFICANT_DIGITS: usize = 9;
+
 /// Anomaly detection result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnomalyEvent {
@@ -41,6 +44,17 @@ impl TimeBucket {
         }
     }
 
+    /// Create the bucket containing `timestamp` on a clock `offset_ms` ahead
+    /// of UTC, so buckets start at local boundaries such as local midnight
+    pub fn from_timestamp_with_offset(timestamp: Timestamp, granularity_ms: u64, offset_ms: i64) -> Self {
+        let local = timestamp as i128 + offset_ms as i128;
+        let local_start = local - local.rem_euclid(granularity_ms as i128);
+        Self {
+            start: (local_start - offset_ms as i128).max(0) as Timestamp,
+            duration_ms: granularity_ms,
+        }
+    }
+
     /// Get end timestamp of bucket
     pub fn end(&self) -> Timestamp {
         self.start + self.duration_ms
@@ -57,6 +71,8 @@ pub enum AggregationType {
     Count,
     Percentile(f64), // e.g., 0.95 for 95th percentile
     StandardDeviation,
+    Median,
+    Mode,
 }
 
 /// Result of cross-chain correlation
@@ -68,6 +84,66 @@ pub struct CorrelationMatrix {
     pub coefficients: Vec<f64>,
     /// Metric used for correlation
     pub metric: String,
+    /// Length series longer than `max_samples` were subsampled to, if any
+    /// were
+    #[serde(default)]
+    pub sampled_size: Option<usize>,
+}
+
+impl CorrelationMatrix {
+    /// Correlate every pair of `series`, labelled by `chains`.
+    ///
+    /// Only series of the same length are paired point by point; other
+    /// pairs get 0. Series longer than `max_samples` are subsampled, and
+    /// since equal lengths pick the same indexes, paired series stay
+    /// aligned.
+    pub fn from_series(
+        chains: Vec<String>,
+        metric: String,
+        series: &[Vec<f64>],
+        max_samples: Option<usize>,
+    ) -> Self {
+        let mut sampled_size = None;
+        let sampled: Vec<Vec<f64>> = series
+            .iter()
+            .map(|values| match max_samples {
+                Some(max_samples) if values.len() > max_samples.max(2) => {
+                    let values = AggregationEngine::subsample(values, max_samples);
+                    sampled_size = Some(values.len());
+                    values
+                }
+                _ => values.clone(),
+            })
+            .collect();
+
+        let n = series.len();
+        let mut coefficients = Vec::with_capacity(n * n);
+        for i in 0..n {
+            for j in 0..n {
+                if i == j {
+                    coefficients.push(1.0);
+                } else if series[i].len() == series[j].len() && !series[i].is_empty() {
+                    coefficients.push(AggregationEngine::correlation(&sampled[i], &sampled[j]));
+                } else {
+                    coefficients.push(0.0);
+                }
+            }
+        }
+
+        Self {
+            chains,
+            coefficients,
+            metric,
+            sampled_size,
+        }
+    }
+
+    /// Round every coefficient to `places` decimal places
+    pub fn round(&mut self, places: u8) {
+        for coefficient in self.coefficients.iter_mut() {
+            *coefficient = AggregationEngine::round_to(*coefficient, places);
+        }
+    }
 }
 
 /// Moving average result
@@ -101,18 +177,64 @@ pub struct AggregatedResult {
     pub metric: String,
     /// Aggregation type used
     pub aggregation: AggregationType,
-    /// Result value
+    /// Result value; `0.0` when there was no data, see `observed_value`
     pub value: f64,
     /// Time bucket if applicable
     pub bucket: Option<TimeBucket>,
-    /// Number of data points aggregated
+    /// Number of data points aggregated; zero means no data
     pub sample_count: usize,
+    /// Whether the data points had different metric types and were coerced
+    #[serde(default)]
+    pub mixed_types: bool,
+}
+
+impl AggregatedResult {
+    /// Aggregate `values`, reporting no data rather than an
+    /// aggregation-specific sentinel when there are none
+    pub fn from_values(metric: String, aggregation: AggregationType, values: &[f64]) -> Self {
+        let value = if values.is_empty() {
+            0.0
+        } else {
+            AggregationEngine::aggregate(values, &aggregation)
+        };
+        Self {
+            metric,
+            aggregation,
+            value,
+            bucket: None,
+            sample_count: values.len(),
+            mixed_types: false,
+        }
+    }
+
+    /// Round the value to `places` decimal places
+    pub fn round(&mut self, places: u8) {
+        self.value = AggregationEngine::round_to(self.value, places);
+    }
+
+    /// The aggregated value, or `None` when no data points were aggregated
+    pub fn observed_value(&self) -> Option<f64> {
+        (self.sample_count > 0).then_some(self.value)
+    }
 }
 
 /// Aggregation engine for computing metrics
 pub struct AggregationEngine;
 
 impl AggregationEngine {
+    /// Round to `places` decimal places.
+    ///
+    /// Non-finite values, and values too large to scale, are returned as is.
+    pub fn round_to(value: f64, places: u8) -> f64 {
+        let factor = 10f64.powi(places as i32);
+        let rounded = (value * factor).round() / factor;
+        if rounded.is_finite() {
+            rounded
+        } else {
+            value
+        }
+    }
+
     /// Compute statistical mean
     pub fn mean(values: &[f64]) -> f64 {
         if values.is_empty() {
@@ -152,6 +274,28 @@ impl AggregationEngine {
             .collect()
     }
 
+    /// Compute exponentially weighted moving average
+    ///
+    /// Applies `s_t = alpha * x_t + (1 - alpha) * s_{t-1}`, starting from
+    /// the first value. Each point's `window_size` is the number of values
+    /// smoothed so far. Callers are expected to keep `alpha` in `(0, 1]`.
+    pub fn ewma(values: &[(Timestamp, f64)], alpha: f64) -> Vec<MovingAveragePoint> {
+        let mut smoothed: Option<f64> = None;
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, &(timestamp, value))| {
+                let s = smoothed.map_or(value, |s| alpha * value + (1.0 - alpha) * s);
+                smoothed = Some(s);
+                MovingAveragePoint {
+                    timestamp,
+                    value: s,
+                    window_size: i as u64 + 1,
+                }
+            })
+            .collect()
+    }
+
     /// Detect anomalies using Z-score
     pub fn detect_anomalies(values: &[(Timestamp, f64)], sensitivity: f64) -> Vec<AnomalyEvent> {
         let vals: Vec<f64> = values.iter().map(|(_, v)| *v).collect();
@@ -182,18 +326,100 @@ impl AggregationEngine {
     }
 
     /// Compute percentile
+    ///
+    /// Values are ordered with `f64::total_cmp`, so the result is identical
+    /// for identical inputs regardless of their order. NaNs sort after all
+    /// other values (negative NaNs before them).
     pub fn percentile(values: &[f64], p: f64) -> f64 {
         if values.is_empty() {
             return 0.0;
         }
 
         let mut sorted = values.to_vec();
-        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        sorted.sort_by(f64::total_cmp);
 
         let index = (p * (sorted.len() - 1) as f64).round() as usize;
         sorted[index.min(sorted.len() - 1)]
     }
 
+    /// Compute median; the average of the middle two for even lengths
+    ///
+    /// Ordered with `f64::total_cmp` like `percentile`.
+    pub fn median(values: &[f64]) -> f64 {
+        if values.is_empty() {
+            return 0.0;
+        }
+
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+
+        let mid = sorted.len() / 2;
+        if sorted.len() % 2 == 1 {
+            sorted[mid]
+        } else {
+            (sorted[mid - 1] + sorted[mid]) / 2.0
+        }
+    }
+
+    /// Compute the most frequent value, or `None` without finite values
+    ///
+    /// Values are bucketed by rounding to `MODE_SIGNIFICANT_DIGITS`, so
+    /// float noise does not split a value at any magnitude. Ties go to the
+    /// smallest value, and the result is the smallest value in the winning
+    /// bucket. Non-finite values are ignored.
+    pub fn mode(values: &[f64]) -> Option<f64> {
+        let mut buckets: BTreeMap<u64, (usize, f64)> = BTreeMap::new();
+        for &value in values.iter().filter(|v| v.is_finite()) {
+            let rounded: f64 = format!("{:.*e}", MODE_SIGNIFICANT_DIGITS - 1, value)
+                .parse()
+                .unwrap_or(value);
+            // `+ 0.0` folds -0.0 into 0.0
+            let bucket = buckets
+                .entry((rounded + 0.0).to_bits())
+                .or_insert((0, value));
+            bucket.0 += 1;
+            bucket.1 = bucket.1.min(value);
+        }
+
+        let mut best: Option<(usize, f64)> = None;
+        for (count, value) in buckets.into_values() {
+            if best.is_none_or(|(best_count, best_value)| {
+                count > best_count || (count == best_count && value < best_value)
+            }) {
+                best = Some((count, value));
+            }
+        }
+        best.map(|(_, value)| value)
+    }
+
+    /// Offer a sample to a bounded reservoir (Algorithm R).
+    ///
+    /// `seen` is the number of samples offered before this one. Once the
+    /// reservoir holds `cap` samples, each new sample replaces a random slot
+    /// with probability `cap / (seen + 1)`, keeping a uniform sample of
+    /// everything offered. Randomness is derived from `seen` alone so every
+    /// validator makes the same choice.
+    pub fn reservoir_insert(samples: &mut Vec<f64>, seen: u64, value: f64, cap: usize) {
+        if samples.len() < cap {
+            samples.push(value);
+            return;
+        }
+        if cap == 0 {
+            return;
+        }
+
+        // SplitMix64 finalizer
+        let mut z = seen.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        let slot = z % (seen + 1);
+        if slot < cap as u64 {
+            samples[slot as usize] = value;
+        }
+    }
+
     /// Aggregate values by type
     pub fn aggregate(values: &[f64], agg_type: &AggregationType) -> f64 {
         match agg_type {
@@ -204,6 +430,8 @@ impl AggregationEngine {
             AggregationType::Count => values.len() as f64,
             AggregationType::Percentile(p) => Self::percentile(values, *p),
             AggregationType::StandardDeviation => Self::std_dev(values),
+            AggregationType::Median => Self::median(values),
+            AggregationType::Mode => Self::mode(values).unwrap_or(0.0),
         }
     }
 
@@ -219,6 +447,21 @@ impl AggregationEngine {
         buckets
     }
 
+    /// Uniformly subsample a series down to at most `max_samples` values.
+    ///
+    /// Picks evenly spaced indexes so two equal-length series subsampled with
+    /// the same cap stay paired. Caps below 2 are raised to 2, the minimum
+    /// needed for a correlation.
+    pub fn subsample(values: &[f64], max_samples: usize) -> Vec<f64> {
+        let max_samples = max_samples.max(2);
+        if values.len() <= max_samples {
+            return values.to_vec();
+        }
+        (0..max_samples)
+            .map(|i| values[i * values.len() / max_samples])
+            .collect()
+    }
+
     /// Compute correlation coefficient between two series
     pub fn correlation(x: &[f64], y: &[f64]) -> f64 {
         if x.len() != y.len() || x.len() < 2 {
@@ -264,6 +507,55 @@ mod tests {
         assert!((AggregationEngine::std_dev(&values) - 1.5811).abs() < 0.01);
     }
 
+    #[test]
+    fn test_subsampled_correlation_stays_close() {
+        let x: Vec<f64> = (0..100_000).map(|i| i as f64).collect();
+        let y: Vec<f64> = x
+            .iter()
+            .enumerate()
+            .map(|(i, v)| 2.0 * v + if i % 2 == 0 { 500.0 } else { -500.0 })
+            .collect();
+
+        let full = AggregationEngine::correlation(&x, &y);
+        let sx = AggregationEngine::subsample(&x, 1000);
+        let sy = AggregationEngine::subsample(&y, 1000);
+        assert_eq!(sx.len(), 1000);
+        assert_eq!(sy.len(), 1000);
+
+        let approx = AggregationEngine::correlation(&sx, &sy);
+        assert!(full > 0.99);
+        assert!((full - approx).abs() < 0.01);
+
+        // Short series are left untouched
+        assert_eq!(AggregationEngine::subsample(&[1.0, 2.0, 3.0], 10).len(), 3);
+    }
+
+    #[test]
+    fn test_subsampled_matrix_pairs_only_equal_length_series() {
+        let rising: Vec<f64> = (0..3000).map(|i| i as f64).collect();
+        let doubled: Vec<f64> = rising.iter().map(|v| 2.0 * v).collect();
+        // Subsampled to the same length as the others, but from other points
+        let shorter: Vec<f64> = (0..2000).map(|i| i as f64).collect();
+        let matrix = CorrelationMatrix::from_series(
+            vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            "m".to_string(),
+            &[rising, doubled, shorter],
+            Some(100),
+        );
+        assert_eq!(matrix.sampled_size, Some(100));
+        assert!((matrix.coefficients[1] - 1.0).abs() < 1e-9);
+        assert_eq!(matrix.coefficients[2], 0.0);
+        assert_eq!(matrix.coefficients[5], 0.0);
+
+        let unsampled = CorrelationMatrix::from_series(
+            vec!["a".to_string()],
+            "m".to_string(),
+            &[vec![1.0, 2.0, 3.0]],
+            Some(100),
+        );
+        assert_eq!(unsampled.sampled_size, None);
+    }
+
     #[test]
     fn test_moving_average() {
         let values = vec![(0, 1.0), (1, 2.0), (2, 3.0), (3, 4.0), (4, 5.0)];
@@ -275,6 +567,24 @@ mod tests {
         assert!((ma[2].value - 4.0).abs() < 0.001); // avg(3,4,5)
     }
 
+    #[test]
+    fn test_ewma() {
+        let values = vec![(0, 10.0), (1, 20.0), (2, 20.0), (3, 0.0)];
+        let ewma = AggregationEngine::ewma(&values, 0.5);
+
+        assert_eq!(ewma.len(), 4);
+        assert!((ewma[0].value - 10.0).abs() < 0.001); // first value
+        assert!((ewma[1].value - 15.0).abs() < 0.001); // 0.5*20 + 0.5*10
+        assert!((ewma[2].value - 17.5).abs() < 0.001); // 0.5*20 + 0.5*15
+        assert!((ewma[3].value - 8.75).abs() < 0.001); // 0.5*0 + 0.5*17.5
+        assert_eq!((ewma[3].timestamp, ewma[3].window_size), (3, 4));
+
+        // alpha = 1 follows the input exactly
+        let raw = AggregationEngine::ewma(&values, 1.0);
+        assert!(raw.iter().zip(&values).all(|(p, (_, v))| p.value == *v));
+        assert!(AggregationEngine::ewma(&[], 0.5).is_empty());
+    }
+
     #[test]
     fn test_anomaly_detection() {
         let values = vec![
@@ -306,4 +616,86 @@ mod tests {
         // For 10 values, index = round(0.9 * 9) = round(8.1) = 8, so value is 9.0
         assert!((AggregationEngine::percentile(&values, 0.9) - 9.0).abs() < 0.001); // 90th percentile
     }
+
+    #[test]
+    fn test_median() {
+        let odd = vec![7.0, 1.0, 3.0, 1000.0, 2.0];
+        assert!((AggregationEngine::median(&odd) - 3.0).abs() < 0.001);
+
+        // Even length averages the middle two: (4.0 + 5.0) / 2
+        let even = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0];
+        assert!((AggregationEngine::median(&even) - 4.5).abs() < 0.001);
+        assert!((AggregationEngine::aggregate(&even, &AggregationType::Median) - 4.5).abs() < 0.001);
+        assert_eq!(AggregationEngine::median(&[]), 0.0);
+    }
+
+    #[test]
+    fn test_mode() {
+        let values = vec![1.0, 2.0, 2.0, 3.0, 3.0, 3.0];
+        assert_eq!(AggregationEngine::mode(&values), Some(3.0));
+
+        // Float noise within the tolerance lands in one bucket
+        let noisy = vec![0.1 + 0.2, 0.3, 0.5, f64::NAN];
+        assert!((AggregationEngine::mode(&noisy).unwrap() - 0.3).abs() < 0.001);
+
+        // Ties go to the smallest value
+        assert_eq!(AggregationEngine::mode(&[4.0, 1.0, 4.0, 1.0]), Some(1.0));
+        assert_eq!(AggregationEngine::mode(&[f64::NAN]), None);
+        assert_eq!(AggregationEngine::mode(&[-2.0, 3.0, 3.0, -2.0]), Some(-2.0));
+
+        // Large values keep their own buckets
+        assert_eq!(AggregationEngine::mode(&[1e10, 2e10, 2e10]), Some(2e10));
+        assert_eq!(AggregationEngine::mode(&[1e300, -1e300, 1e300]), Some(1e300));
+        assert_eq!(AggregationEngine::mode(&[4e15, 4e15 + 1.0, 9e15, 9e15 + 2.0, 4e15 + 2.0]), Some(4e15));
+        assert_eq!(AggregationEngine::aggregate(&values, &AggregationType::Mode), 3.0);
+    }
+
+    #[test]
+    fn test_empty_aggregation_reports_no_data() {
+        for aggregation in [AggregationType::Sum, AggregationType::Min, AggregationType::Max] {
+            let result = AggregatedResult::from_values("m".to_string(), aggregation, &[]);
+            assert_eq!(result.sample_count, 0);
+            assert_eq!(result.observed_value(), None);
+            assert_eq!(result.value, 0.0);
+        }
+
+        let zero = AggregatedResult::from_values("m".to_string(), AggregationType::Sum, &[0.0, 0.0]);
+        assert_eq!(zero.observed_value(), Some(0.0));
+    }
+
+    #[test]
+    fn test_round_to_places() {
+        let mut matrix = CorrelationMatrix {
+            chains: vec!["a".to_string(), "b".to_string()],
+            coefficients: vec![1.0, 0.99999998, 0.99999998, 1.0],
+            metric: "m".to_string(),
+            sampled_size: None,
+        };
+        let raw = matrix.coefficients.clone();
+        assert_eq!(raw[1], 0.99999998);
+
+        matrix.round(2);
+        assert_eq!(matrix.coefficients[1], 1.0);
+        assert_eq!(AggregationEngine::round_to(-0.123456, 3), -0.123);
+        assert_eq!(AggregationEngine::round_to(2.5, 0), 3.0);
+        assert!(AggregationEngine::round_to(f64::NAN, 2).is_nan());
+        assert_eq!(AggregationEngine::round_to(f64::MAX, 10), f64::MAX);
+    }
+
+    #[test]
+    fn test_percentile_is_deterministic() {
+        let values = vec![3.0, 1.0, 2.0, 2.0, f64::NAN, 5.0, 2.0, -0.0, 0.0];
+        let mut reversed = values.clone();
+        reversed.reverse();
+
+        for p in [0.0, 0.25, 0.5, 0.75, 0.9] {
+            let a = AggregationEngine::percentile(&values, p);
+            let b = AggregationEngine::percentile(&reversed, p);
+            assert_eq!(a.to_bits(), b.to_bits());
+        }
+
+        // Duplicates resolve to the same value; NaN sorts last
+        assert_eq!(AggregationEngine::percentile(&values, 0.5), 2.0);
+        assert!(AggregationEngine::percentile(&values, 1.0).is_nan());
+    }
 }
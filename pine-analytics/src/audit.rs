@@ -0,0 +1,82 @@
+//! Audit Log for Pine Analytics
+//!
+//! Who changed roles or ran admin operations, and when.
+
+use serde::{Deserialize, Serialize};
+
+use crate::state::{AnalyticsState, Owner, Pagination};
+use crate::Operation;
+
+/// A recorded role change or admin operation
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AuditEntry {
+    /// Block height at which the operation was applied
+    pub block: u64,
+    /// Owner that submitted the operation
+    pub actor: Owner,
+    /// Operation name, e.g. `AssignRole`
+    pub action: String,
+    /// Operation arguments
+    pub detail: String,
+}
+
+impl Operation {
+    /// Action name and detail to audit for this operation, if it is audited
+    pub fn audit_record(&self) -> Option<(&'static str, String)> {
+        match self {
+            Operation::AssignRole { target, role } => {
+                Some(("AssignRole", format!("{:?} -> {:?}", target, role)))
+            }
+            Operation::AssignRoleTemporary {
+                target,
+                role,
+                expires_at_block,
+            } => Some((
+                "AssignRoleTemporary",
+                format!("{:?} -> {:?} until block {}", target, role, expires_at_block),
+            )),
+            Operation::RemoveRole { target } => Some(("RemoveRole", format!("{:?}", target))),
+            Operation::DefineCustomRole { name, permissions } => {
+                Some(("DefineCustomRole", format!("{} -> {:?}", name, permissions)))
+            }
+            Operation::DeleteCustomRole { name } => Some(("DeleteCustomRole", name.clone())),
+            Operation::ImportRBAC { snapshot } => Some((
+                "ImportRBAC",
+                format!("{} role assignments", snapshot.roles.len()),
+            )),
+            Operation::AdminAction { action } => Some(("AdminAction", format!("{:?}", action))),
+            _ => None,
+        }
+    }
+}
+
+impl AnalyticsState {
+    /// Audit an applied operation; a batch audits each of its operations
+    pub fn record_audit(&mut self, actor: Owner, operation: &Operation) {
+        if let Operation::Batch { operations, .. } = operation {
+            for operation in operations {
+                self.record_audit(actor, operation);
+            }
+            return;
+        }
+        if let Some((action, detail)) = operation.audit_record() {
+            self.audit_log.push(AuditEntry {
+                block: self.current_block,
+                actor,
+                action: action.to_string(),
+                detail,
+            });
+        }
+    }
+
+    /// Audit entries oldest first, optionally only those by `actor`
+    pub fn audit_entries(&self, pagination: &Pagination, actor: Option<&Owner>) -> Vec<AuditEntry> {
+        self.audit_log
+            .iter()
+            .filter(|entry| actor.is_none_or(|actor| entry.actor == *actor))
+            .skip(pagination.offset)
+            .take(pagination.limit)
+            .cloned()
+            .collect()
+    }
+}
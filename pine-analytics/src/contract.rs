@@ -4,20 +4,21 @@
 
 use hex::decode;
 use linera_sdk::abi::WithContractAbi;
-use linera_sdk::linera_base_types::CryptoHash;
+use linera_sdk::views::ViewStorageContext;
 use linera_sdk::{Contract, ContractRuntime};
 use pine_analytics::{
-    AdminOperation, AggregatedResult, AggregationQuery, AnalyticsAbi, AnalyticsState, AppConfig,
-    ApplicationId, CapturedEvent, ChainId, Message, MetricDefinition, MetricKey,
-    MetricValue, MerkleProof, Operation, OperationResponse, Owner, Permission, RateLimitConfig,
-    Result, Role, TransactionRecord,
+    AdminOperation, AnalyticsAbi, AnalyticsConfig, AnalyticsState, AnalyticsStorage, AppConfig,
+    ApplicationId, BatchRun, CapturedEvent, ChainId, Message, MetricDefinition, MetricKey,
+    MetricValue, Operation, OperationResponse, Owner, Permission, RateLimitConfig, Result, Role,
+    TransactionRecord,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 
 /// Analytics contract with advanced features
 pub struct AnalyticsContract {
     state: AnalyticsState,
-    #[allow(dead_code)]
+    storage: AnalyticsStorage<ViewStorageContext>,
     runtime: ContractRuntime<Self>,
 }
 
@@ -33,6 +34,8 @@ pub struct InstantiateArgs {
     pub admin_owner: Option<String>,
     #[serde(default)]
     pub rate_limit_config: Option<RateLimitConfig>,
+    #[serde(default)]
+    pub config: Option<AnalyticsConfig>,
 }
 
 impl Contract for AnalyticsContract {
@@ -41,9 +44,16 @@ impl Contract for AnalyticsContract {
     type Parameters = ();
     type EventValue = ();
 
-    async fn load(runtime: ContractRuntime<Self>) -> Self {
-        let state = AnalyticsState::default();
-        Self { state, runtime }
+    async fn load(mut runtime: ContractRuntime<Self>) -> Self {
+        let mut storage = AnalyticsStorage::load(runtime.root_view_storage_context())
+            .await
+            .expect("Failed to load state");
+        let state = storage.read_state().await.expect("Failed to read state");
+        Self {
+            state,
+            storage,
+            runtime,
+        }
     }
 
     async fn instantiate(&mut self, args: InstantiateArgs) {
@@ -63,28 +73,202 @@ impl Contract for AnalyticsContract {
 
         // Apply custom rate limit config if provided
         if let Some(config) = args.rate_limit_config {
-            self.state.rate_limiter.update_config(config);
+            if let Err(err) = self.state.rate_limiter.try_update_config(config) {
+                panic!("Invalid rate_limit_config: {err}");
+            }
+        }
+
+        if let Some(config) = args.config {
+            self.state.apply_config(config);
         }
     }
 
     async fn execute_operation(&mut self, operation: Operation) -> Self::Response {
+        // Expire temporary role grants before checking permissions
+        let block = self.runtime.block_height().0;
+        self.state.set_block_height(block);
+
         // Get caller for permission checks
-        let caller = self.get_caller();
-
-        // Helper macro-like closure to check permission and return early on error
-        let check_perm = |rbac: &pine_analytics::RBACState, owner: &Owner, perm: &Permission| -> Option<OperationResponse> {
-            if !rbac.has_permission(owner, perm) {
-                Some(OperationResponse {
-                    success: false,
-                    event_id: None,
-                    error: Some("Unauthorized".to_string()),
-                })
-            } else {
-                None
-            }
+        let Some(caller) = self.get_caller() else {
+            let error = pine_analytics::AnalyticsError::Unauthenticated;
+            return OperationResponse { success: false, event_id: None, error: Some(error.to_string()), results: Vec::new() };
         };
 
+        if let Err(e) = self.state.check_maintenance(&operation) {
+            return OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() };
+        }
+
+        let logged = operation.is_logged().then(|| operation.clone());
+        let response = self.dispatch_operation(caller, operation).await;
+        if let Some(operation) = logged.and_then(|operation| operation.logged_part(&response)) {
+            self.state.record_audit(caller, &operation);
+            self.state.log_operation(caller, operation);
+        }
+        response
+    }
+
+    async fn execute_message(&mut self, message: Message) {
+        let block = self.runtime.block_height().0;
+        self.state.set_block_height(block);
+        match message {
+            // === Basic Notifications ===
+            Message::EventNotification { event } => {
+                if let Err(e) = self.capture_event_with_checks(event).await {
+                    self.soft_failure("Event notification", e);
+                }
+            }
+            Message::TransactionNotification { transaction } => {
+                if let Err(e) = self.capture_transaction(transaction).await {
+                    self.soft_failure("Transaction notification", e);
+                }
+            }
+            Message::Subscribe { application_id } => {
+                log::info!("Subscription established for app: {:?}", application_id);
+            }
+            Message::Unsubscribe { application_id } => {
+                log::info!("Unsubscribed from app: {:?}", application_id);
+            }
+
+            // === Cross-Chain Aggregation ===
+            Message::AggregationRequest {
+                request_id,
+                source_chain,
+                metric_queries,
+                callback_chain,
+            } => {
+                let response = self
+                    .state
+                    .answer_aggregation_request(request_id, &metric_queries);
+                if let Message::AggregationResponse { results, .. } = &response {
+                    log::info!(
+                        "Processed aggregation request {} from {:?}, {} results",
+                        request_id,
+                        source_chain,
+                        results.len()
+                    );
+                }
+                self.runtime.send_message(callback_chain, response);
+            }
+            Message::AggregationResponse {
+                request_id,
+                results,
+                proof: _,
+            } => {
+                let without_data = results
+                    .iter()
+                    .filter(|result| result.observed_value().is_none())
+                    .count();
+                log::info!(
+                    "Received aggregation response {} with {} results ({} without data)",
+                    request_id,
+                    results.len(),
+                    without_data
+                );
+            }
+
+            // === Chain Synchronization ===
+            Message::SyncRequest {
+                from_event_id,
+                to_chain,
+            } => {
+                let batches = self.state.sync_batches(from_event_id);
+                log::info!(
+                    "Sync request from event {} to {:?}, {} batches",
+                    from_event_id,
+                    to_chain,
+                    batches.len()
+                );
+                for batch in batches {
+                    self.runtime.send_message(to_chain, batch);
+                }
+            }
+            Message::SyncBatch { events, batch_proof: _ } => {
+                match self.capture_event_batch(events).await {
+                    Ok(_) => log::info!("Processed sync batch"),
+                    Err(e) => self.soft_failure("Sync batch", e),
+                }
+            }
+
+            // === Replication ===
+            Message::OperationLogBatch { entries } => {
+                let Some(origin) = self.runtime.message_origin_chain_id() else {
+                    log::warn!("Operation log batch without an origin chain refused");
+                    return;
+                };
+                match self.state.apply_replicated_log(origin, entries) {
+                    Ok(applied) => log::info!("Applied {} replicated operations", applied),
+                    Err(e) => self.soft_failure("Operation log replication", e),
+                }
+            }
+        }
+    }
+
+    async fn store(mut self) {
+        self.storage
+            .persist(&mut self.state)
+            .await
+            .expect("Failed to save state");
+    }
+}
+
+// Helper methods
+impl AnalyticsContract {
+    /// The authenticated signer of the current operation, if any
+    fn get_caller(&mut self) -> Option<Owner> {
+        self.runtime.authenticated_signer()
+    }
+
+    /// Handle a failure that is skipped outside strict mode.
+    ///
+    /// Messages cannot return errors, so strict mode aborts by panicking,
+    /// which rejects the message instead of half-applying it.
+    fn soft_failure(&self, context: &str, error: pine_analytics::AnalyticsError) {
+        if self.state.config.strict_mode {
+            panic!("{} failed in strict mode: {}", context, error);
+        }
+        log::warn!("{} failed: {}", context, error);
+    }
+
+    fn parse_address20(bytes: &[u8]) -> Owner {
+        let mut addr = [0u8; 20];
+        addr.copy_from_slice(bytes);
+        Owner::Address20(addr)
+    }
+
+    fn parse_owner_from_hex(value: &str) -> core::result::Result<Owner, &'static str> {
+        let trimmed = value.trim();
+        if trimmed.is_empty() {
+            return Err("value is empty");
+        }
+
+        let hex_str = trimmed.strip_prefix("0x").unwrap_or(trimmed);
+        let bytes = decode(hex_str).map_err(|_| "invalid hex string")?;
+        if bytes.len() < 20 {
+            return Err("admin_owner must be at least 20 bytes");
+        }
+
+        let start = bytes.len() - 20;
+        let mut owner_bytes = [0u8; 20];
+        owner_bytes.copy_from_slice(&bytes[start..]);
+
+        Ok(Self::parse_address20(&owner_bytes))
+    }
+}
+
+// Operation Dispatch
+impl AnalyticsContract {
+    /// Permission-check and apply a single operation
+    async fn dispatch_operation(&mut self, caller: Owner, operation: Operation) -> OperationResponse {
         // Check permission first, then execute operation
+        if self.state.rbac.authorize(&caller, &operation).is_err() {
+            return OperationResponse {
+                success: false,
+                event_id: None,
+                error: Some("Unauthorized".to_string()),
+                results: Vec::new(),
+            };
+        }
+
         match operation {
             // === Application Management ===
             Operation::AddMonitoredApp {
@@ -92,277 +276,234 @@ impl Contract for AnalyticsContract {
                 chain_id,
                 graphql_endpoint,
             } => {
-                if let Some(err) = check_perm(&self.state.rbac, &caller, &Permission::AddApplication) {
-                    return err;
-                }
                 match self.add_monitored_app(application_id, chain_id, graphql_endpoint).await {
-                    Ok(_) => OperationResponse { success: true, event_id: None, error: None },
-                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()) },
+                    Ok(_) => OperationResponse { success: true, event_id: None, error: None, results: Vec::new() },
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
                 }
             }
             Operation::RemoveMonitoredApp { application_id } => {
-                if let Some(err) = check_perm(&self.state.rbac, &caller, &Permission::RemoveApplication) {
-                    return err;
-                }
                 match self.remove_monitored_app(application_id).await {
-                    Ok(_) => OperationResponse { success: true, event_id: None, error: None },
-                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()) },
+                    Ok(_) => OperationResponse { success: true, event_id: None, error: None, results: Vec::new() },
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
                 }
             }
             Operation::UpdateAppConfig {
                 application_id,
                 config,
             } => {
-                if let Some(err) = check_perm(&self.state.rbac, &caller, &Permission::AddApplication) {
-                    return err;
-                }
                 match self.update_app_config(application_id, config).await {
-                    Ok(_) => OperationResponse { success: true, event_id: None, error: None },
-                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()) },
+                    Ok(_) => OperationResponse { success: true, event_id: None, error: None, results: Vec::new() },
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
+                }
+            }
+
+            Operation::PatchAppConfig {
+                application_id,
+                patch,
+            } => {
+                match self.state.patch_app_config(&application_id, &patch) {
+                    Ok(_) => OperationResponse { success: true, event_id: None, error: None, results: Vec::new() },
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
+                }
+            }
+
+            Operation::MergeApplications { from_id, into_id } => {
+                match self.state.merge_applications(&from_id, &into_id) {
+                    Ok(merged) => {
+                        log::info!("Merged {} events from {:?} into {:?}", merged, from_id, into_id);
+                        OperationResponse { success: true, event_id: None, error: None, results: Vec::new() }
+                    }
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
                 }
             }
 
             // === Event Capture ===
             Operation::CaptureEvent { event } => {
-                if let Some(err) = check_perm(&self.state.rbac, &caller, &Permission::CaptureEvents) {
-                    return err;
-                }
                 match self.capture_event_with_checks(event).await {
-                    Ok(id) => OperationResponse { success: true, event_id: id, error: None },
-                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()) },
+                    Ok(id) => OperationResponse { success: true, event_id: id, error: None, results: Vec::new() },
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
                 }
             }
             Operation::CaptureTransaction { transaction } => {
-                if let Some(err) = check_perm(&self.state.rbac, &caller, &Permission::CaptureEvents) {
-                    return err;
-                }
                 match self.capture_transaction(transaction).await {
-                    Ok(_) => OperationResponse { success: true, event_id: None, error: None },
-                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()) },
+                    Ok(_) => OperationResponse { success: true, event_id: None, error: None, results: Vec::new() },
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
                 }
             }
             Operation::CaptureEventBatch { events } => {
-                if let Some(err) = check_perm(&self.state.rbac, &caller, &Permission::CaptureEvents) {
-                    return err;
-                }
                 match self.capture_event_batch(events).await {
-                    Ok(id) => OperationResponse { success: true, event_id: id, error: None },
-                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()) },
+                    Ok(id) => OperationResponse { success: true, event_id: id, error: None, results: Vec::new() },
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
+                }
+            }
+
+            Operation::ImportEvents {
+                events,
+                on_conflict,
+            } => {
+                match self.state.import_events(events, on_conflict) {
+                    Ok(report) => OperationResponse::from_import(&report),
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
+                }
+            }
+            Operation::PinEvent { event_id, pinned } => {
+                match self.state.pin_event(event_id, pinned) {
+                    Ok(_) => OperationResponse { success: true, event_id: Some(event_id), error: None, results: Vec::new() },
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
                 }
             }
 
             // === Metric Management ===
             Operation::UpdateMetric { key, value } => {
-                if let Some(err) = check_perm(&self.state.rbac, &caller, &Permission::ModifyMetrics) {
-                    return err;
-                }
                 match self.update_metric(key, value).await {
-                    Ok(_) => OperationResponse { success: true, event_id: None, error: None },
-                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()) },
+                    Ok(_) => OperationResponse { success: true, event_id: None, error: None, results: Vec::new() },
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
                 }
             }
             Operation::DefineMetric { definition } => {
-                if let Some(err) = check_perm(&self.state.rbac, &caller, &Permission::ModifyMetrics) {
-                    return err;
-                }
                 match self.define_metric(definition).await {
-                    Ok(_) => OperationResponse { success: true, event_id: None, error: None },
-                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()) },
+                    Ok(_) => OperationResponse { success: true, event_id: None, error: None, results: Vec::new() },
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
+                }
+            }
+            Operation::UpdateMetricDefinition { name, patch } => {
+                match self.state.update_metric_definition(&name, &patch) {
+                    Ok(_) => OperationResponse { success: true, event_id: None, error: None, results: Vec::new() },
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
+                }
+            }
+            Operation::SaveQuery { name, request } => {
+                match self.state.save_query(name, request) {
+                    Ok(_) => OperationResponse { success: true, event_id: None, error: None, results: Vec::new() },
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
                 }
             }
 
             // === Admin Operations ===
             Operation::AdminAction { action } => {
-                if let Some(err) = check_perm(&self.state.rbac, &caller, &Permission::ConfigureSystem) {
-                    return err;
+                if let AdminOperation::ClearEvents { token } = &action {
+                    if let Err(e) = self.state.check_clear_events_token(token.as_deref()) {
+                        return OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() };
+                    }
                 }
                 match self.execute_admin_action(action).await {
-                    Ok(_) => OperationResponse { success: true, event_id: None, error: None },
-                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()) },
+                    Ok(_) => OperationResponse { success: true, event_id: None, error: None, results: Vec::new() },
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
                 }
             }
 
             // === RBAC Operations ===
             Operation::AssignRole { target, role } => {
-                if let Some(err) = check_perm(&self.state.rbac, &caller, &Permission::ManageRoles) {
-                    return err;
-                }
                 match self.assign_role(&caller, target, role).await {
-                    Ok(_) => OperationResponse { success: true, event_id: None, error: None },
-                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()) },
+                    Ok(_) => OperationResponse { success: true, event_id: None, error: None, results: Vec::new() },
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
                 }
             }
-            Operation::RemoveRole { target } => {
-                if let Some(err) = check_perm(&self.state.rbac, &caller, &Permission::ManageRoles) {
-                    return err;
+            Operation::AssignRoleTemporary { target, role, expires_at_block } => {
+                match self.assign_role_until(&caller, target, role, expires_at_block).await {
+                    Ok(_) => OperationResponse { success: true, event_id: None, error: None, results: Vec::new() },
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
                 }
+            }
+            Operation::RemoveRole { target } => {
                 match self.remove_role(&caller, &target).await {
-                    Ok(_) => OperationResponse { success: true, event_id: None, error: None },
-                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()) },
+                    Ok(_) => OperationResponse { success: true, event_id: None, error: None, results: Vec::new() },
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
+                }
+            }
+            Operation::DefineCustomRole { name, permissions } => {
+                match self.define_custom_role(&caller, name, permissions).await {
+                    Ok(_) => OperationResponse { success: true, event_id: None, error: None, results: Vec::new() },
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
+                }
+            }
+            Operation::DeleteCustomRole { name } => {
+                match self.delete_custom_role(&caller, &name).await {
+                    Ok(_) => OperationResponse { success: true, event_id: None, error: None, results: Vec::new() },
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
+                }
+            }
+
+            Operation::ImportRBAC { snapshot } => {
+                match self.state.rbac.import(snapshot) {
+                    Ok(_) => OperationResponse { success: true, event_id: None, error: None, results: Vec::new() },
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
                 }
             }
 
             // === Rate Limit Control ===
             Operation::UpdateRateLimitConfig { config } => {
-                if let Some(err) = check_perm(&self.state.rbac, &caller, &Permission::ControlIngestion) {
-                    return err;
+                match self.state.rate_limiter.try_update_config(config) {
+                    Ok(_) => OperationResponse { success: true, event_id: None, error: None, results: Vec::new() },
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
                 }
-                self.state.rate_limiter.update_config(config);
-                OperationResponse { success: true, event_id: None, error: None }
             }
             Operation::PauseIngestion => {
-                if let Some(err) = check_perm(&self.state.rbac, &caller, &Permission::ControlIngestion) {
-                    return err;
-                }
                 self.state.rate_limiter.pause();
                 log::info!("Ingestion paused by {:?}", caller);
-                OperationResponse { success: true, event_id: None, error: None }
+                OperationResponse { success: true, event_id: None, error: None, results: Vec::new() }
             }
             Operation::ResumeIngestion => {
-                if let Some(err) = check_perm(&self.state.rbac, &caller, &Permission::ControlIngestion) {
-                    return err;
-                }
                 self.state.rate_limiter.resume();
                 log::info!("Ingestion resumed by {:?}", caller);
-                OperationResponse { success: true, event_id: None, error: None }
+                OperationResponse { success: true, event_id: None, error: None, results: Vec::new() }
             }
             Operation::UnblockApp { application_id } => {
-                if let Some(err) = check_perm(&self.state.rbac, &caller, &Permission::ControlIngestion) {
-                    return err;
-                }
                 self.state.rate_limiter.unblock_app(&application_id);
-                OperationResponse { success: true, event_id: None, error: None }
-            }
-        }
-    }
-
-    async fn execute_message(&mut self, message: Message) {
-        match message {
-            // === Basic Notifications ===
-            Message::EventNotification { event } => {
-                let _ = self.capture_event_internal(event).await;
+                OperationResponse { success: true, event_id: None, error: None, results: Vec::new() }
             }
-            Message::TransactionNotification { transaction } => {
-                let _ = self.capture_transaction(transaction).await;
-            }
-            Message::Subscribe { application_id } => {
-                log::info!("Subscription established for app: {:?}", application_id);
+            Operation::SetRateLimitOverrides { overrides, merge } => {
+                log::info!("Setting {} rate limit overrides (merge: {})", overrides.len(), merge);
+                match self.state.rate_limiter.set_overrides(overrides, merge) {
+                    Ok(_) => OperationResponse { success: true, event_id: None, error: None, results: Vec::new() },
+                    Err(e) => OperationResponse { success: false, event_id: None, error: Some(e.to_string()), results: Vec::new() },
+                }
             }
-            Message::Unsubscribe { application_id } => {
-                log::info!("Unsubscribed from app: {:?}", application_id);
+            Operation::ClearRateLimitOverrides => {
+                self.state.rate_limiter.clear_overrides();
+                OperationResponse { success: true, event_id: None, error: None, results: Vec::new() }
             }
 
-            // === Cross-Chain Aggregation ===
-            Message::AggregationRequest {
-                request_id,
-                source_chain,
-                metric_queries,
-                callback_chain: _,
+            // === Replication ===
+            Operation::ReplicateOperationLog {
+                target_chain,
+                from_index,
             } => {
-                let results = self.process_aggregation_queries(&metric_queries).await;
-                let _proof = self.state.merkle_index.get_root().map(|root| MerkleProof {
-                    path: vec![],
-                    leaf_hash: root,
-                    event_id: 0,
-                });
-
-                // Send response back (in production, use runtime.send_message)
+                let entries = self.state.operation_log_since(from_index);
                 log::info!(
-                    "Processed aggregation request {} from {:?}, {} results",
-                    request_id,
-                    source_chain,
-                    results.len()
+                    "Replicating {} operation log entries to {:?}",
+                    entries.len(),
+                    target_chain
                 );
+                self.runtime
+                    .send_message(target_chain, Message::OperationLogBatch { entries });
+                OperationResponse { success: true, event_id: None, error: None, results: Vec::new() }
             }
-            Message::AggregationResponse {
-                request_id,
-                results,
-                proof: _,
-            } => {
-                log::info!(
-                    "Received aggregation response {} with {} results",
-                    request_id,
-                    results.len()
-                );
+            Operation::SetReplicationSource { source } => {
+                self.state.replication_source = source;
+                log::info!("Replication source set to {:?}", source);
+                OperationResponse { success: true, event_id: None, error: None, results: Vec::new() }
+            }
+            Operation::TruncateOperationLog { before_index } => {
+                let removed = self.state.truncate_operation_log(before_index);
+                log::info!("Truncated {} operation log entries", removed);
+                OperationResponse { success: true, event_id: None, error: None, results: Vec::new() }
             }
 
-            // === Chain Synchronization ===
-            Message::SyncRequest {
-                from_event_id,
-                to_chain,
+            // === Batching ===
+            Operation::Batch {
+                operations,
+                stop_on_error,
             } => {
-                let events: Vec<CapturedEvent> = self
-                    .state
-                    .events
-                    .iter()
-                    .filter(|e| e.id >= from_event_id)
-                    .cloned()
-                    .collect();
-
-                log::info!(
-                    "Sync request from event {} to {:?}, {} events",
-                    from_event_id,
-                    to_chain,
-                    events.len()
-                );
-            }
-            Message::SyncBatch { events, batch_proof: _ } => {
-                for event in events {
-                    let _ = self.capture_event_internal(event).await;
+                let mut run = BatchRun::new(operations, stop_on_error);
+                while let Some(operation) = run.next_operation() {
+                    let result = Box::pin(self.dispatch_operation(caller, operation)).await;
+                    run.record(result);
                 }
-                log::info!("Processed sync batch");
+                run.finish()
             }
         }
     }
-
-    async fn store(self) {
-        // In production with views, state would be persisted here
-        // For now, state management is handled in-memory
-    }
-}
-
-// Helper methods
-impl AnalyticsContract {
-    /// Get the caller's owner address
-    fn get_caller(&self) -> Owner {
-        // In production, get from runtime
-        self.state.admin_owner.clone()
-    }
-
-    /// Check if caller has required permission
-    #[allow(dead_code)]
-    fn require_permission(&self, caller: &Owner, permission: &Permission) -> Result<()> {
-        if self.state.rbac.has_permission(caller, permission) {
-            Ok(())
-        } else {
-            Err(pine_analytics::AnalyticsError::Unauthorized)
-        }
-    }
-
-    fn parse_address20(bytes: &[u8]) -> Owner {
-        let mut addr = [0u8; 20];
-        addr.copy_from_slice(bytes);
-        Owner::Address20(addr)
-    }
-
-    fn parse_owner_from_hex(value: &str) -> core::result::Result<Owner, &'static str> {
-        let trimmed = value.trim();
-        if trimmed.is_empty() {
-            return Err("value is empty");
-        }
-
-        let hex_str = trimmed.strip_prefix("0x").unwrap_or(trimmed);
-        let bytes = decode(hex_str).map_err(|_| "invalid hex string")?;
-        if bytes.len() < 20 {
-            return Err("admin_owner must be at least 20 bytes");
-        }
-
-        let start = bytes.len() - 20;
-        let mut owner_bytes = [0u8; 20];
-        owner_bytes.copy_from_slice(&bytes[start..]);
-
-        Ok(Self::parse_address20(&owner_bytes))
-    }
 }
 
 // Application Management
@@ -373,10 +514,8 @@ impl AnalyticsContract {
         chain_id: ChainId,
         graphql_endpoint: String,
     ) -> Result<()> {
-        let config = AppConfig::new(application_id.clone(), chain_id, graphql_endpoint);
-        self.state
-            .monitored_applications
-            .insert(application_id.clone(), config);
+        let config = AppConfig::new(application_id, chain_id, graphql_endpoint);
+        self.state.upsert_app_config(config)?;
         log::info!("Added monitored application: {:?}", application_id);
         Ok(())
     }
@@ -392,9 +531,12 @@ impl AnalyticsContract {
         application_id: ApplicationId,
         config: AppConfig,
     ) -> Result<()> {
-        self.state
-            .monitored_applications
-            .insert(application_id.clone(), config);
+        if config.application_id != application_id {
+            return Err(pine_analytics::AnalyticsError::ConfigError(
+                "Config application_id does not match target application".to_string(),
+            ));
+        }
+        self.state.upsert_app_config(config)?;
         log::info!("Updated config for application: {:?}", application_id);
         Ok(())
     }
@@ -403,88 +545,26 @@ impl AnalyticsContract {
 // Event Capture with Rate Limiting and Deduplication
 impl AnalyticsContract {
     async fn capture_event_with_checks(&mut self, event: CapturedEvent) -> Result<Option<u64>> {
-        // Check for duplicates
-        if self.state.is_duplicate_tx(&event.transaction_hash) {
-            return Err(pine_analytics::AnalyticsError::DuplicateEvent(
-                event.transaction_hash.clone(),
-            ));
-        }
-
-        // Check rate limit
-        self.state
-            .rate_limiter
-            .check_and_increment(&event.source_app, self.state.current_block)?;
-
-        // Capture the event
-        self.capture_event_internal(event).await
-    }
-
-    async fn capture_event_internal(&mut self, mut event: CapturedEvent) -> Result<Option<u64>> {
-        // Assign event ID
-        event.id = self.state.next_event_id;
-        self.state.next_event_id += 1;
-        event.block_height = Some(self.state.current_block);
-
-        // Add to deduplication index
-        self.state.tx_hash_index.insert(event.transaction_hash.clone());
-
-        // Store event
-        self.state.events.push(event.clone());
-
-        // Update indexes
-        self.update_event_indexes(&event).await?;
-
-        // Update Merkle tree
-        let event_hash = CryptoHash::from(event.data_hash());
-        self.state.merkle_index.insert_hash(event.id, event_hash);
-
-        // Update statistics
-        self.state.total_events_captured += 1;
+        let now_ms = self.runtime.system_time().micros() / 1000;
+        let source_app = event.source_app;
+        let Some(event_id) = self.state.capture_event_checked(event, now_ms)? else {
+            log::debug!("Sampled out event from app {:?}", source_app);
+            return Ok(None);
+        };
 
-        log::info!("Captured event {} from app {:?}", event.id, event.source_app);
-        Ok(Some(event.id))
+        log::info!("Captured event {} from app {:?}", event_id, source_app);
+        Ok(Some(event_id))
     }
 
     async fn capture_event_batch(&mut self, events: Vec<CapturedEvent>) -> Result<Option<u64>> {
-        let mut last_id = None;
-        let mut _processed = 0;
-
-        for event in events {
-            match self.capture_event_with_checks(event).await {
-                Ok(id) => {
-                    last_id = id;
-                    _processed += 1;
-                }
-                Err(e) => {
-                    log::warn!("Batch event failed: {}", e);
-                    // Continue processing other events
-                }
-            }
-        }
-
-        Ok(last_id)
+        let now_ms = self.runtime.system_time().micros() / 1000;
+        self.state.capture_event_batch(events, now_ms)
     }
 
     async fn capture_transaction(&mut self, transaction: TransactionRecord) -> Result<()> {
-        log::info!("Captured transaction {}", transaction.hash);
-        Ok(())
-    }
-
-    async fn update_event_indexes(&mut self, event: &CapturedEvent) -> Result<()> {
-        // Update time index
-        self.state
-            .event_index
-            .entry(event.timestamp)
-            .or_insert_with(Vec::new)
-            .push(event.id);
-
-        // Update app index
-        self.state
-            .app_index
-            .entry(event.source_app.clone())
-            .or_insert_with(Vec::new)
-            .push(event.id);
-
+        let hash = transaction.hash.clone();
+        self.state.record_transaction(transaction)?;
+        log::info!("Captured transaction {}", hash);
         Ok(())
     }
 }
@@ -492,7 +572,7 @@ impl AnalyticsContract {
 // Metric Management
 impl AnalyticsContract {
     async fn update_metric(&mut self, key: MetricKey, value: MetricValue) -> Result<()> {
-        self.state.aggregated_metrics.insert(key.clone(), value);
+        self.state.update_metric(key.clone(), value);
         log::info!("Updated metric: {}", key);
         Ok(())
     }
@@ -509,61 +589,34 @@ impl AnalyticsContract {
 // Admin Operations
 impl AnalyticsContract {
     async fn execute_admin_action(&mut self, action: AdminOperation) -> Result<()> {
-        match action {
-            AdminOperation::PauseIngestion => {
-                self.state.rate_limiter.pause();
-                log::info!("Admin: Ingestion paused");
-            }
-            AdminOperation::ResumeIngestion => {
-                self.state.rate_limiter.resume();
-                log::info!("Admin: Ingestion resumed");
-            }
-            AdminOperation::SetRateLimit {
-                max_events_per_app_per_block,
-                max_total_events_per_block,
-            } => {
-                let mut config = self.state.rate_limiter.config.clone();
-                config.max_events_per_app_per_block = max_events_per_app_per_block;
-                config.max_total_events_per_block = max_total_events_per_block;
-                self.state.rate_limiter.update_config(config);
-                log::info!("Admin: Rate limit updated");
-            }
-            AdminOperation::ClearEvents => {
-                self.state.events.clear();
-                self.state.event_index.clear();
-                self.state.app_index.clear();
-                self.state.tx_hash_index.clear();
-                self.state.merkle_index = pine_analytics::MerkleIndex::new(16);
-                log::warn!("Admin: All events cleared!");
-            }
-            AdminOperation::RebuildMerkleIndex => {
-                self.state.merkle_index = pine_analytics::MerkleIndex::new(16);
-                for event in &self.state.events {
-                    let event_hash = CryptoHash::from(event.data_hash());
-                    self.state.merkle_index.insert_hash(event.id, event_hash);
-                }
-                log::info!("Admin: Merkle index rebuilt");
-            }
-            AdminOperation::TransferSuperAdmin { new_admin } => {
-                self.state.admin_owner = new_admin.clone();
-                self.state.rbac = pine_analytics::RBACState::new(new_admin);
-                log::warn!("Admin: Super admin transferred!");
-            }
-        }
-        Ok(())
+        self.state.execute_admin_action(action)
     }
 }
 
 // RBAC Operations
 impl AnalyticsContract {
     async fn assign_role(&mut self, caller: &Owner, target: Owner, role: Role) -> Result<()> {
-        if !self.state.rbac.can_manage(caller, &target) {
+        if !self.state.rbac.can_manage(caller, &target) || !self.state.rbac.can_grant(caller, &role) {
             return Err(pine_analytics::AnalyticsError::Unauthorized);
         }
         self.state.rbac.assign_role(target, role)?;
         Ok(())
     }
 
+    async fn assign_role_until(
+        &mut self,
+        caller: &Owner,
+        target: Owner,
+        role: Role,
+        expires_at_block: u64,
+    ) -> Result<()> {
+        if !self.state.rbac.can_manage(caller, &target) || !self.state.rbac.can_grant(caller, &role) {
+            return Err(pine_analytics::AnalyticsError::Unauthorized);
+        }
+        self.state.rbac.assign_role_until(target, role, expires_at_block)?;
+        Ok(())
+    }
+
     async fn remove_role(&mut self, caller: &Owner, target: &Owner) -> Result<()> {
         if !self.state.rbac.can_manage(caller, target) {
             return Err(pine_analytics::AnalyticsError::Unauthorized);
@@ -571,35 +624,26 @@ impl AnalyticsContract {
         self.state.rbac.remove_role(target)?;
         Ok(())
     }
-}
 
-// Cross-Chain Aggregation
-impl AnalyticsContract {
-    async fn process_aggregation_queries(&self, queries: &[AggregationQuery]) -> Vec<AggregatedResult> {
-        use pine_analytics::AggregationEngine;
-
-        queries
-            .iter()
-            .map(|query| {
-                let values: Vec<f64> = self
-                    .state
-                    .aggregated_metrics
-                    .iter()
-                    .filter(|(k, _)| k.contains(&query.metric))
-                    .map(|(_, v)| v.as_f64())
-                    .collect();
-
-                let value = AggregationEngine::aggregate(&values, &query.aggregation);
+    async fn define_custom_role(
+        &mut self,
+        caller: &Owner,
+        name: String,
+        permissions: BTreeSet<Permission>,
+    ) -> Result<()> {
+        // A caller cannot define or take over a role stronger than their own
+        if !self.state.rbac.can_define_custom_role(caller, &name, &permissions) {
+            return Err(pine_analytics::AnalyticsError::Unauthorized);
+        }
+        self.state.rbac.define_custom_role(name, permissions)?;
+        Ok(())
+    }
 
-                AggregatedResult {
-                    metric: query.metric.clone(),
-                    aggregation: query.aggregation.clone(),
-                    value,
-                    bucket: None,
-                    sample_count: values.len(),
-                }
-            })
-            .collect()
+    async fn delete_custom_role(&mut self, caller: &Owner, name: &str) -> Result<()> {
+        if !self.state.rbac.can_delete_custom_role(caller, name) {
+            return Err(pine_analytics::AnalyticsError::Unauthorized);
+        }
+        self.state.rbac.delete_custom_role(name)?;
+        Ok(())
     }
 }
-
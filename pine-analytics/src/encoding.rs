@@ -0,0 +1,79 @@
+//! Binary Response Encoding for Pine Analytics
+//!
+//! Compact alternatives to JSON for high-throughput clients.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AnalyticsError, Result};
+
+/// Supported binary encodings
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum BinaryFormat {
+    /// Concise Binary Object Representation (RFC 8949)
+    #[default]
+    Cbor,
+}
+
+/// A response body encoded in a binary format
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BinaryPayload {
+    pub format: BinaryFormat,
+    pub data: Vec<u8>,
+}
+
+impl BinaryPayload {
+    /// Encode a value in the given format
+    pub fn encode<T: Serialize>(format: BinaryFormat, value: &T) -> Result<Self> {
+        let mut data = Vec::new();
+        match format {
+            BinaryFormat::Cbor => ciborium::into_writer(value, &mut data)
+                .map_err(|e| AnalyticsError::EncodingError(e.to_string()))?,
+        }
+        Ok(Self { format, data })
+    }
+
+    /// Decode the payload back into a value
+    pub fn decode<T: DeserializeOwned>(&self) -> Result<T> {
+        match self.format {
+            BinaryFormat::Cbor => ciborium::from_reader(self.data.as_slice())
+                .map_err(|e| AnalyticsError::EncodingError(e.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{CapturedEvent, EventSeverity};
+    use linera_sdk::linera_base_types::{ApplicationId, ChainId, CryptoHash};
+
+    #[test]
+    fn test_cbor_round_trip_matches_json() {
+        let app = ApplicationId::new(CryptoHash::from([1u8; 32]));
+        let chain = ChainId(CryptoHash::from([2u8; 32]));
+        let mut events = Vec::new();
+        for i in 0..3u64 {
+            let mut event = CapturedEvent::new(
+                app,
+                chain,
+                1000 + i,
+                "transfer".to_string(),
+                serde_json::json!({"amount": i * 10, "ratio": 0.5, "tags": ["a", "b"], "meta": null}),
+                format!("tx_{}", i),
+            );
+            event.id = i;
+            event.severity = EventSeverity::Warning;
+            events.push(event);
+        }
+
+        let payload = BinaryPayload::encode(BinaryFormat::Cbor, &events).unwrap();
+        let decoded: Vec<CapturedEvent> = payload.decode().unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&decoded).unwrap(),
+            serde_json::to_value(&events).unwrap()
+        );
+        assert!(payload.data.len() < serde_json::to_vec(&events).unwrap().len());
+    }
+}
@@ -20,6 +20,9 @@ pub enum AnalyticsError {
     #[error("Unauthorized operation")]
     Unauthorized,
 
+    #[error("Operation has no authenticated signer")]
+    Unauthenticated,
+
     #[error("Invalid metric definition: {0}")]
     InvalidMetric(String),
 
@@ -29,6 +32,9 @@ pub enum AnalyticsError {
     #[error("Serialization error: {0}")]
     SerializationError(#[from] serde_json::Error),
 
+    #[error("Binary encoding error: {0}")]
+    EncodingError(String),
+
     // === New Error Types ===
 
     #[error("RBAC error: {0}")]
@@ -57,6 +63,9 @@ pub enum AnalyticsError {
 
     #[error("Configuration error: {0}")]
     ConfigError(String),
+
+    #[error("Contract is in maintenance mode")]
+    Maintenance,
 }
 
 pub type Result<T> = std::result::Result<T, AnalyticsError>;
@@ -1,9 +1,16 @@
 pub mod aggregations;
+pub mod audit;
+pub mod encoding;
 pub mod error;
 pub mod merkle;
+pub mod oplog;
+pub mod queries;
+pub mod query_cache;
+pub mod query_stats;
 pub mod rate_limit;
 pub mod rbac;
 pub mod state;
+pub mod storage;
 
 #[cfg(test)]
 mod tests;
@@ -11,13 +18,21 @@ mod tests;
 use linera_sdk::abi::{ContractAbi, ServiceAbi};
 use linera_sdk::linera_base_types::CryptoHash;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 
 pub use aggregations::*;
+pub use audit::*;
+pub use encoding::*;
 pub use error::{AnalyticsError, Result};
 pub use merkle::*;
+pub use oplog::*;
+pub use queries::*;
+pub use query_cache::*;
+pub use query_stats::*;
 pub use rate_limit::*;
 pub use rbac::*;
 pub use state::*;
+pub use storage::*;
 
 /// Application Binary Interface
 #[derive(Debug, Clone)]
@@ -42,6 +57,118 @@ pub struct OperationResponse {
     pub event_id: Option<u64>,
     /// Error message if operation failed
     pub error: Option<String>,
+    /// Per-operation results of a `Batch`, in order
+    #[serde(default)]
+    pub results: Vec<OperationResponse>,
+}
+
+impl OperationResponse {
+    /// Combine the results of a batch; it succeeds only if every entry did
+    pub fn from_batch(results: Vec<OperationResponse>) -> Self {
+        let failed = results.iter().filter(|result| !result.success).count();
+        Self {
+            success: failed == 0,
+            event_id: None,
+            error: (failed > 0).then(|| format!("{} of {} operations failed", failed, results.len())),
+            results,
+        }
+    }
+
+    /// Report an import with one result per event; skipped events are
+    /// reported as failed entries, while the import itself succeeds
+    pub fn from_import(report: &ImportReport) -> Self {
+        let results = report
+            .outcomes
+            .iter()
+            .map(|(event_id, outcome)| match outcome {
+                ImportOutcome::Skipped => Self {
+                    success: false,
+                    event_id: Some(*event_id),
+                    error: Some(format!("Event {} already exists; skipped", event_id)),
+                    results: Vec::new(),
+                },
+                ImportOutcome::Imported | ImportOutcome::Overwritten => Self {
+                    success: true,
+                    event_id: Some(*event_id),
+                    ..Default::default()
+                },
+            })
+            .collect();
+        Self {
+            success: true,
+            results,
+            ..Default::default()
+        }
+    }
+
+    fn failure(error: &str) -> Self {
+        Self {
+            success: false,
+            error: Some(error.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Drives a `Batch` operation: hands out its entries in order and collects
+/// their results
+pub struct BatchRun {
+    pending: std::vec::IntoIter<Operation>,
+    total: usize,
+    stop_on_error: bool,
+    results: Vec<OperationResponse>,
+}
+
+impl BatchRun {
+    pub fn new(operations: Vec<Operation>, stop_on_error: bool) -> Self {
+        Self {
+            total: operations.len(),
+            pending: operations.into_iter(),
+            stop_on_error,
+            results: Vec::new(),
+        }
+    }
+
+    /// The next operation to dispatch, or `None` once the batch is done.
+    ///
+    /// Nested batches are failed here instead of being handed out.
+    pub fn next_operation(&mut self) -> Option<Operation> {
+        loop {
+            if self.stopped() {
+                return None;
+            }
+            let operation = self.pending.next()?;
+            if !matches!(operation, Operation::Batch { .. }) {
+                return Some(operation);
+            }
+            self.record(OperationResponse::failure("Nested batches are not allowed"));
+        }
+    }
+
+    /// Record the result of the operation last handed out
+    pub fn record(&mut self, result: OperationResponse) {
+        self.results.push(result);
+    }
+
+    /// Combined response; a stopped batch names the operation it stopped at
+    pub fn finish(self) -> OperationResponse {
+        let stopped = self.stopped();
+        let total = self.total;
+        let mut response = OperationResponse::from_batch(self.results);
+        if stopped {
+            let index = response.results.len() - 1;
+            let cause = response.results[index].error.clone().unwrap_or_default();
+            response.error = Some(format!(
+                "Stopped at operation {} of {}: {}",
+                index, total, cause
+            ));
+        }
+        response
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop_on_error && self.results.last().is_some_and(|result| !result.success)
+    }
 }
 
 /// Operations that modify contract state
@@ -60,6 +187,16 @@ pub enum Operation {
         application_id: ApplicationId,
         config: AppConfig,
     },
+    /// Apply a JSON merge patch (RFC 7386) to an app's stored config
+    PatchAppConfig {
+        application_id: ApplicationId,
+        patch: serde_json::Value,
+    },
+    /// Attribute all events of `from_id` to `into_id` and drop `from_id`
+    MergeApplications {
+        from_id: ApplicationId,
+        into_id: ApplicationId,
+    },
 
     // === Event Capture ===
     CaptureEvent {
@@ -71,6 +208,17 @@ pub enum Operation {
     CaptureEventBatch {
         events: Vec<CapturedEvent>,
     },
+    /// Store events under the IDs they carry, e.g. from another instance
+    ImportEvents {
+        events: Vec<CapturedEvent>,
+        #[serde(default)]
+        on_conflict: ImportConflictPolicy,
+    },
+    /// Exempt a stored event from eviction, or lift the exemption
+    PinEvent {
+        event_id: EventId,
+        pinned: bool,
+    },
 
     // === Metric Management ===
     UpdateMetric {
@@ -80,6 +228,16 @@ pub enum Operation {
     DefineMetric {
         definition: MetricDefinition,
     },
+    /// Apply a JSON merge patch to a stored metric definition
+    UpdateMetricDefinition {
+        name: String,
+        patch: serde_json::Value,
+    },
+    /// Store a named `Request` template for `RunSavedQuery`
+    SaveQuery {
+        name: String,
+        request: serde_json::Value,
+    },
 
     // === Admin Operations (NEW) ===
     AdminAction {
@@ -91,9 +249,30 @@ pub enum Operation {
         target: Owner,
         role: Role,
     },
+    /// Assign a role that reverts to `Viewer` at `expires_at_block`
+    AssignRoleTemporary {
+        target: Owner,
+        role: Role,
+        expires_at_block: u64,
+    },
     RemoveRole {
         target: Owner,
     },
+    /// Define a named role with a bespoke permission set, replacing any
+    /// existing role of that name; the caller must hold every permission
+    /// of both the new and the replaced set
+    DefineCustomRole {
+        name: String,
+        permissions: BTreeSet<Permission>,
+    },
+    /// Delete a custom role that is no longer assigned
+    DeleteCustomRole {
+        name: String,
+    },
+    /// Restore role assignments from an `ExportRBAC` snapshot
+    ImportRBAC {
+        snapshot: RBACState,
+    },
 
     // === Rate Limit Control (NEW) ===
     UpdateRateLimitConfig {
@@ -104,6 +283,40 @@ pub enum Operation {
     UnblockApp {
         application_id: ApplicationId,
     },
+    /// Install per-app limit overrides, merging into or replacing the current map
+    SetRateLimitOverrides {
+        overrides: BTreeMap<ApplicationId, AppRateLimitOverride>,
+        #[serde(default)]
+        merge: bool,
+    },
+    ClearRateLimitOverrides,
+
+    // === Replication ===
+    /// Send the operation log from `from_index` onward to a standby chain
+    ReplicateOperationLog {
+        target_chain: ChainId,
+        from_index: u64,
+    },
+    /// Chain whose `OperationLogBatch` messages this chain applies; `None`
+    /// refuses all of them
+    SetReplicationSource {
+        source: Option<ChainId>,
+    },
+    /// Drop operation log entries before `before_index`, e.g. once every
+    /// standby has applied them
+    TruncateOperationLog {
+        before_index: u64,
+    },
+
+    // === Batching ===
+    /// Apply operations in order, each with its own permission check;
+    /// batches cannot be nested
+    Batch {
+        operations: Vec<Operation>,
+        /// Skip the remaining operations once one fails
+        #[serde(default)]
+        stop_on_error: bool,
+    },
 }
 
 /// Admin operations requiring elevated permissions
@@ -118,14 +331,33 @@ pub enum AdminOperation {
         max_events_per_app_per_block: u64,
         max_total_events_per_block: u64,
     },
-    /// Clear all events (dangerous!)
-    ClearEvents,
+    /// Clear all events (dangerous!); `token` must match the current
+    /// `GetClearEventsToken` answer
+    ClearEvents {
+        #[serde(default)]
+        token: Option<String>,
+    },
+    /// Evict events older than `retention_blocks` now
+    EvictExpiredEvents,
+    /// Forget seen transaction hashes without touching stored events
+    ResetDedupIndex,
     /// Rebuild Merkle index
     RebuildMerkleIndex,
     /// Transfer super admin
     TransferSuperAdmin {
         new_admin: Owner,
     },
+    /// Replace the contract-wide configuration
+    UpdateConfig {
+        config: AnalyticsConfig,
+    },
+    /// Reject all other operations while enabled; queries keep working
+    SetMaintenanceMode {
+        enabled: bool,
+    },
+    /// Record the current Merkle root and leaf set; checkpoints are numbered
+    /// from 0 in creation order
+    CreateCheckpoint,
 }
 
 /// Cross-chain messages (Enhanced)
@@ -155,7 +387,8 @@ pub enum Message {
     AggregationResponse {
         request_id: u64,
         results: Vec<AggregatedResult>,
-        proof: Option<MerkleProof>,
+        /// Inclusion proof for the events the queries span
+        proof: Option<BatchProof>,
     },
 
     // === Chain Synchronization (NEW) ===
@@ -167,26 +400,137 @@ pub enum Message {
         events: Vec<CapturedEvent>,
         batch_proof: Option<BatchProof>,
     },
+    OperationLogBatch {
+        entries: Vec<OperationLogEntry>,
+    },
 }
 
 /// Service query requests (Enhanced)
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum Request {
     // === Basic Queries ===
-    GetMonitoredApplications,
+    GetMonitoredApplications {
+        #[serde(default)]
+        filters: AppFilters,
+        #[serde(default)]
+        pagination: Pagination,
+    },
     GetApplicationMetrics {
         application_id: String,
         time_range: Option<TimeRange>,
     },
+    /// Current value of every stored metric, optionally filtered
+    GetAllMetrics {
+        #[serde(default)]
+        filter: Option<MetricFilter>,
+    },
+    /// Metrics updated at or after a block
+    GetMetricsChangedSince {
+        block: u64,
+    },
     GetEvents {
         filters: EventFilters,
         pagination: Pagination,
+        /// Return only these fields of each event
+        #[serde(default)]
+        fields: Option<Vec<String>>,
+    },
+    /// Same as `GetEvents`, also reporting whether the page was cut short
+    /// by its limit
+    GetEventPage {
+        filters: EventFilters,
+        pagination: Pagination,
+    },
+    /// Same as `GetEvents`, with the page encoded in a binary format
+    GetEventsBinary {
+        filters: EventFilters,
+        pagination: Pagination,
+        #[serde(default)]
+        format: BinaryFormat,
     },
     GetTimeSeries {
         metric: String,
         time_range: TimeRange,
         granularity_ms: u64,
+        /// Only this app's events, bucketed on its local clock
+        #[serde(default)]
+        application_id: Option<ApplicationId>,
+    },
+    GetMetricOwner {
+        metric: String,
+    },
+    /// Roll up metrics across every app carrying a tag
+    GetMetricsByTag {
+        tag: String,
+        time_range: Option<TimeRange>,
+    },
+    GetMetricNames {
+        #[serde(default)]
+        sort: MetricSort,
+    },
+    /// A defined metric over a time range, using its aggregation method
+    GetDefinedMetricValue {
+        name: String,
+        time_range: TimeRange,
+    },
+    GetMetricDefinitions {
+        #[serde(default)]
+        sort: MetricSort,
     },
+    GetEventTree {
+        root_event_id: u64,
+    },
+    /// Poll for events at or above a severity captured after a cursor
+    GetSevereEventsSince {
+        after_event_id: Option<u64>,
+        min_severity: EventSeverity,
+    },
+    /// Pre-check a raw event payload before submitting it
+    ValidateEvent {
+        event: serde_json::Value,
+    },
+    GetTransactions {
+        filters: TransactionFilters,
+        pagination: Pagination,
+    },
+    GetGasStats {
+        chain_id: Option<ChainId>,
+        time_range: TimeRange,
+    },
+    /// Lowest and highest stored event IDs, for planning incremental syncs
+    GetEventIdRange,
+    /// Confirmation token for `AdminOperation::ClearEvents`
+    GetClearEventsToken,
+    /// Chains that have contributed stored events, with event counts
+    GetSourceChains,
+    /// Events added between two Merkle checkpoints
+    GetCheckpointDelta {
+        from_checkpoint: u64,
+        to_checkpoint: u64,
+    },
+    /// Availability of an app from gaps in its heartbeat events
+    GetAppUptime {
+        application_id: ApplicationId,
+        heartbeat_type: String,
+        expected_interval_ms: u64,
+        time_range: TimeRange,
+    },
+    /// Events without a matching captured transaction record
+    GetOrphanEvents {
+        time_range: TimeRange,
+    },
+    /// Capture lag of events timestamped within the range
+    GetIngestionLagStats {
+        time_range: TimeRange,
+    },
+    /// Serialized size of event data, over all events or one app's
+    GetEventSizeStats {
+        #[serde(default)]
+        application_id: Option<ApplicationId>,
+    },
+    /// Stored events per event type; empty unless
+    /// `track_event_type_counts` is on
+    GetEventTypeCounts,
 
     // === Advanced Analytics (NEW) ===
     GetMovingAverage {
@@ -194,61 +538,283 @@ pub enum Request {
         window_size: u64,
         time_range: TimeRange,
     },
+    /// Exponentially weighted moving average of a metric's event values;
+    /// `alpha` must be in `(0, 1]`
+    GetEWMA {
+        metric: String,
+        alpha: f64,
+        time_range: TimeRange,
+    },
     DetectAnomalies {
         metric: String,
         sensitivity: f64,
         time_range: Option<TimeRange>,
     },
+    /// Bucketed series for a metric with the anomalous buckets in it
+    GetAnomaliesWithSeries {
+        metric: String,
+        sensitivity: f64,
+        time_range: TimeRange,
+        granularity_ms: u64,
+    },
+    /// Metrics whose latest `window` values have near-zero variance
+    DetectFlatlines {
+        metric: String,
+        window: usize,
+        time_range: TimeRange,
+        /// Standard deviation threshold; `DEFAULT_FLATLINE_THRESHOLD` if unset
+        #[serde(default)]
+        threshold: Option<f64>,
+    },
+    GetAnomalousMetrics {
+        sensitivity: f64,
+        top_n: usize,
+    },
     GetAggregation {
         query: AggregationQuery,
+        /// Round the result to this many decimal places
+        #[serde(default)]
+        round_to: Option<u8>,
+    },
+    /// Aggregate over captured events, with a batch proof of the inputs
+    GetVerifiableAggregation {
+        query: AggregationQuery,
+        #[serde(default)]
+        round_to: Option<u8>,
     },
     GetCorrelation {
         metrics: Vec<String>,
         time_range: TimeRange,
+        /// Uniformly subsample each series to this many values before correlating
+        #[serde(default)]
+        max_samples: Option<usize>,
+        #[serde(default)]
+        round_to: Option<u8>,
+    },
+    CorrelateVolumeWithMetric {
+        event_type: String,
+        metric: String,
+        time_range: TimeRange,
+        granularity_ms: u64,
+        #[serde(default)]
+        round_to: Option<u8>,
     },
 
     // === Merkle Proofs (NEW) ===
     GetEventProof {
         event_id: u64,
     },
+    /// Proof path for an event with the running hash at each level
+    ExplainProof {
+        event_id: u64,
+    },
+    /// Proofs for several events against a single root
+    GetEventProofs {
+        event_ids: Vec<u64>,
+    },
     VerifyEventProof {
         proof: MerkleProof,
         expected_root: CryptoHash,
     },
+    /// Verify every proof of a batch against one root
+    VerifyBatchProof {
+        batch: BatchProof,
+        expected_root: CryptoHash,
+    },
+    /// Verify a proof against the current root or a recent checkpoint root
+    VerifyEventProofWithTolerance {
+        proof: MerkleProof,
+        root: CryptoHash,
+    },
+    /// Verify many proofs against one root; results follow input order
+    VerifyEventProofs {
+        proofs: Vec<MerkleProof>,
+        expected_root: CryptoHash,
+    },
     GetMerkleRoot,
+    /// Merkle root as of the end of a block
+    GetMerkleRootAt {
+        block: u64,
+    },
 
     // === System Status (NEW) ===
     GetRateLimitStats,
+    /// Most recent rate-limit rejections, newest first
+    GetRecentRejections {
+        limit: usize,
+    },
     GetRBACInfo {
         owner: Option<Owner>,
     },
+    GetPermissionMatrix,
+    /// Owners with an explicit role, optionally only those holding `role`
+    ListRoleAssignments {
+        #[serde(default)]
+        role: Option<Role>,
+    },
+    /// Snapshot of all role assignments, for backup
+    ExportRBAC,
+    /// Recorded role changes and admin operations, oldest first
+    GetAuditLog {
+        #[serde(default)]
+        pagination: Pagination,
+        #[serde(default)]
+        actor_filter: Option<Owner>,
+    },
     GetSystemHealth,
+    /// Approximate storage used by each part of the state
+    GetStorageEstimate,
+    /// Internal consistency checks across events, indexes, Merkle tree and
+    /// rate limiter
+    CheckInvariants,
+    DiagnoseApp {
+        application_id: ApplicationId,
+    },
+    GetQueryStats,
+    /// Run a template stored with `SaveQuery`, filling in its time range
+    RunSavedQuery {
+        name: String,
+        #[serde(default)]
+        time_range: Option<TimeRange>,
+    },
+}
+
+impl Request {
+    /// Stable name of the query kind, used for usage statistics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Request::GetMonitoredApplications { .. } => "GetMonitoredApplications",
+            Request::GetApplicationMetrics { .. } => "GetApplicationMetrics",
+            Request::GetAllMetrics { .. } => "GetAllMetrics",
+            Request::GetMetricsChangedSince { .. } => "GetMetricsChangedSince",
+            Request::GetEvents { .. } => "GetEvents",
+            Request::GetEventPage { .. } => "GetEventPage",
+            Request::GetEventsBinary { .. } => "GetEventsBinary",
+            Request::GetTimeSeries { .. } => "GetTimeSeries",
+            Request::GetMetricOwner { .. } => "GetMetricOwner",
+            Request::GetMetricsByTag { .. } => "GetMetricsByTag",
+            Request::GetMetricNames { .. } => "GetMetricNames",
+            Request::GetMetricDefinitions { .. } => "GetMetricDefinitions",
+            Request::GetDefinedMetricValue { .. } => "GetDefinedMetricValue",
+            Request::GetEventTree { .. } => "GetEventTree",
+            Request::GetSevereEventsSince { .. } => "GetSevereEventsSince",
+            Request::ValidateEvent { .. } => "ValidateEvent",
+            Request::GetTransactions { .. } => "GetTransactions",
+            Request::GetGasStats { .. } => "GetGasStats",
+            Request::GetEventIdRange => "GetEventIdRange",
+            Request::GetClearEventsToken => "GetClearEventsToken",
+            Request::GetSourceChains => "GetSourceChains",
+            Request::GetCheckpointDelta { .. } => "GetCheckpointDelta",
+            Request::GetAppUptime { .. } => "GetAppUptime",
+            Request::GetOrphanEvents { .. } => "GetOrphanEvents",
+            Request::GetIngestionLagStats { .. } => "GetIngestionLagStats",
+            Request::GetEventSizeStats { .. } => "GetEventSizeStats",
+            Request::GetEventTypeCounts => "GetEventTypeCounts",
+            Request::GetMovingAverage { .. } => "GetMovingAverage",
+            Request::GetEWMA { .. } => "GetEWMA",
+            Request::DetectAnomalies { .. } => "DetectAnomalies",
+            Request::GetAnomaliesWithSeries { .. } => "GetAnomaliesWithSeries",
+            Request::DetectFlatlines { .. } => "DetectFlatlines",
+            Request::GetAnomalousMetrics { .. } => "GetAnomalousMetrics",
+            Request::GetAggregation { .. } => "GetAggregation",
+            Request::GetVerifiableAggregation { .. } => "GetVerifiableAggregation",
+            Request::GetCorrelation { .. } => "GetCorrelation",
+            Request::CorrelateVolumeWithMetric { .. } => "CorrelateVolumeWithMetric",
+            Request::GetEventProof { .. } => "GetEventProof",
+            Request::ExplainProof { .. } => "ExplainProof",
+            Request::GetEventProofs { .. } => "GetEventProofs",
+            Request::VerifyEventProof { .. } => "VerifyEventProof",
+            Request::VerifyBatchProof { .. } => "VerifyBatchProof",
+            Request::VerifyEventProofWithTolerance { .. } => "VerifyEventProofWithTolerance",
+            Request::VerifyEventProofs { .. } => "VerifyEventProofs",
+            Request::GetMerkleRoot => "GetMerkleRoot",
+            Request::GetMerkleRootAt { .. } => "GetMerkleRootAt",
+            Request::GetRateLimitStats => "GetRateLimitStats",
+            Request::GetRecentRejections { .. } => "GetRecentRejections",
+            Request::GetRBACInfo { .. } => "GetRBACInfo",
+            Request::GetPermissionMatrix => "GetPermissionMatrix",
+            Request::ListRoleAssignments { .. } => "ListRoleAssignments",
+            Request::ExportRBAC => "ExportRBAC",
+            Request::GetAuditLog { .. } => "GetAuditLog",
+            Request::GetSystemHealth => "GetSystemHealth",
+            Request::GetStorageEstimate => "GetStorageEstimate",
+            Request::CheckInvariants => "CheckInvariants",
+            Request::DiagnoseApp { .. } => "DiagnoseApp",
+            Request::GetQueryStats => "GetQueryStats",
+            Request::RunSavedQuery { .. } => "RunSavedQuery",
+        }
+    }
 }
 
 /// Service query responses (Enhanced)
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum Response {
     // === Basic Responses ===
-    MonitoredApplications(Vec<AppConfig>),
+    MonitoredApplications(ApplicationPage),
     ApplicationMetrics(Vec<(String, MetricValue)>),
     Events(Vec<CapturedEvent>),
+    ProjectedEvents(Vec<serde_json::Value>),
+    EventPage(EventPage),
+    EventsBinary(BinaryPayload),
     TimeSeries(Vec<TimeSeriesPoint>),
+    MetricOwners(Vec<ApplicationId>),
+    AllMetrics(BTreeMap<String, MetricSnapshot>),
+    TagMetrics(TagMetrics),
+    MetricNames(Vec<String>),
+    MetricDefinitions(Vec<MetricDefinition>),
+    DefinedMetricValue(Option<f64>),
+    EventTree(EventTreeNode),
+    SevereEvents(SevereEventsPage),
+    EventValidation(EventValidation),
+    Transactions(TransactionPage),
+    GasStats(GasStats),
+    IngestionLagStats(IngestionLagStats),
+    EventSizeStats(EventSizeStats),
+    EventTypeCounts(BTreeMap<String, u64>),
+    AppUptime(AppUptime),
+    EventIdRange(Option<EventIdRange>),
+    ClearEventsToken(String),
+    SourceChains(Vec<SourceChain>),
+    CheckpointDelta(CheckpointDelta),
 
     // === Advanced Analytics Responses (NEW) ===
     MovingAverage(Vec<MovingAveragePoint>),
     Anomalies(Vec<AnomalyEvent>),
+    AnomaliesWithSeries(AnomaliesWithSeries),
+    AnomalousMetrics(Vec<MetricAnomalyScore>),
+    Flatlines(Vec<Flatline>),
     Aggregation(AggregatedResult),
+    VerifiableAggregation(VerifiableAggregation),
     Correlation(CorrelationMatrix),
+    VolumeCorrelation(VolumeCorrelation),
 
     // === Merkle Responses (NEW) ===
-    EventProof(Option<MerkleProof>),
+    EventProof(ProofLookup),
+    ProofExplanation(Option<ProofExplanation>),
+    EventProofs(EventProofBatch),
     ProofVerification(bool),
+    ProofVerifications(Vec<bool>),
+    ToleratedProofVerification(ToleratedProofVerification),
     MerkleRoot(Option<CryptoHash>),
 
     // === System Status Responses (NEW) ===
     RateLimitStats(RateLimitStats),
+    RecentRejections(Vec<RateLimitRejection>),
     RBACInfo(RBACInfoResponse),
+    PermissionMatrix(Vec<(Role, Vec<Permission>)>),
+    RoleAssignments(Vec<(Owner, Role)>),
+    RBACSnapshot(RBACState),
+    AuditLog(Vec<AuditEntry>),
     SystemHealth(SystemHealthResponse),
+    StorageEstimate(StorageEstimate),
+    InvariantReport(InvariantReport),
+    AppDiagnosis(AppDiagnosis),
+    QueryStats(QueryStatsResponse),
+    /// Best-effort answer computed while parts of the state failed to load
+    Partial {
+        unloaded_parts: Vec<String>,
+        response: Box<Response>,
+    },
 
     // === Error Response ===
     Error(String),
@@ -265,8 +831,15 @@ pub struct RBACInfoResponse {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct SystemHealthResponse {
     pub total_events: u64,
+    /// Events seen, including those not stored because of sampling
+    pub total_events_seen: u64,
     pub total_applications: usize,
     pub merkle_root: Option<CryptoHash>,
     pub rate_limit_enabled: bool,
     pub ingestion_paused: bool,
+    pub maintenance_mode: bool,
+    /// Whether some state failed to load, see `unloaded_parts`
+    pub partial: bool,
+    /// State fields that fell back to defaults when loading
+    pub unloaded_parts: Vec<String>,
 }
@@ -8,6 +8,33 @@ use std::collections::BTreeMap;
 
 use crate::state::EventId;
 
+/// Root reported by an index with no leaves.
+///
+/// `Absent` keeps the historical `None`. Verifiers that need a concrete
+/// value should prefer `Fixed` with a domain-separated constant: `Zero` is
+/// simple but equals the root of a single leaf whose hash is all zeros.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum EmptyRoot {
+    /// No root at all
+    #[default]
+    Absent,
+    /// The all-zero hash
+    Zero,
+    /// A caller-chosen constant
+    Fixed(CryptoHash),
+}
+
+impl EmptyRoot {
+    /// The root an empty index reports under this policy
+    pub fn root(&self) -> Option<CryptoHash> {
+        match self {
+            EmptyRoot::Absent => None,
+            EmptyRoot::Zero => Some(CryptoHash::from([0u8; 32])),
+            EmptyRoot::Fixed(hash) => Some(*hash),
+        }
+    }
+}
+
 /// Sparse Merkle Tree for event indexing
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct MerkleIndex {
@@ -17,8 +44,16 @@ pub struct MerkleIndex {
     pub depth: u8,
     /// Leaf nodes: EventId -> Hash of event data
     pub leaves: BTreeMap<EventId, CryptoHash>,
-    /// Internal nodes for proof generation
+    /// Tree nodes by `node_key(level, position)`, leaves at level 0; nodes
+    /// past the last leaf are absent and stand for padding
     pub internal_nodes: BTreeMap<u64, CryptoHash>,
+    /// Root reported while there are no leaves
+    #[serde(default)]
+    pub empty_root: EmptyRoot,
+    /// Root at the end of each block the index has moved past, recorded
+    /// only for blocks that changed it
+    #[serde(default)]
+    pub root_history: BTreeMap<u64, CryptoHash>,
 }
 
 /// Merkle proof for verifying event inclusion
@@ -32,6 +67,56 @@ pub struct MerkleProof {
     pub event_id: EventId,
 }
 
+/// Inclusion proof for several leaves that shares their common path nodes
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MultiProof {
+    /// Proven leaves as (event ID, leaf position, leaf hash), by position
+    pub leaves: Vec<(EventId, u64, CryptoHash)>,
+    /// Number of levels between the leaves and the root
+    pub height: u8,
+    /// Hashes the verifier cannot derive from the proven leaves, level by
+    /// level from the leaves up and left to right within a level
+    pub siblings: Vec<CryptoHash>,
+}
+
+/// Outcome of looking up a proof for an event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ProofLookup {
+    /// The index holds no leaves at all
+    IndexEmpty,
+    /// The index is non-empty but has no leaf for this event
+    EventNotFound(EventId),
+    /// A proof would need more than `depth` levels, so the tree holds
+    /// more leaves than its configured depth allows
+    TreeTooDeep { path_length: usize, depth: u8 },
+    /// Inclusion proof for the event
+    Found(MerkleProof),
+}
+
+/// One level of a proof path, from the leaf towards the root
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProofStep {
+    /// Level combined at, with leaves at level 0
+    pub level: u32,
+    pub sibling_hash: CryptoHash,
+    /// Whether the sibling is the left input of the combination
+    pub sibling_is_left: bool,
+    /// Hash after combining with the sibling
+    pub running_hash: CryptoHash,
+}
+
+/// A Merkle proof laid out step by step for inspection
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProofExplanation {
+    pub event_id: EventId,
+    pub leaf_hash: CryptoHash,
+    pub steps: Vec<ProofStep>,
+    /// Root reported by the index
+    pub root: Option<CryptoHash>,
+    /// Whether the final running hash equals `root`
+    pub matches_root: bool,
+}
+
 /// Batch proof for multiple events
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BatchProof {
@@ -41,6 +126,7 @@ pub struct BatchProof {
     pub proofs: Vec<MerkleProof>,
     /// Batch metadata
     pub batch_id: u64,
+    /// Number of proofs; requested events without a proof are left out
     pub event_count: usize,
 }
 
@@ -52,9 +138,24 @@ impl MerkleIndex {
             depth,
             leaves: BTreeMap::new(),
             internal_nodes: BTreeMap::new(),
+            empty_root: EmptyRoot::default(),
+            root_history: BTreeMap::new(),
         }
     }
 
+    /// Change the root reported while the index is empty
+    pub fn set_empty_root(&mut self, empty_root: EmptyRoot) {
+        self.empty_root = empty_root;
+        self.recompute_root();
+    }
+
+    /// Remove all leaves, keeping the depth and empty-root policy
+    pub fn clear(&mut self) {
+        self.leaves.clear();
+        self.internal_nodes.clear();
+        self.recompute_root();
+    }
+
     /// Hash data to create a CryptoHash
     fn hash_data(data: &[u8]) -> CryptoHash {
         // Simple hash using the data bytes
@@ -84,42 +185,149 @@ impl MerkleIndex {
         self.recompute_root();
     }
 
-    /// Insert with pre-computed hash
+    /// Insert with pre-computed hash.
+    ///
+    /// Appending past the highest event ID or replacing a leaf updates only
+    /// the path to the root; inserting between existing leaves shifts their
+    /// positions and rebuilds the tree.
     pub fn insert_hash(&mut self, event_id: EventId, event_hash: CryptoHash) {
-        self.leaves.insert(event_id, event_hash);
-        self.recompute_root();
+        let consistent = self.nodes_consistent();
+        let appended = self
+            .leaves
+            .last_key_value()
+            .is_none_or(|(last, _)| event_id > *last);
+        let replaced = self.leaves.insert(event_id, event_hash).is_some();
+
+        if consistent && appended {
+            self.update_path(self.leaves.len() as u64 - 1, event_hash);
+        } else if consistent && replaced {
+            let position = self.leaves.range(..event_id).count() as u64;
+            self.update_path(position, event_hash);
+        } else {
+            self.recompute_root();
+        }
+    }
+
+    /// Remove an event's leaf; returns whether it was present
+    pub fn remove(&mut self, event_id: EventId) -> bool {
+        let removed = self.leaves.remove(&event_id).is_some();
+        if removed {
+            self.recompute_root();
+        }
+        removed
     }
 
-    /// Recompute the Merkle root from leaves
+    /// Remove several leaves, recomputing the root once
+    pub fn remove_batch(&mut self, event_ids: &[EventId]) {
+        let before = self.leaves.len();
+        for event_id in event_ids {
+            self.leaves.remove(event_id);
+        }
+        if self.leaves.len() != before {
+            self.recompute_root();
+        }
+    }
+
+    /// Rebuild every tree node and the root from the leaves
     fn recompute_root(&mut self) {
+        self.internal_nodes.clear();
         if self.leaves.is_empty() {
-            self.root = None;
+            self.root = self.empty_root.root();
             return;
         }
 
-        // Simple binary tree construction
         let mut current_level: Vec<CryptoHash> = self.leaves.values().cloned().collect();
-        
-        // Pad to power of 2
-        let next_pow2 = current_level.len().next_power_of_two();
-        let zero_hash = CryptoHash::from([0u8; 32]);
-        current_level.resize(next_pow2, zero_hash);
-
-        while current_level.len() > 1 {
-            let mut next_level = Vec::new();
-            for chunk in current_level.chunks(2) {
-                let left = &chunk[0];
-                let right = chunk.get(1).unwrap_or(&zero_hash);
-                next_level.push(Self::combine_hashes(left, right));
+        let mut level = 0;
+        loop {
+            for (position, hash) in current_level.iter().enumerate() {
+                self.internal_nodes
+                    .insert(Self::node_key(level, position as u64), *hash);
             }
-            current_level = next_level;
+            if current_level.len() == 1 {
+                break;
+            }
+
+            // Missing right children are padding
+            let padding = Self::padding_hash(level);
+            current_level = current_level
+                .chunks(2)
+                .map(|chunk| Self::combine_hashes(&chunk[0], chunk.get(1).unwrap_or(&padding)))
+                .collect();
+            level += 1;
         }
 
         self.root = current_level.into_iter().next();
     }
 
+    /// Store a leaf hash at `position` and recompute its ancestors
+    fn update_path(&mut self, position: u64, leaf_hash: CryptoHash) {
+        let height = self.leaves.len().next_power_of_two().trailing_zeros();
+        self.internal_nodes
+            .insert(Self::node_key(0, position), leaf_hash);
+
+        let mut hash = leaf_hash;
+        for level in 1..=height {
+            let parent = position >> level;
+            hash = Self::combine_hashes(
+                &self.node(level - 1, 2 * parent),
+                &self.node(level - 1, 2 * parent + 1),
+            );
+            self.internal_nodes
+                .insert(Self::node_key(level, parent), hash);
+        }
+        self.root = Some(hash);
+    }
+
+    /// Hash of the node at `position` within `level`, padding past the
+    /// last leaf
+    fn node(&self, level: u32, position: u64) -> CryptoHash {
+        self.internal_nodes
+            .get(&Self::node_key(level, position))
+            .copied()
+            .unwrap_or_else(|| Self::padding_hash(level))
+    }
+
+    /// Whether `internal_nodes` holds exactly the nodes of the current
+    /// leaves, so paths can be updated in place
+    fn nodes_consistent(&self) -> bool {
+        let leaves = self.leaves.len();
+        if leaves == 0 {
+            return self.internal_nodes.is_empty();
+        }
+        let height = leaves.next_power_of_two().trailing_zeros();
+        let expected: usize = (0..=height)
+            .map(|level| leaves.div_ceil(1 << level))
+            .sum();
+        self.internal_nodes.len() == expected
+    }
+
+    /// Key of the node at `position` within `level`
+    fn node_key(level: u32, position: u64) -> u64 {
+        ((level as u64) << 56) | position
+    }
+
+    /// Hash of an all-padding subtree at `level`
+    fn padding_hash(level: u32) -> CryptoHash {
+        let mut hash = CryptoHash::from([0u8; 32]);
+        for _ in 0..level {
+            hash = Self::combine_hashes(&hash, &hash);
+        }
+        hash
+    }
+
+    /// Number of sibling hashes in a proof for the current tree
+    pub fn proof_path_length(&self) -> usize {
+        self.leaves.len().next_power_of_two().trailing_zeros() as usize
+    }
+
     /// Generate a Merkle proof for an event
+    ///
+    /// Proof paths never exceed `depth` entries; if the tree has grown past
+    /// `2^depth` leaves no proof is produced (see `lookup_proof`).
     pub fn generate_proof(&self, event_id: EventId) -> Option<MerkleProof> {
+        if self.proof_path_length() > self.depth as usize {
+            return None;
+        }
         let leaf_hash = self.leaves.get(&event_id)?;
         
         // Build proof path
@@ -161,6 +369,56 @@ impl MerkleIndex {
         })
     }
 
+    /// Look up a proof, distinguishing an empty index from a missing event
+    pub fn lookup_proof(&self, event_id: EventId) -> ProofLookup {
+        if self.leaves.is_empty() {
+            return ProofLookup::IndexEmpty;
+        }
+        let path_length = self.proof_path_length();
+        if path_length > self.depth as usize {
+            return ProofLookup::TreeTooDeep {
+                path_length,
+                depth: self.depth,
+            };
+        }
+        match self.generate_proof(event_id) {
+            Some(proof) => ProofLookup::Found(proof),
+            None => ProofLookup::EventNotFound(event_id),
+        }
+    }
+
+    /// The proof for `event_id` with the running hash after each step,
+    /// or `None` when no proof can be generated
+    pub fn explain_proof(&self, event_id: EventId) -> Option<ProofExplanation> {
+        let proof = self.generate_proof(event_id)?;
+        let mut running_hash = proof.leaf_hash;
+        let steps: Vec<ProofStep> = proof
+            .path
+            .iter()
+            .zip(0..)
+            .map(|(&(sibling_hash, sibling_is_left), level)| {
+                running_hash = if sibling_is_left {
+                    Self::combine_hashes(&sibling_hash, &running_hash)
+                } else {
+                    Self::combine_hashes(&running_hash, &sibling_hash)
+                };
+                ProofStep {
+                    level,
+                    sibling_hash,
+                    sibling_is_left,
+                    running_hash,
+                }
+            })
+            .collect();
+        Some(ProofExplanation {
+            event_id,
+            leaf_hash: proof.leaf_hash,
+            steps,
+            root: self.root,
+            matches_root: self.root == Some(running_hash),
+        })
+    }
+
     /// Verify a Merkle proof
     pub fn verify_proof(root: &CryptoHash, proof: &MerkleProof) -> bool {
         let mut current_hash = proof.leaf_hash;
@@ -176,11 +434,164 @@ impl MerkleIndex {
         current_hash == *root
     }
 
+    /// Verify several proofs against one root, in input order
+    pub fn verify_proofs(root: &CryptoHash, proofs: &[MerkleProof]) -> Vec<bool> {
+        proofs
+            .iter()
+            .map(|proof| Self::verify_proof(root, proof))
+            .collect()
+    }
+
+    /// Verify a batch proof: its root must be `root`, `event_count` must
+    /// match its proofs, and every proof must verify against the root
+    pub fn verify_batch_proof(root: &CryptoHash, batch: &BatchProof) -> bool {
+        batch.batch_root == *root
+            && batch.event_count == batch.proofs.len()
+            && batch
+                .proofs
+                .iter()
+                .all(|proof| Self::verify_proof(&batch.batch_root, proof))
+    }
+
+    /// Generate one proof covering several events.
+    ///
+    /// Sibling hashes shared by several paths, or derivable from another
+    /// proven leaf, are included once or not at all. Event IDs are sorted
+    /// and deduplicated first, so the proof does not depend on their order.
+    /// Returns `None` if no IDs are given, any ID has no leaf, or the tree
+    /// is too deep for proofs.
+    pub fn generate_multiproof(&self, event_ids: &[EventId]) -> Option<MultiProof> {
+        let height = self.proof_path_length();
+        if event_ids.is_empty() || height > self.depth as usize {
+            return None;
+        }
+        let mut ids = event_ids.to_vec();
+        ids.sort_unstable();
+        ids.dedup();
+
+        let leaves: Vec<(EventId, u64, CryptoHash)> = self
+            .leaves
+            .iter()
+            .enumerate()
+            .filter(|(_, (id, _))| ids.binary_search(id).is_ok())
+            .map(|(position, (id, hash))| (*id, position as u64, *hash))
+            .collect();
+        if leaves.len() != ids.len() {
+            return None;
+        }
+
+        let mut siblings = Vec::new();
+        let mut known: Vec<u64> = leaves.iter().map(|(_, position, _)| *position).collect();
+        for level in 0..height as u32 {
+            let mut parents = Vec::with_capacity(known.len());
+            let mut i = 0;
+            while i < known.len() {
+                let position = known[i];
+                if known.get(i + 1) == Some(&(position ^ 1)) {
+                    i += 2;
+                } else {
+                    siblings.push(self.node(level, position ^ 1));
+                    i += 1;
+                }
+                parents.push(position / 2);
+            }
+            known = parents;
+        }
+
+        Some(MultiProof {
+            leaves,
+            height: height as u8,
+            siblings,
+        })
+    }
+
+    /// Verify a multi-leaf proof: leaf positions must be strictly
+    /// increasing and inside the tree, and every sibling must be used
+    pub fn verify_multiproof(root: &CryptoHash, proof: &MultiProof) -> bool {
+        if proof.height >= 64 {
+            return false;
+        }
+        let width = 1u64 << proof.height;
+        let ordered = proof.leaves.windows(2).all(|pair| pair[0].1 < pair[1].1);
+        match proof.leaves.last() {
+            Some((_, last, _)) if ordered && *last < width => {}
+            _ => return false,
+        }
+
+        let mut level: Vec<(u64, CryptoHash)> = proof
+            .leaves
+            .iter()
+            .map(|(_, position, hash)| (*position, *hash))
+            .collect();
+        let mut siblings = proof.siblings.iter();
+        for _ in 0..proof.height {
+            let mut parents = Vec::with_capacity(level.len());
+            let mut i = 0;
+            while i < level.len() {
+                let (position, hash) = level[i];
+                let parent = match level.get(i + 1) {
+                    Some((next, right)) if *next == position ^ 1 => {
+                        i += 2;
+                        Self::combine_hashes(&hash, right)
+                    }
+                    _ => {
+                        let Some(sibling) = siblings.next() else {
+                            return false;
+                        };
+                        i += 1;
+                        if position % 2 == 1 {
+                            Self::combine_hashes(sibling, &hash)
+                        } else {
+                            Self::combine_hashes(&hash, sibling)
+                        }
+                    }
+                };
+                parents.push((position / 2, parent));
+            }
+            level = parents;
+        }
+
+        siblings.next().is_none() && level.len() == 1 && level[0].1 == *root
+    }
+
     /// Get current root
     pub fn get_root(&self) -> Option<CryptoHash> {
         self.root
     }
 
+    /// Record the current root as the root at the end of `block`.
+    ///
+    /// Nothing is recorded while the index has no root, or when the root is
+    /// unchanged since the latest snapshot, which still answers for `block`.
+    pub fn snapshot_root(&mut self, block: u64) {
+        let Some(root) = self.root else {
+            return;
+        };
+        if self.root_history.last_key_value().map(|(_, last)| *last) != Some(root) {
+            self.root_history.insert(block, root);
+        }
+    }
+
+    /// Drop snapshots no longer needed to answer for `block` or later.
+    ///
+    /// The latest snapshot before `block` is kept, since it is the root of
+    /// every block up to the next one.
+    pub fn prune_root_history(&mut self, block: u64) {
+        let kept = self.root_history.range(..=block).next_back().map(|(at, _)| *at);
+        if let Some(kept) = kept {
+            self.root_history = self.root_history.split_off(&kept);
+        }
+    }
+
+    /// Root as of the end of `block`: the latest snapshot taken at or
+    /// before it, or `None` if no snapshot is that old
+    pub fn get_root_at(&self, block: u64) -> Option<CryptoHash> {
+        self.root_history
+            .range(..=block)
+            .next_back()
+            .map(|(_, root)| *root)
+    }
+
     /// Get number of events indexed
     pub fn event_count(&self) -> usize {
         self.leaves.len()
@@ -203,9 +614,9 @@ impl MerkleIndex {
 
         Some(BatchProof {
             batch_root: root,
-            proofs,
             batch_id,
-            event_count: event_ids.len(),
+            event_count: proofs.len(),
+            proofs,
         })
     }
 }
@@ -214,6 +625,52 @@ impl MerkleIndex {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_verify_proofs_flags_tampered_entries() {
+        let mut index = MerkleIndex::new(8);
+        for id in 0..4u64 {
+            index.insert(id, format!("event{}", id).as_bytes());
+        }
+        let root = index.get_root().unwrap();
+
+        let mut proofs: Vec<MerkleProof> = (0..4).filter_map(|id| index.generate_proof(id)).collect();
+        proofs[1].leaf_hash = CryptoHash::from([0xFF; 32]);
+        proofs[3].path[0].0 = CryptoHash::from([0x11; 32]);
+
+        assert_eq!(
+            MerkleIndex::verify_proofs(&root, &proofs),
+            vec![true, false, true, false]
+        );
+        assert!(MerkleIndex::verify_proofs(&root, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_configured_empty_root() {
+        let mut index = MerkleIndex::new(8);
+        assert_eq!(index.get_root(), None);
+
+        let marker = CryptoHash::from([0xAB; 32]);
+        index.set_empty_root(EmptyRoot::Fixed(marker));
+        assert_eq!(index.get_root(), Some(marker));
+
+        // Stable across inserts and clears
+        index.insert(1, b"event1");
+        let single_leaf_root = index.get_root().unwrap();
+        assert_ne!(single_leaf_root, marker);
+        index.clear();
+        assert_eq!(index.get_root(), Some(marker));
+
+        let mut zero = MerkleIndex::new(8);
+        zero.set_empty_root(EmptyRoot::Zero);
+        let empty = zero.get_root().unwrap();
+        for data in [b"a".as_slice(), b"event", b"{\"amount\":1}"] {
+            let mut single = MerkleIndex::new(8);
+            single.set_empty_root(EmptyRoot::Zero);
+            single.insert(1, data);
+            assert_ne!(single.get_root(), Some(empty));
+        }
+    }
+
     #[test]
     fn test_merkle_insert_and_root() {
         let mut index = MerkleIndex::new(8);
@@ -245,6 +702,27 @@ mod tests {
         assert!(MerkleIndex::verify_proof(&root, &proof));
     }
 
+    #[test]
+    fn test_explain_proof_ends_at_root() {
+        let mut index = MerkleIndex::new(8);
+        for id in 1..=5u64 {
+            index.insert(id, format!("event{}", id).as_bytes());
+        }
+
+        let explanation = index.explain_proof(4).unwrap();
+        let proof = index.generate_proof(4).unwrap();
+        assert_eq!(explanation.leaf_hash, proof.leaf_hash);
+        assert_eq!(explanation.steps.len(), proof.path.len());
+        assert_eq!(
+            explanation.steps.last().unwrap().running_hash,
+            index.get_root().unwrap()
+        );
+        assert!(explanation.matches_root);
+        // The fourth leaf is a right child, so its first sibling is on the left
+        assert!(explanation.steps[0].sibling_is_left);
+        assert!(index.explain_proof(9).is_none());
+    }
+
     #[test]
     fn test_invalid_proof_fails() {
         let mut index = MerkleIndex::new(8);
@@ -260,4 +738,210 @@ mod tests {
         
         assert!(!MerkleIndex::verify_proof(&root, &proof));
     }
+
+    #[test]
+    fn test_lookup_proof_cases() {
+        let mut index = MerkleIndex::new(8);
+        assert!(matches!(index.lookup_proof(1), ProofLookup::IndexEmpty));
+
+        index.insert(1, b"event1");
+        index.insert(2, b"event2");
+        assert!(matches!(index.lookup_proof(7), ProofLookup::EventNotFound(7)));
+
+        let root = index.get_root().unwrap();
+        match index.lookup_proof(2) {
+            ProofLookup::Found(proof) => {
+                assert_eq!(proof.event_id, 2);
+                assert!(MerkleIndex::verify_proof(&root, &proof));
+            }
+            other => panic!("expected proof, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_proof_path_never_exceeds_depth() {
+        let mut index = MerkleIndex::new(4);
+        for id in 0..16 {
+            index.insert(id, format!("event{}", id).as_bytes());
+        }
+        for id in 0..16 {
+            let proof = index.generate_proof(id).unwrap();
+            assert!(proof.path.len() <= 4);
+        }
+
+        // A 17th leaf needs a 5-level path, which exceeds the configured depth
+        index.insert(16, b"event16");
+        assert!(index.generate_proof(3).is_none());
+        assert!(matches!(
+            index.lookup_proof(3),
+            ProofLookup::TreeTooDeep { path_length: 5, depth: 4 }
+        ));
+    }
+
+    #[test]
+    fn test_remove_leaves() {
+        let mut index = MerkleIndex::new(16);
+        for id in 0..6 {
+            index.insert(id, format!("event{}", id).as_bytes());
+        }
+        let mut expected = MerkleIndex::new(16);
+        for id in [0, 3, 5] {
+            expected.insert(id, format!("event{}", id).as_bytes());
+        }
+
+        assert!(index.remove(1));
+        assert!(!index.remove(1));
+        assert!(!index.remove(42));
+        index.remove_batch(&[2, 4, 42]);
+        assert_eq!(index.get_root(), expected.get_root());
+
+        let root = index.get_root().unwrap();
+        for id in [0, 3, 5] {
+            let proof = index.generate_proof(id).unwrap();
+            assert!(MerkleIndex::verify_proof(&root, &proof));
+        }
+        assert!(index.generate_proof(2).is_none());
+    }
+
+    #[test]
+    fn test_verify_batch_proof() {
+        let mut index = MerkleIndex::new(8);
+        for id in 0..6u64 {
+            index.insert(id, format!("event{}", id).as_bytes());
+        }
+        let root = index.get_root().unwrap();
+        let batch = index.generate_batch_proof(&[1, 3, 4, 99], 7).unwrap();
+        assert_eq!(batch.event_count, 3);
+        assert!(MerkleIndex::verify_batch_proof(&root, &batch));
+
+        let mut tampered = batch.clone();
+        tampered.proofs[1].leaf_hash = CryptoHash::from([0xEE; 32]);
+        assert!(!MerkleIndex::verify_batch_proof(&root, &tampered));
+
+        assert!(!MerkleIndex::verify_batch_proof(&CryptoHash::from([1u8; 32]), &batch));
+
+        let mut miscounted = batch.clone();
+        miscounted.event_count = 4;
+        assert!(!MerkleIndex::verify_batch_proof(&root, &miscounted));
+    }
+
+    #[test]
+    fn test_incremental_root_matches_full_rebuild() {
+        let mut index = MerkleIndex::new(16);
+        let check = |index: &MerkleIndex| {
+            let mut rebuilt = index.clone();
+            rebuilt.recompute_root();
+            assert_eq!(index.root, rebuilt.root);
+            assert_eq!(index.internal_nodes, rebuilt.internal_nodes);
+        };
+        for id in 0..33u64 {
+            index.insert(id * 2, format!("event{}", id).as_bytes());
+            check(&index);
+        }
+        // Replacing a leaf, inserting between leaves and removing one
+        index.insert(10, b"replaced");
+        check(&index);
+        index.insert(11, b"between");
+        check(&index);
+        index.remove(4);
+        index.insert(100, b"after removal");
+        check(&index);
+
+        // State from before nodes were tracked is rebuilt on the next insert
+        index.internal_nodes.clear();
+        index.insert(200, b"stale nodes");
+        check(&index);
+    }
+
+    /// Tree nodes `operation` writes when applied to a copy of `index`.
+    ///
+    /// Every stored node is first overwritten with a marker unique to its
+    /// key, so the nodes still holding their marker were left alone.
+    fn node_writes(index: &MerkleIndex, operation: impl FnOnce(&mut MerkleIndex)) -> usize {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{BuildHasher, BuildHasherDefault};
+
+        let marker = |key: u64| {
+            let mut bytes = [0u8; 32];
+            for (i, chunk) in bytes.chunks_mut(8).enumerate() {
+                let word = BuildHasherDefault::<DefaultHasher>::default().hash_one((key, i));
+                chunk.copy_from_slice(&word.to_le_bytes());
+            }
+            CryptoHash::from(bytes)
+        };
+        let mut probe = index.clone();
+        for (key, hash) in probe.internal_nodes.iter_mut() {
+            *hash = marker(*key);
+        }
+        operation(&mut probe);
+        probe
+            .internal_nodes
+            .iter()
+            .filter(|(key, hash)| **hash != marker(**key))
+            .count()
+    }
+
+    #[test]
+    fn test_incremental_insert_outpaces_full_recompute() {
+        let mut index = MerkleIndex::new(16);
+        let mut incremental = 0;
+        for id in 0..1024u64 {
+            let hash = CryptoHash::from([(id % 251) as u8; 32]);
+            incremental += node_writes(&index, |index| index.insert_hash(id, hash));
+            index.insert_hash(id, hash);
+        }
+
+        // Each append writes only its path to the root, where a rebuild
+        // writes every node. Rebuilding on every insert, as before, would
+        // cost about as much as 500 rebuilds of the final tree.
+        let full = node_writes(&index, |index| index.recompute_root());
+        let mut rebuilt = index.clone();
+        rebuilt.recompute_root();
+
+        assert_eq!(index.root, rebuilt.root);
+        assert_eq!(full, index.internal_nodes.len());
+        assert!(full >= 2047, "a rebuild wrote {} nodes", full);
+        assert!(
+            incremental < 10 * full,
+            "1024 appends wrote {} nodes, a rebuild {}",
+            incremental,
+            full
+        );
+    }
+
+    #[test]
+    fn test_multiproof_smaller_than_separate_proofs() {
+        let mut index = MerkleIndex::new(8);
+        for id in 0..16u64 {
+            index.insert(id, format!("event{}", id).as_bytes());
+        }
+        let root = index.get_root().unwrap();
+        let ids = [14, 0, 2, 4, 6, 8, 10, 12];
+
+        let multiproof = index.generate_multiproof(&ids).unwrap();
+        assert!(MerkleIndex::verify_multiproof(&root, &multiproof));
+        assert_eq!(multiproof, index.generate_multiproof(&[0, 2, 4, 6, 8, 10, 12, 14]).unwrap());
+
+        let proofs: Vec<MerkleProof> = ids
+            .iter()
+            .map(|id| index.generate_proof(*id).unwrap())
+            .collect();
+        let separate_hashes: usize = proofs.iter().map(|proof| proof.path.len()).sum();
+        assert_eq!(separate_hashes, 32);
+        assert_eq!(multiproof.siblings.len(), 8);
+        let separate_size = serde_json::to_vec(&proofs).unwrap().len();
+        let multi_size = serde_json::to_vec(&multiproof).unwrap().len();
+        assert!(multi_size * 2 < separate_size);
+
+        // A tampered leaf or a missing sibling no longer verifies
+        let mut tampered = multiproof.clone();
+        tampered.leaves[3].2 = CryptoHash::from([7u8; 32]);
+        assert!(!MerkleIndex::verify_multiproof(&root, &tampered));
+        let mut truncated = multiproof;
+        truncated.siblings.pop();
+        assert!(!MerkleIndex::verify_multiproof(&root, &truncated));
+
+        assert!(index.generate_multiproof(&[1, 99]).is_none());
+        assert!(index.generate_multiproof(&[]).is_none());
+    }
 }
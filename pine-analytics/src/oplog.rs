@@ -0,0 +1,286 @@
+//! Operation Log for Pine Analytics
+//!
+//! Append-only history of applied operations that can be replicated to a
+//! standby chain for disaster recovery.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AnalyticsError, Result};
+use crate::state::{AnalyticsState, AppConfig, ChainId, Owner};
+use crate::{AdminOperation, Operation, OperationResponse};
+
+/// A single applied operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationLogEntry {
+    /// Position in the log (0-based)
+    pub index: u64,
+    /// Block height at which the operation was applied
+    pub block_height: u64,
+    /// Owner that submitted the operation
+    pub caller: Owner,
+    /// The operation itself
+    pub operation: Operation,
+}
+
+impl Operation {
+    /// Whether the operation is recorded in the operation log.
+    ///
+    /// Event and transaction captures are replicated through the event sync
+    /// messages instead, replication requests are not state changes, and
+    /// the replication source and log truncation are local to each chain.
+    pub fn is_logged(&self) -> bool {
+        !matches!(
+            self,
+            Operation::CaptureEvent { .. }
+                | Operation::CaptureTransaction { .. }
+                | Operation::CaptureEventBatch { .. }
+                | Operation::ReplicateOperationLog { .. }
+                | Operation::SetReplicationSource { .. }
+                | Operation::TruncateOperationLog { .. }
+        )
+    }
+
+    /// What to record in the log once the operation got `response`.
+    ///
+    /// A batch keeps only the entries that succeeded and are logged
+    /// themselves, so a partly failed batch replicates what was applied.
+    pub fn logged_part(self, response: &OperationResponse) -> Option<Operation> {
+        match self {
+            Operation::Batch {
+                operations,
+                stop_on_error,
+            } => {
+                let operations: Vec<Operation> = operations
+                    .into_iter()
+                    .zip(&response.results)
+                    .filter(|(operation, result)| result.success && operation.is_logged())
+                    .map(|(operation, _)| operation.without_confirmation())
+                    .collect();
+                (!operations.is_empty()).then_some(Operation::Batch {
+                    operations,
+                    stop_on_error,
+                })
+            }
+            operation => (response.success && operation.is_logged())
+                .then(|| operation.without_confirmation()),
+        }
+    }
+
+    /// The operation without its `ClearEvents` token, which only confirmed
+    /// the submission against the primary's event store
+    fn without_confirmation(self) -> Operation {
+        match self {
+            Operation::AdminAction {
+                action: AdminOperation::ClearEvents { .. },
+            } => Operation::AdminAction {
+                action: AdminOperation::ClearEvents { token: None },
+            },
+            operation => operation,
+        }
+    }
+}
+
+impl AnalyticsState {
+    /// Append an applied operation to the log
+    pub fn log_operation(&mut self, caller: Owner, operation: Operation) {
+        let entry = OperationLogEntry {
+            index: self.next_log_index(),
+            block_height: self.current_block,
+            caller,
+            operation,
+        };
+        self.operation_log.push(entry);
+        self.enforce_operation_log_cap();
+    }
+
+    /// Index the next log entry gets
+    pub fn next_log_index(&self) -> u64 {
+        self.operation_log_start + self.operation_log.len() as u64
+    }
+
+    /// Log entries starting at `from_index`, or at the oldest entry kept if
+    /// that is later; a standby then sees the gap and refuses the batch
+    pub fn operation_log_since(&self, from_index: u64) -> Vec<OperationLogEntry> {
+        self.operation_log
+            .iter()
+            .skip(from_index.saturating_sub(self.operation_log_start) as usize)
+            .cloned()
+            .collect()
+    }
+
+    /// Drop log entries before `before_index`, returning how many were
+    /// removed. Later entries keep their indexes.
+    pub fn truncate_operation_log(&mut self, before_index: u64) -> usize {
+        let count = before_index
+            .saturating_sub(self.operation_log_start)
+            .min(self.operation_log.len() as u64);
+        self.operation_log.drain(..count as usize);
+        self.operation_log_start += count;
+        count as usize
+    }
+
+    /// Drop the oldest entries beyond `max_operation_log_entries`
+    fn enforce_operation_log_cap(&mut self) {
+        if let Some(max_entries) = self.config.max_operation_log_entries {
+            let excess = self.operation_log.len().saturating_sub(max_entries);
+            self.truncate_operation_log(self.operation_log_start + excess as u64);
+        }
+    }
+
+    /// Apply log entries replicated from the `origin` chain.
+    ///
+    /// Entries skip permission checks, so they are only accepted from the
+    /// configured `replication_source`. Entries already present locally are
+    /// skipped, so replaying an overlapping batch is idempotent. A gap in
+    /// indexes or an entry that fails to apply aborts the replay, leaving
+    /// that entry unapplied. Returns the number of newly applied entries.
+    pub fn apply_replicated_log(
+        &mut self,
+        origin: ChainId,
+        entries: Vec<OperationLogEntry>,
+    ) -> Result<usize> {
+        if self.replication_source != Some(origin) {
+            return Err(AnalyticsError::CrossChainError(format!(
+                "Operation log from {} refused: not the replication source",
+                origin
+            )));
+        }
+        let mut applied = 0;
+
+        for entry in entries {
+            let next_index = self.next_log_index();
+            if entry.index < next_index {
+                continue;
+            }
+            if entry.index > next_index {
+                return Err(AnalyticsError::CrossChainError(format!(
+                    "Operation log gap: expected entry {}, got {}",
+                    next_index, entry.index
+                )));
+            }
+
+            // Single operations fail before changing anything, but a batch
+            // may fail partway; undo it so a retry does not apply its
+            // earlier operations twice
+            if matches!(entry.operation, Operation::Batch { .. }) {
+                let before = self.clone();
+                if let Err(e) = self.apply_logged_operation(&entry.operation) {
+                    *self = before;
+                    return Err(e);
+                }
+            } else {
+                self.apply_logged_operation(&entry.operation)?;
+            }
+            self.operation_log.push(entry);
+            self.enforce_operation_log_cap();
+            applied += 1;
+        }
+
+        Ok(applied)
+    }
+
+    /// Re-apply a logged operation without permission checks.
+    ///
+    /// Authorization already happened on the chain that produced the entry.
+    fn apply_logged_operation(&mut self, operation: &Operation) -> Result<()> {
+        match operation.clone() {
+            Operation::AddMonitoredApp {
+                application_id,
+                chain_id,
+                graphql_endpoint,
+            } => {
+                self.upsert_app_config(AppConfig::new(application_id, chain_id, graphql_endpoint))?;
+            }
+            Operation::RemoveMonitoredApp { application_id } => {
+                self.monitored_applications.remove(&application_id);
+            }
+            Operation::UpdateAppConfig { config, .. } => {
+                self.upsert_app_config(config)?;
+            }
+            Operation::PatchAppConfig {
+                application_id,
+                patch,
+            } => {
+                self.patch_app_config(&application_id, &patch)?;
+            }
+            Operation::MergeApplications { from_id, into_id } => {
+                self.merge_applications(&from_id, &into_id)?;
+            }
+            Operation::ImportEvents {
+                events,
+                on_conflict,
+            } => {
+                self.import_events(events, on_conflict)?;
+            }
+            Operation::PinEvent { event_id, pinned } => {
+                self.pin_event(event_id, pinned)?;
+            }
+            Operation::UpdateMetric { key, value } => {
+                self.update_metric(key, value);
+            }
+            Operation::DefineMetric { definition } => {
+                self.metric_definitions
+                    .insert(definition.name.clone(), definition);
+            }
+            Operation::UpdateMetricDefinition { name, patch } => {
+                self.update_metric_definition(&name, &patch)?;
+            }
+            Operation::SaveQuery { name, request } => {
+                self.save_query(name, request)?;
+            }
+            Operation::AdminAction {
+                action: AdminOperation::ClearEvents { .. },
+            } => self.clear_events(),
+            Operation::AdminAction { action } => {
+                self.execute_admin_action(action)?;
+            }
+            Operation::AssignRole { target, role } => {
+                self.rbac.assign_role(target, role)?;
+            }
+            Operation::AssignRoleTemporary { target, role, expires_at_block } => {
+                self.rbac.assign_role_until(target, role, expires_at_block)?;
+            }
+            Operation::RemoveRole { target } => {
+                self.rbac.remove_role(&target)?;
+            }
+            Operation::DefineCustomRole { name, permissions } => {
+                self.rbac.define_custom_role(name, permissions)?;
+            }
+            Operation::DeleteCustomRole { name } => {
+                self.rbac.delete_custom_role(&name)?;
+            }
+            Operation::ImportRBAC { snapshot } => {
+                self.rbac.import_replicated(snapshot)?;
+            }
+            Operation::UpdateRateLimitConfig { config } => {
+                self.rate_limiter.try_update_config(config)?;
+            }
+            Operation::PauseIngestion => self.rate_limiter.pause(),
+            Operation::ResumeIngestion => self.rate_limiter.resume(),
+            Operation::UnblockApp { application_id } => {
+                self.rate_limiter.unblock_app(&application_id);
+            }
+            Operation::SetRateLimitOverrides { overrides, merge } => {
+                self.rate_limiter.set_overrides(overrides, merge)?;
+            }
+            Operation::ClearRateLimitOverrides => self.rate_limiter.clear_overrides(),
+            Operation::Batch { operations, .. } => {
+                if operations.iter().any(|op| matches!(op, Operation::Batch { .. })) {
+                    return Err(AnalyticsError::InvalidOperation(
+                        "Nested batches are not allowed".to_string(),
+                    ));
+                }
+                for operation in &operations {
+                    self.apply_logged_operation(operation)?;
+                }
+            }
+            Operation::CaptureEvent { .. }
+            | Operation::CaptureTransaction { .. }
+            | Operation::CaptureEventBatch { .. }
+            | Operation::ReplicateOperationLog { .. }
+            | Operation::SetReplicationSource { .. }
+            | Operation::TruncateOperationLog { .. } => {}
+        }
+        Ok(())
+    }
+}
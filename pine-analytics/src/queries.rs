@@ -0,0 +1,1520 @@
+//! Read-side Analytics Queries for Pine Analytics
+//!
+//! Computations over `AnalyticsState` served by the service layer.
+
+use linera_sdk::linera_base_types::CryptoHash;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::aggregations::{
+    AggregatedResult, AggregationEngine, AggregationQuery, AggregationType, AnomalyEvent,
+    MovingAveragePoint, TimeBucket,
+};
+use crate::error::{AnalyticsError, Result};
+use crate::merkle::{BatchProof, MerkleIndex, MerkleProof};
+use crate::rate_limit::RateLimitError;
+use crate::Message;
+use crate::state::{
+    AnalyticsState, ApplicationId, CapturedEvent, ChainId, EventFilters, EventId, EventIdStrategy,
+    EventSeverity, MetricDefinition, MetricFilter, MetricType, MetricValue, MixedMetricPolicy,
+    Pagination, TimeRange, TimeSeriesPoint, Timestamp, ANOMALY_EVENT_TYPE,
+};
+
+/// Approximate encoded size of each part of the state, in bytes
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct StorageEstimate {
+    pub events_bytes: u64,
+    /// Aggregated metrics, their bookkeeping, and metric definitions
+    pub metrics_bytes: u64,
+    /// Event and transaction lookup indexes
+    pub indexes_bytes: u64,
+    /// Merkle leaves, nodes and root history
+    pub merkle_bytes: u64,
+    pub rbac_bytes: u64,
+    pub total_bytes: u64,
+}
+
+/// An internal consistency property that does not hold
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum InvariantViolation {
+    /// Two stored events share an ID
+    DuplicateEventId(EventId),
+    /// A sequential ID at or past `next_event_id`
+    EventIdNotYetAssigned { event_id: EventId, next_event_id: EventId },
+    /// More events stored than were ever captured
+    MoreEventsThanCaptured { stored: usize, captured: u64 },
+    /// Merkle leaves and stored events differ in number
+    MerkleLeafCountMismatch { leaves: usize, events: usize },
+    /// A stored event has no Merkle leaf
+    MissingMerkleLeaf(EventId),
+    /// An index entry names an event that is not stored
+    DanglingIndexEntry { index: String, event_id: EventId },
+    /// An app's counter for its block exceeds its limit
+    AppCounterOverLimit { app_id: ApplicationId, count: u64, limit: u64 },
+    /// The global counter for its block exceeds the limit
+    GlobalCounterOverLimit { count: u64, limit: u64 },
+}
+
+/// Result of `check_invariants`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InvariantReport {
+    pub violations: Vec<InvariantViolation>,
+    /// True when no violation was found
+    pub healthy: bool,
+}
+
+/// Current value of a stored metric
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MetricSnapshot {
+    pub value: MetricValue,
+    /// The value as used by aggregations, see `MetricValue::as_f64`
+    pub numeric: f64,
+    pub metric_type: MetricType,
+}
+
+impl MetricSnapshot {
+    fn of(value: &MetricValue) -> Self {
+        Self {
+            numeric: value.as_f64(),
+            metric_type: value.metric_type(),
+            value: value.clone(),
+        }
+    }
+}
+
+/// Most recent samples considered per metric when scanning for anomalies
+pub const MAX_ANOMALY_SAMPLES_PER_METRIC: usize = 1000;
+
+/// Most recent events searched for samples when scoring a captured event
+pub const MAX_ANOMALY_ALERT_SCAN_EVENTS: usize = 1000;
+
+/// Standard deviation below which a metric window counts as flat
+pub const DEFAULT_FLATLINE_THRESHOLD: f64 = 1e-9;
+
+/// Most event IDs accepted by a single `GetEventProofs` query
+pub const MAX_PROOFS_PER_QUERY: usize = 256;
+
+/// Items encoded per collection when estimating storage size
+pub const STORAGE_ESTIMATE_SAMPLE_SIZE: usize = 64;
+
+/// Correlation between event volume and a metric over a shared bucket grid
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolumeCorrelation {
+    pub event_type: String,
+    pub metric: String,
+    /// Pearson correlation between the two series
+    pub coefficient: f64,
+    /// Bucket start timestamps shared by both series
+    pub bucket_starts: Vec<Timestamp>,
+    /// Number of `event_type` events per bucket
+    pub volume_series: Vec<f64>,
+    /// Sum of the metric's values per bucket
+    pub metric_series: Vec<f64>,
+}
+
+/// A metric whose recent values have stopped changing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Flatline {
+    pub metric: String,
+    /// Value the metric is stuck at (its latest sample)
+    pub value: f64,
+    /// Standard deviation over the window
+    pub std_dev: f64,
+    /// Samples in the window
+    pub window: usize,
+}
+
+impl VolumeCorrelation {
+    /// Round the coefficient and metric sums to `places` decimal places
+    pub fn round(&mut self, places: u8) {
+        self.coefficient = AggregationEngine::round_to(self.coefficient, places);
+        for value in self.metric_series.iter_mut() {
+            *value = AggregationEngine::round_to(*value, places);
+        }
+    }
+}
+
+/// How anomalous a metric's most recent value is
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricAnomalyScore {
+    pub metric: String,
+    /// Most recent sample of the metric
+    pub latest_value: f64,
+    /// Z-score of the latest sample relative to the recent series
+    pub z_score: f64,
+    /// Number of samples in the recent series
+    pub sample_count: usize,
+}
+
+/// An aggregate together with proof of the events it was computed from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifiableAggregation {
+    pub result: AggregatedResult,
+    /// Events that contributed a value, in ID order
+    pub event_ids: Vec<EventId>,
+    /// Inclusion proofs for `event_ids`; `None` when nothing contributed
+    pub proof: Option<BatchProof>,
+}
+
+/// Inclusion proofs for several events against one root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventProofBatch {
+    /// Root every proof in `proof` verifies against
+    pub root: Option<CryptoHash>,
+    /// `None` when none of the requested events could be proven
+    pub proof: Option<BatchProof>,
+    /// Requested IDs without a proof
+    pub missing: Vec<EventId>,
+}
+
+/// A problem found while validating an externally supplied event
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum EventProblem {
+    /// The payload is not a JSON object
+    NotAnObject,
+    /// A required field is absent
+    MissingField(String),
+    /// All fields are present but some have the wrong type
+    Malformed(String),
+    /// `event_type` is empty
+    EmptyEventType,
+    /// `transaction_hash` is empty
+    EmptyTransactionHash,
+    /// `data` is not a JSON object
+    DataNotObject,
+    /// `source_app` is not a monitored application
+    UnknownApplication,
+    /// `source_chain` differs from the chain in the app's config
+    ChainMismatch { expected: ChainId },
+    /// The app's allow list does not include `event_type`
+    EventTypeNotAllowed,
+    /// `parent_event_id` does not refer to a captured event
+    UnknownParent(EventId),
+    /// `data` lacks fields required by the app's schema for this event type
+    SchemaViolation,
+}
+
+/// Result of validating an event before submission
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventValidation {
+    /// True when `problems` is empty
+    pub valid: bool,
+    pub problems: Vec<EventProblem>,
+}
+
+/// Fields a submitted event must carry
+const REQUIRED_EVENT_FIELDS: [&str; 8] = [
+    "id",
+    "source_app",
+    "source_chain",
+    "timestamp",
+    "event_type",
+    "data",
+    "transaction_hash",
+    "severity",
+];
+
+/// Fields of `CapturedEvent` that `GetEvents` can project
+pub const PROJECTABLE_EVENT_FIELDS: [&str; 13] = [
+    "id",
+    "source_app",
+    "source_chain",
+    "timestamp",
+    "event_type",
+    "data",
+    "transaction_hash",
+    "block_height",
+    "severity",
+    "parent_event_id",
+    "schema_valid",
+    "value",
+    "ingestion_lag_ms",
+];
+
+/// Severe events captured after a cursor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SevereEventsPage {
+    /// Matching events in ID order
+    pub events: Vec<CapturedEvent>,
+    /// Highest event ID seen so far; pass it as `after_event_id` on the next poll
+    pub cursor: Option<EventId>,
+}
+
+/// Ordering for metric discovery queries
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum MetricSort {
+    /// Storage order: byte-wise by key, so uppercase sorts before lowercase
+    #[default]
+    KeyOrder,
+    /// Case-insensitive alphabetical by name
+    Alphabetical,
+    /// Grouped by owning application, alphabetical within each group;
+    /// metrics without an owner come last
+    ByApp,
+}
+
+/// Event-derived metrics rolled up across every app carrying a tag
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagMetrics {
+    pub tag: String,
+    /// Monitored applications carrying the tag
+    pub applications: Vec<ApplicationId>,
+    /// Events from those applications within the time range
+    pub total_events: u64,
+    pub events_by_type: BTreeMap<String, u64>,
+    /// Sum of each defined metric over those events
+    pub metric_totals: BTreeMap<String, f64>,
+}
+
+/// A bucketed series with the anomalies detected in it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnomaliesWithSeries {
+    pub series: Vec<TimeSeriesPoint>,
+    /// Anomalous buckets; each `index` points into `series`
+    pub anomalies: Vec<AnomalyEvent>,
+}
+
+/// Bounds of the event IDs currently stored
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EventIdRange {
+    pub min_id: EventId,
+    pub max_id: EventId,
+    /// Events stored; less than the span when IDs have been removed
+    pub count: usize,
+}
+
+/// Root a proof was checked against
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum MatchedRoot {
+    /// The current Merkle root
+    Current,
+    /// The root recorded by this checkpoint
+    Checkpoint(u64),
+}
+
+/// Outcome of verifying a proof against a possibly stale root
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ToleratedProofVerification {
+    pub valid: bool,
+    /// Where the client's root was found; `None` if it is not a known root
+    pub matched: Option<MatchedRoot>,
+}
+
+/// A chain that has contributed stored events
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SourceChain {
+    pub chain_id: ChainId,
+    pub event_count: usize,
+}
+
+/// Events added between two Merkle checkpoints
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CheckpointDelta {
+    pub from_checkpoint: u64,
+    pub to_checkpoint: u64,
+    /// Number of events in `event_ids`
+    pub added: usize,
+    /// Events indexed at `to_checkpoint` but not at `from_checkpoint`
+    pub event_ids: Vec<EventId>,
+}
+
+/// Availability of an application derived from its heartbeat events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppUptime {
+    pub application_id: ApplicationId,
+    /// Heartbeats seen within the window
+    pub heartbeat_count: usize,
+    /// Gaps between heartbeats, or between a window edge and the nearest
+    /// heartbeat, longer than the expected interval
+    pub missed_gaps: usize,
+    /// Time beyond the expected interval summed over those gaps
+    pub downtime_ms: u64,
+    /// Fraction of the window the app was up, in `[0, 1]`
+    pub availability: f64,
+}
+
+/// Distribution of capture lag over a set of events, in milliseconds
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct IngestionLagStats {
+    /// Events with a recorded lag; the other fields are zero when none
+    pub sample_count: usize,
+    pub min_ms: f64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+    pub p95_ms: f64,
+}
+
+/// Distribution of serialized event `data` sizes, in bytes
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct EventSizeStats {
+    /// Events measured; the other fields are zero when none
+    pub sample_count: usize,
+    pub min_bytes: f64,
+    pub avg_bytes: f64,
+    pub max_bytes: f64,
+    pub p95_bytes: f64,
+}
+
+/// An event together with its descendants
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventTreeNode {
+    pub event: CapturedEvent,
+    /// Direct children in capture order
+    pub children: Vec<EventTreeNode>,
+}
+
+/// A reason events from an application are currently rejected
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RejectionReason {
+    /// The application's config has `enabled: false`
+    AppDisabled,
+    /// A rate-limiter precondition fails
+    RateLimited(RateLimitError),
+}
+
+/// Structured "why blocked" diagnostic for an application
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppDiagnosis {
+    pub application_id: ApplicationId,
+    /// Whether the application has a monitored config
+    pub monitored: bool,
+    /// Every currently-applicable rejection reason
+    pub reasons: Vec<RejectionReason>,
+    /// True when no rejection reason applies
+    pub healthy: bool,
+}
+
+impl AnalyticsState {
+    /// Explain every reason events from `application_id` would be rejected now
+    pub fn diagnose_app(&self, application_id: &ApplicationId) -> AppDiagnosis {
+        let config = self.monitored_applications.get(application_id);
+        let mut reasons = Vec::new();
+
+        if config.is_some_and(|config| !config.enabled) {
+            reasons.push(RejectionReason::AppDisabled);
+        }
+        reasons.extend(
+            self.rate_limiter
+                .diagnose(application_id, self.app_priority(application_id), self.current_block)
+                .into_iter()
+                .map(RejectionReason::RateLimited),
+        );
+
+        AppDiagnosis {
+            application_id: *application_id,
+            monitored: config.is_some(),
+            healthy: reasons.is_empty(),
+            reasons,
+        }
+    }
+
+    /// Fetch an event with all of its descendants.
+    ///
+    /// Parents must be captured before their children, so the tree is
+    /// acyclic and always terminates.
+    pub fn event_tree(&self, root_event_id: EventId) -> Option<EventTreeNode> {
+        let event = self.get_event(root_event_id)?.clone();
+        let children = self
+            .child_index
+            .get(&root_event_id)
+            .map(|ids| ids.iter().filter_map(|id| self.event_tree(*id)).collect())
+            .unwrap_or_default();
+        Some(EventTreeNode { event, children })
+    }
+
+    /// Events at or above `min_severity` with IDs after `after_event_id`.
+    ///
+    /// The returned cursor covers every captured event, not only matching
+    /// ones, so polls never rescan events that were already considered.
+    pub fn severe_events_since(
+        &self,
+        after_event_id: Option<EventId>,
+        min_severity: &EventSeverity,
+    ) -> SevereEventsPage {
+        let is_new = |event: &&CapturedEvent| after_event_id.is_none_or(|after| event.id > after);
+        let events = self
+            .events
+            .iter()
+            .filter(is_new)
+            .filter(|event| event.severity >= *min_severity)
+            .cloned()
+            .collect();
+        let newest = self.events.iter().filter(is_new).map(|event| event.id).max();
+
+        SevereEventsPage {
+            events,
+            cursor: newest.or(after_event_id),
+        }
+    }
+
+    /// Monitored applications that own a metric key.
+    ///
+    /// Metric keys are namespaced by convention as `{application_id}_{name}`,
+    /// so an application owns a key that equals its id or starts with its id
+    /// followed by `_`.
+    pub fn metric_owners(&self, metric: &str) -> Vec<ApplicationId> {
+        self.monitored_applications
+            .keys()
+            .filter(|application_id| owns_metric(application_id, metric))
+            .copied()
+            .collect()
+    }
+
+    /// Current value of every stored metric matching `filter`, keyed by
+    /// metric key
+    pub fn all_metrics(&self, filter: Option<&MetricFilter>) -> BTreeMap<String, MetricSnapshot> {
+        let tagged_apps: Option<Vec<ApplicationId>> = filter
+            .and_then(|filter| filter.tag.as_ref())
+            .map(|tag| {
+                self.monitored_applications
+                    .values()
+                    .filter(|config| config.tags.contains(tag))
+                    .map(|config| config.application_id)
+                    .collect()
+            });
+
+        self.aggregated_metrics
+            .iter()
+            .filter(|(key, _)| {
+                let Some(filter) = filter else {
+                    return true;
+                };
+                filter
+                    .application_id
+                    .is_none_or(|app| owns_metric(&app, key))
+                    && filter.name.as_ref().is_none_or(|name| key.contains(name.as_str()))
+                    && tagged_apps
+                        .as_ref()
+                        .is_none_or(|apps| apps.iter().any(|app| owns_metric(app, key)))
+            })
+            .map(|(key, value)| (key.clone(), MetricSnapshot::of(value)))
+            .collect()
+    }
+
+    /// Current value of every metric updated at or after `block`
+    pub fn metrics_changed_since(&self, block: u64) -> BTreeMap<String, MetricSnapshot> {
+        self.metric_updated_at
+            .iter()
+            .filter(|(_, updated_at)| **updated_at >= block)
+            .filter_map(|(key, _)| {
+                let value = self.aggregated_metrics.get(key)?;
+                Some((key.clone(), MetricSnapshot::of(value)))
+            })
+            .collect()
+    }
+
+    /// Order metric names according to `sort`
+    fn sort_metric_names(&self, names: &mut [String], sort: MetricSort) {
+        match sort {
+            MetricSort::KeyOrder => names.sort(),
+            MetricSort::Alphabetical => {
+                names.sort_by_cached_key(|name| (name.to_lowercase(), name.clone()))
+            }
+            MetricSort::ByApp => names.sort_by_cached_key(|name| {
+                let owner = self.metric_owners(name).into_iter().next();
+                (owner.is_none(), owner, name.to_lowercase(), name.clone())
+            }),
+        }
+    }
+
+    /// Names of every known metric, from definitions and stored values
+    pub fn metric_names(&self, sort: MetricSort) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .metric_definitions
+            .keys()
+            .chain(self.aggregated_metrics.keys())
+            .cloned()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        self.sort_metric_names(&mut names, sort);
+        names
+    }
+
+    /// All metric definitions
+    pub fn metric_definitions_sorted(&self, sort: MetricSort) -> Vec<MetricDefinition> {
+        let mut names: Vec<String> = self.metric_definitions.keys().cloned().collect();
+        self.sort_metric_names(&mut names, sort);
+        names
+            .iter()
+            .filter_map(|name| self.metric_definitions.get(name).cloned())
+            .collect()
+    }
+
+    /// Roll up event-derived metrics for all monitored apps tagged `tag`
+    pub fn tag_metrics(&self, tag: &str, time_range: Option<&TimeRange>) -> TagMetrics {
+        let applications: Vec<ApplicationId> = self
+            .monitored_applications
+            .values()
+            .filter(|config| config.tags.iter().any(|t| t == tag))
+            .map(|config| config.application_id)
+            .collect();
+
+        let mut rollup = TagMetrics {
+            tag: tag.to_string(),
+            applications: Vec::new(),
+            total_events: 0,
+            events_by_type: BTreeMap::new(),
+            metric_totals: BTreeMap::new(),
+        };
+        for app_id in &applications {
+            for event in self.get_app_events(app_id) {
+                if time_range.is_some_and(|range| !range.contains(event.timestamp)) {
+                    continue;
+                }
+                rollup.total_events += 1;
+                *rollup
+                    .events_by_type
+                    .entry(event.event_type.clone())
+                    .or_default() += 1;
+                for metric in self.metric_definitions.keys() {
+                    if let Some(value) = self.extract_event_metric(event, metric) {
+                        *rollup.metric_totals.entry(metric.clone()).or_default() += value;
+                    }
+                }
+            }
+        }
+        rollup.applications = applications;
+        rollup
+    }
+
+    /// Verify `proof` against `root` when it is the current root or the root
+    /// of one of the last `proof_checkpoint_tolerance` checkpoints.
+    ///
+    /// The newest matching checkpoint is reported; a root that was never
+    /// current or checkpointed within the tolerance fails.
+    pub fn verify_proof_with_tolerance(
+        &self,
+        proof: &MerkleProof,
+        root: &CryptoHash,
+    ) -> ToleratedProofVerification {
+        let matched = if self.merkle_index.get_root().as_ref() == Some(root) {
+            Some(MatchedRoot::Current)
+        } else {
+            self.checkpoints
+                .iter()
+                .enumerate()
+                .rev()
+                .take(self.config.proof_checkpoint_tolerance)
+                .find(|(_, checkpoint)| checkpoint.root.as_ref() == Some(root))
+                .map(|(id, _)| MatchedRoot::Checkpoint(id as u64))
+        };
+        ToleratedProofVerification {
+            valid: matched.is_some() && MerkleIndex::verify_proof(root, proof),
+            matched,
+        }
+    }
+
+    /// Merkle root as of the end of `block`; the current block and any
+    /// later one report the live root
+    pub fn merkle_root_at(&self, block: u64) -> Option<CryptoHash> {
+        if block >= self.current_block {
+            self.merkle_index.get_root()
+        } else {
+            self.merkle_index.get_root_at(block)
+        }
+    }
+
+    /// Chains with stored events, most events first
+    pub fn source_chains(&self) -> Vec<SourceChain> {
+        let mut chains: Vec<SourceChain> = self
+            .chain_index
+            .iter()
+            .filter(|(_, ids)| !ids.is_empty())
+            .map(|(chain_id, ids)| SourceChain {
+                chain_id: *chain_id,
+                event_count: ids.len(),
+            })
+            .collect();
+        chains.sort_by_key(|chain| std::cmp::Reverse(chain.event_count));
+        chains
+    }
+
+    /// Events added between two checkpoints, by diffing their leaf sets
+    pub fn checkpoint_delta(&self, from_checkpoint: u64, to_checkpoint: u64) -> Result<CheckpointDelta> {
+        let checkpoint = |id: u64| {
+            self.checkpoints
+                .get(id as usize)
+                .ok_or_else(|| AnalyticsError::QueryError(format!("Unknown checkpoint: {}", id)))
+        };
+        let from: BTreeSet<EventId> = checkpoint(from_checkpoint)?.event_ids.iter().copied().collect();
+        let event_ids: Vec<EventId> = checkpoint(to_checkpoint)?
+            .event_ids
+            .iter()
+            .copied()
+            .filter(|id| !from.contains(id))
+            .collect();
+        Ok(CheckpointDelta {
+            from_checkpoint,
+            to_checkpoint,
+            added: event_ids.len(),
+            event_ids,
+        })
+    }
+
+    /// Availability of `application_id` over `time_range` from heartbeats.
+    ///
+    /// Every stretch without a `heartbeat_type` event longer than
+    /// `expected_interval_ms` counts as down for the excess; the window edges
+    /// count as stretch boundaries. No heartbeats means 0% availability.
+    pub fn app_uptime(
+        &self,
+        application_id: &ApplicationId,
+        heartbeat_type: &str,
+        expected_interval_ms: u64,
+        time_range: &TimeRange,
+    ) -> Result<AppUptime> {
+        if expected_interval_ms == 0 {
+            return Err(AnalyticsError::QueryError(
+                "expected_interval_ms must be greater than zero".to_string(),
+            ));
+        }
+        if time_range.end <= time_range.start {
+            return Err(AnalyticsError::QueryError(
+                "time_range must not be empty".to_string(),
+            ));
+        }
+
+        let beats: Vec<Timestamp> = self
+            .get_app_events(application_id)
+            .into_iter()
+            .filter(|event| event.event_type == heartbeat_type && time_range.contains(event.timestamp))
+            .map(|event| event.timestamp)
+            .collect();
+        let window = time_range.end - time_range.start;
+        if beats.is_empty() {
+            return Ok(AppUptime {
+                application_id: *application_id,
+                heartbeat_count: 0,
+                missed_gaps: 0,
+                downtime_ms: window,
+                availability: 0.0,
+            });
+        }
+
+        let mut boundaries = Vec::with_capacity(beats.len() + 2);
+        boundaries.push(time_range.start);
+        boundaries.extend(&beats);
+        boundaries.push(time_range.end);
+
+        let mut missed_gaps = 0;
+        let mut downtime_ms = 0;
+        for pair in boundaries.windows(2) {
+            let gap = pair[1] - pair[0];
+            if gap > expected_interval_ms {
+                missed_gaps += 1;
+                downtime_ms += gap - expected_interval_ms;
+            }
+        }
+
+        Ok(AppUptime {
+            application_id: *application_id,
+            heartbeat_count: beats.len(),
+            missed_gaps,
+            downtime_ms,
+            availability: 1.0 - downtime_ms as f64 / window as f64,
+        })
+    }
+
+    /// Lowest and highest stored event IDs, or `None` when no events are stored.
+    ///
+    /// Every stored event has a Merkle leaf keyed by its ID, so this reads
+    /// the ends of the leaf map instead of scanning the store; imported
+    /// events can leave the store itself out of ID order.
+    pub fn event_id_range(&self) -> Option<EventIdRange> {
+        let leaves = &self.merkle_index.leaves;
+        Some(EventIdRange {
+            min_id: *leaves.first_key_value()?.0,
+            max_id: *leaves.last_key_value()?.0,
+            count: self.events.len(),
+        })
+    }
+
+    /// Capture lag statistics for events timestamped within `time_range`
+    pub fn ingestion_lag_stats(&self, time_range: &TimeRange) -> IngestionLagStats {
+        let lags: Vec<f64> = self
+            .get_events_in_range(time_range.start, time_range.end)
+            .into_iter()
+            .filter_map(|event| event.ingestion_lag_ms)
+            .map(|lag| lag as f64)
+            .collect();
+        if lags.is_empty() {
+            return IngestionLagStats::default();
+        }
+
+        IngestionLagStats {
+            sample_count: lags.len(),
+            min_ms: AggregationEngine::aggregate(&lags, &AggregationType::Min),
+            avg_ms: AggregationEngine::aggregate(&lags, &AggregationType::Average),
+            max_ms: AggregationEngine::aggregate(&lags, &AggregationType::Max),
+            p95_ms: AggregationEngine::aggregate(&lags, &AggregationType::Percentile(0.95)),
+        }
+    }
+
+    /// Sizes of the JSON-serialized `data` of stored events, optionally only
+    /// those from `application_id`
+    pub fn event_size_stats(&self, application_id: Option<&ApplicationId>) -> EventSizeStats {
+        let events = match application_id {
+            Some(app_id) => self.get_app_events(app_id),
+            None => self.events.iter().collect(),
+        };
+        let sizes: Vec<f64> = events
+            .into_iter()
+            .map(|event| event.data.to_string().len() as f64)
+            .collect();
+        if sizes.is_empty() {
+            return EventSizeStats::default();
+        }
+
+        EventSizeStats {
+            sample_count: sizes.len(),
+            min_bytes: AggregationEngine::aggregate(&sizes, &AggregationType::Min),
+            avg_bytes: AggregationEngine::aggregate(&sizes, &AggregationType::Average),
+            max_bytes: AggregationEngine::aggregate(&sizes, &AggregationType::Max),
+            p95_bytes: AggregationEngine::aggregate(&sizes, &AggregationType::Percentile(0.95)),
+        }
+    }
+
+    /// Check internal consistency of the event store, its indexes, the
+    /// Merkle tree and the rate limiter, reporting every violation found
+    pub fn check_invariants(&self) -> InvariantReport {
+        let mut violations = Vec::new();
+
+        let mut stored = BTreeSet::new();
+        for event in &self.events {
+            if !stored.insert(event.id) {
+                violations.push(InvariantViolation::DuplicateEventId(event.id));
+            }
+            if self.config.event_id_strategy == EventIdStrategy::Sequential
+                && event.id >= self.next_event_id
+            {
+                violations.push(InvariantViolation::EventIdNotYetAssigned {
+                    event_id: event.id,
+                    next_event_id: self.next_event_id,
+                });
+            }
+            if !self.merkle_index.leaves.contains_key(&event.id) {
+                violations.push(InvariantViolation::MissingMerkleLeaf(event.id));
+            }
+        }
+        if self.events.len() as u64 > self.total_events_captured {
+            violations.push(InvariantViolation::MoreEventsThanCaptured {
+                stored: self.events.len(),
+                captured: self.total_events_captured,
+            });
+        }
+        if self.merkle_index.leaves.len() != self.events.len() {
+            violations.push(InvariantViolation::MerkleLeafCountMismatch {
+                leaves: self.merkle_index.leaves.len(),
+                events: self.events.len(),
+            });
+        }
+
+        let indexes = [
+            ("app_index", self.app_index.values().flatten().collect::<Vec<_>>()),
+            ("chain_index", self.chain_index.values().flatten().collect()),
+            ("event_index", self.event_index.values().flatten().collect()),
+        ];
+        for (index, event_ids) in indexes {
+            violations.extend(
+                event_ids
+                    .into_iter()
+                    .filter(|event_id| !stored.contains(*event_id))
+                    .map(|event_id| InvariantViolation::DanglingIndexEntry {
+                        index: index.to_string(),
+                        event_id: *event_id,
+                    }),
+            );
+        }
+
+        let limiter = &self.rate_limiter;
+        for (app_id, counter) in &limiter.app_counters {
+            let limit = limiter.app_limit(app_id, self.app_priority(app_id));
+            if counter.count > limit {
+                violations.push(InvariantViolation::AppCounterOverLimit {
+                    app_id: *app_id,
+                    count: counter.count,
+                    limit,
+                });
+            }
+        }
+        if limiter.global_counter.count > limiter.global_limit() {
+            violations.push(InvariantViolation::GlobalCounterOverLimit {
+                count: limiter.global_counter.count,
+                limit: limiter.global_limit(),
+            });
+        }
+
+        InvariantReport {
+            healthy: violations.is_empty(),
+            violations,
+        }
+    }
+
+    /// Approximate encoded size of the state's main collections.
+    ///
+    /// Each collection is estimated from its length and the average CBOR
+    /// size of up to `STORAGE_ESTIMATE_SAMPLE_SIZE` evenly spaced items, so
+    /// the cost stays bounded however much has been captured.
+    pub fn storage_estimate(&self) -> StorageEstimate {
+        let merkle = &self.merkle_index;
+        let events_bytes = estimated_len(&self.events);
+        let metrics_bytes = estimated_len(&self.aggregated_metrics)
+            + estimated_len(&self.histogram_samples_seen)
+            + estimated_len(&self.metric_updated_at)
+            + estimated_len(&self.metric_definitions);
+        let indexes_bytes = estimated_len(&self.event_index)
+            + estimated_len(&self.app_index)
+            + estimated_len(&self.chain_index)
+            + estimated_len(&self.child_index)
+            + estimated_len(&self.tx_hash_index)
+            + estimated_len(&self.transaction_index)
+            + estimated_len(&self.chain_transaction_index);
+        let merkle_bytes = estimated_len(&merkle.leaves)
+            + estimated_len(&merkle.internal_nodes)
+            + estimated_len(&merkle.root_history);
+        let rbac_bytes = encoded_len(&self.rbac);
+
+        StorageEstimate {
+            events_bytes,
+            metrics_bytes,
+            indexes_bytes,
+            merkle_bytes,
+            rbac_bytes,
+            total_bytes: events_bytes + metrics_bytes + indexes_bytes + merkle_bytes + rbac_bytes,
+        }
+    }
+
+    /// Events in `time_range` whose transaction hash has no captured
+    /// `TransactionRecord`, in canonical order
+    pub fn orphan_events(&self, time_range: &TimeRange) -> Vec<CapturedEvent> {
+        self.get_events_in_range(time_range.start, time_range.end)
+            .into_iter()
+            .filter(|event| !self.transaction_index.contains_key(&event.transaction_hash))
+            .cloned()
+            .collect()
+    }
+
+    /// Extract a numeric metric value from an event.
+    ///
+    /// Uses the metric definition's `extraction_path` when one is defined
+    /// (a JSON pointer if it starts with `/`, a top-level key otherwise).
+    /// Without a definition, an event's direct `value` is used for the metric
+    /// named after its `event_type`, falling back to a top-level field named
+    /// after the metric.
+    pub fn extract_event_metric(&self, event: &CapturedEvent, metric: &str) -> Option<f64> {
+        let definition = self.metric_definitions.get(metric);
+        if definition.is_none() && event.event_type == metric {
+            if let Some(value) = event.value {
+                return Some(value);
+            }
+        }
+
+        let path = definition
+            .map(|definition| definition.extraction_path.as_str())
+            .unwrap_or(metric);
+
+        let value = if path.starts_with('/') {
+            event.data.pointer(path)
+        } else {
+            event.data.get(path)
+        };
+        value.and_then(|v| v.as_f64())
+    }
+
+    /// A defined metric over events timestamped within `time_range`,
+    /// combined with the definition's aggregation method.
+    ///
+    /// `None` when the metric is not defined or no event yields a value.
+    pub fn defined_metric_value(&self, name: &str, time_range: &TimeRange) -> Option<f64> {
+        let definition = self.metric_definitions.get(name)?;
+        let values: Vec<f64> = self
+            .get_events_in_range(time_range.start, time_range.end)
+            .into_iter()
+            .filter(|event| event.event_type != ANOMALY_EVENT_TYPE)
+            .filter_map(|event| self.extract_event_metric(event, name))
+            .collect();
+        definition.aggregation.apply(&values)
+    }
+
+    /// Exponentially weighted moving average of `metric` over events
+    /// timestamped within `time_range`, in timestamp order.
+    ///
+    /// Fails unless `0 < alpha <= 1`.
+    pub fn metric_ewma(
+        &self,
+        metric: &str,
+        alpha: f64,
+        time_range: &TimeRange,
+    ) -> Result<Vec<MovingAveragePoint>> {
+        if !(alpha > 0.0 && alpha <= 1.0) {
+            return Err(AnalyticsError::QueryError(format!(
+                "alpha must be in (0, 1], got {}",
+                alpha
+            )));
+        }
+        let values: Vec<(Timestamp, f64)> = self
+            .get_events_in_range(time_range.start, time_range.end)
+            .into_iter()
+            .filter(|event| event.event_type != ANOMALY_EVENT_TYPE)
+            .filter_map(|event| {
+                self.extract_event_metric(event, metric)
+                    .map(|value| (event.timestamp, value))
+            })
+            .collect();
+        Ok(AggregationEngine::ewma(&values, alpha))
+    }
+
+    /// Per-bucket series for `metric` over `time_range`.
+    ///
+    /// Buckets hold the sum of direct event values for the metric as a gauge
+    /// when any event in range carries one, and the event count otherwise.
+    /// Buckets start at `time_range.start`; a series scoped to one app
+    /// covers only its events, with buckets aligned to its local clock.
+    pub fn time_series(
+        &self,
+        metric: &str,
+        time_range: &TimeRange,
+        granularity_ms: u64,
+        application_id: Option<&ApplicationId>,
+    ) -> Vec<TimeSeriesPoint> {
+        if granularity_ms == 0 {
+            return Vec::new();
+        }
+        let origin = match application_id {
+            Some(app) => {
+                let offset_ms = self
+                    .monitored_applications
+                    .get(app)
+                    .and_then(|config| config.utc_offset_minutes)
+                    .map_or(0, |minutes| minutes as i64 * 60_000);
+                TimeBucket::from_timestamp_with_offset(time_range.start, granularity_ms, offset_ms).start
+            }
+            None => time_range.start,
+        };
+        let Ok(bucket_starts) = Self::bucket_grid(&TimeRange::new(origin, time_range.end), granularity_ms) else {
+            return Vec::new();
+        };
+        let mut counts = vec![0u64; bucket_starts.len()];
+        let mut sums = vec![0.0; bucket_starts.len()];
+        let mut has_values = false;
+
+        for event in self.get_events_in_range(time_range.start, time_range.end) {
+            if application_id.is_some_and(|app| event.source_app != *app) {
+                continue;
+            }
+            let bucket = ((event.timestamp - origin) / granularity_ms) as usize;
+            if bucket >= bucket_starts.len() {
+                continue;
+            }
+            counts[bucket] += 1;
+            if event.event_type == metric {
+                if let Some(value) = event.value {
+                    sums[bucket] += value;
+                    has_values = true;
+                }
+            }
+        }
+
+        bucket_starts
+            .into_iter()
+            .enumerate()
+            .map(|(i, timestamp)| TimeSeriesPoint {
+                timestamp,
+                value: if has_values {
+                    MetricValue::Gauge(sums[i])
+                } else {
+                    MetricValue::Counter(counts[i])
+                },
+            })
+            .collect()
+    }
+
+    /// `time_series` for `metric` together with the buckets whose value is
+    /// more than `sensitivity` standard deviations from the series mean
+    pub fn anomalies_with_series(
+        &self,
+        metric: &str,
+        sensitivity: f64,
+        time_range: &TimeRange,
+        granularity_ms: u64,
+    ) -> AnomaliesWithSeries {
+        let series = self.time_series(metric, time_range, granularity_ms, None);
+        let values: Vec<(Timestamp, f64)> = series
+            .iter()
+            .map(|point| (point.timestamp, point.value.as_f64()))
+            .collect();
+        AnomaliesWithSeries {
+            anomalies: AggregationEngine::detect_anomalies(&values, sensitivity),
+            series,
+        }
+    }
+
+    /// Recent sample series for every known metric, oldest first.
+    ///
+    /// Defined metrics are extracted from captured events other than anomaly
+    /// alerts; histogram-valued aggregated metrics contribute their stored
+    /// samples. Each series keeps at most `MAX_ANOMALY_SAMPLES_PER_METRIC` of
+    /// the latest samples.
+    fn recent_metric_series(&self) -> Vec<(String, Vec<f64>)> {
+        let mut series = Vec::new();
+
+        for name in self.metric_definitions.keys() {
+            let values: Vec<f64> = self
+                .event_index
+                .values()
+                .flatten()
+                .filter_map(|id| self.get_event(*id))
+                .filter(|event| event.event_type != ANOMALY_EVENT_TYPE)
+                .filter_map(|event| self.extract_event_metric(event, name))
+                .collect();
+            series.push((name.clone(), values));
+        }
+        for (key, value) in &self.aggregated_metrics {
+            if let MetricValue::Histogram(samples) = value {
+                series.push((key.clone(), samples.clone()));
+            }
+        }
+
+        for (_, values) in series.iter_mut() {
+            let excess = values.len().saturating_sub(MAX_ANOMALY_SAMPLES_PER_METRIC);
+            values.drain(..excess);
+        }
+        series
+    }
+
+    /// Metrics matching `metric` whose latest `window` values barely vary.
+    ///
+    /// Defined metrics are read from events timestamped within `time_range`;
+    /// histogram metrics use their stored samples, which carry no timestamps.
+    /// Series with fewer than `window` samples are not judged.
+    pub fn detect_flatlines(
+        &self,
+        metric: &str,
+        window: usize,
+        time_range: &TimeRange,
+        threshold: f64,
+    ) -> Vec<Flatline> {
+        let window = window.max(2);
+        let mut series: Vec<(String, Vec<f64>)> = Vec::new();
+        for name in self.metric_definitions.keys().filter(|name| name.contains(metric)) {
+            let values = self
+                .get_events_in_range(time_range.start, time_range.end)
+                .into_iter()
+                .filter(|event| event.event_type != ANOMALY_EVENT_TYPE)
+                .filter_map(|event| self.extract_event_metric(event, name))
+                .collect();
+            series.push((name.clone(), values));
+        }
+        for (key, value) in &self.aggregated_metrics {
+            if let MetricValue::Histogram(samples) = value {
+                if key.contains(metric) {
+                    series.push((key.clone(), samples.clone()));
+                }
+            }
+        }
+
+        series
+            .into_iter()
+            .filter_map(|(metric, values)| {
+                let recent = values.get(values.len().checked_sub(window)?..)?;
+                let value = *recent.last()?;
+                let std_dev = AggregationEngine::std_dev(recent);
+                (std_dev < threshold).then_some(Flatline {
+                    metric,
+                    value,
+                    std_dev,
+                    window,
+                })
+            })
+            .collect()
+    }
+
+    /// Metrics whose latest value deviates most from their recent series.
+    ///
+    /// Returns at most `top_n` metrics with `|z| > sensitivity`, highest
+    /// first, ties broken by metric name.
+    pub fn anomalous_metrics(&self, sensitivity: f64, top_n: usize) -> Vec<MetricAnomalyScore> {
+        let mut scores: Vec<MetricAnomalyScore> = self
+            .recent_metric_series()
+            .into_iter()
+            .filter_map(|(metric, values)| {
+                let latest_value = *values.last()?;
+                let std_dev = AggregationEngine::std_dev(&values);
+                if std_dev == 0.0 {
+                    return None;
+                }
+                let z_score = (latest_value - AggregationEngine::mean(&values)) / std_dev;
+                (z_score.abs() > sensitivity).then_some(MetricAnomalyScore {
+                    metric,
+                    latest_value,
+                    z_score,
+                    sample_count: values.len(),
+                })
+            })
+            .collect();
+
+        scores.sort_by(|a, b| {
+            b.z_score
+                .abs()
+                .total_cmp(&a.z_score.abs())
+                .then_with(|| a.metric.cmp(&b.metric))
+        });
+        scores.truncate(top_n);
+        scores
+    }
+
+    /// Check an event against the standardized schema before submission.
+    ///
+    /// Reports every problem found rather than stopping at the first. Checks
+    /// that need a well-formed event only run once it deserializes.
+    pub fn validate_event(&self, event: &serde_json::Value) -> EventValidation {
+        let problems = self.event_problems(event);
+        EventValidation {
+            valid: problems.is_empty(),
+            problems,
+        }
+    }
+
+    fn event_problems(&self, event: &serde_json::Value) -> Vec<EventProblem> {
+        let Some(fields) = event.as_object() else {
+            return vec![EventProblem::NotAnObject];
+        };
+
+        let mut problems: Vec<EventProblem> = REQUIRED_EVENT_FIELDS
+            .iter()
+            .filter(|field| !fields.contains_key(**field))
+            .map(|field| EventProblem::MissingField(field.to_string()))
+            .collect();
+        if fields.get("data").is_some_and(|data| !data.is_object()) {
+            problems.push(EventProblem::DataNotObject);
+        }
+        if !problems.is_empty() {
+            return problems;
+        }
+
+        let mut event: CapturedEvent = match serde_json::from_value(event.clone()) {
+            Ok(event) => event,
+            Err(e) => return vec![EventProblem::Malformed(e.to_string())],
+        };
+
+        if event.event_type.is_empty() {
+            problems.push(EventProblem::EmptyEventType);
+        }
+        if event.transaction_hash.is_empty() {
+            problems.push(EventProblem::EmptyTransactionHash);
+        }
+        match self.monitored_applications.get(&event.source_app) {
+            None => problems.push(EventProblem::UnknownApplication),
+            Some(config) => {
+                if config.chain_id != event.source_chain {
+                    problems.push(EventProblem::ChainMismatch {
+                        expected: config.chain_id,
+                    });
+                }
+                if !config.allows_event_type(&event.event_type) {
+                    problems.push(EventProblem::EventTypeNotAllowed);
+                }
+            }
+        }
+        if let Some(parent_id) = event.parent_event_id {
+            if self.get_event(parent_id).is_none() {
+                problems.push(EventProblem::UnknownParent(parent_id));
+            }
+        }
+        self.apply_event_schema(&mut event);
+        if event.schema_valid == Some(false) {
+            problems.push(EventProblem::SchemaViolation);
+        }
+        problems
+    }
+
+    /// Aggregate a metric over captured events with a batch inclusion proof.
+    ///
+    /// Considers events in `[start_time, end_time]` from the apps in
+    /// `app_filter` (all apps if unset) that yield a value for the metric.
+    pub fn verifiable_aggregation(&self, query: &AggregationQuery) -> VerifiableAggregation {
+        let (event_ids, values) = self.aggregation_inputs(query);
+        let proof = self
+            .merkle_index
+            .generate_batch_proof(&event_ids, self.current_block);
+        VerifiableAggregation {
+            result: AggregatedResult::from_values(
+                query.metric.clone(),
+                query.aggregation.clone(),
+                &values,
+            ),
+            event_ids,
+            proof,
+        }
+    }
+
+    /// IDs, sorted, and metric values of the events `query` spans
+    fn aggregation_inputs(&self, query: &AggregationQuery) -> (Vec<EventId>, Vec<f64>) {
+        let mut event_ids = Vec::new();
+        let mut values = Vec::new();
+        for event in self.get_events_in_range(query.start_time, query.end_time) {
+            if let Some(apps) = &query.app_filter {
+                if !apps.contains(&event.source_app) {
+                    continue;
+                }
+            }
+            if let Some(value) = self.extract_event_metric(event, &query.metric) {
+                event_ids.push(event.id);
+                values.push(value);
+            }
+        }
+        event_ids.sort_unstable();
+        (event_ids, values)
+    }
+
+    /// Aggregate the stored metrics whose key contains `query.metric`.
+    ///
+    /// Values of different metric types are handled per the
+    /// `mixed_metric_types` policy: coerced and flagged, or rejected.
+    pub fn aggregate_metric(&self, query: &AggregationQuery) -> Result<AggregatedResult> {
+        let matching: Vec<&MetricValue> = self
+            .aggregated_metrics
+            .iter()
+            .filter(|(k, _)| k.contains(&query.metric))
+            .map(|(_, v)| v)
+            .collect();
+        let mixed_types = matching
+            .windows(2)
+            .any(|pair| pair[0].metric_type() != pair[1].metric_type());
+        if mixed_types && self.config.mixed_metric_types == MixedMetricPolicy::Reject {
+            return Err(AnalyticsError::QueryError(format!(
+                "Metric {} mixes value types",
+                query.metric
+            )));
+        }
+
+        let values: Vec<f64> = matching.iter().map(|v| v.as_f64()).collect();
+        let mut result =
+            AggregatedResult::from_values(query.metric.clone(), query.aggregation.clone(), &values);
+        result.mixed_types = mixed_types;
+        Ok(result)
+    }
+
+    /// Answer a cross-chain `AggregationRequest`.
+    ///
+    /// Queries that fail are skipped. The proof covers the events the
+    /// queries span, as selected by `verifiable_aggregation`, up to
+    /// `MAX_PROOFS_PER_QUERY` in all, lowest IDs first. It is `None` when
+    /// no such event is indexed.
+    pub fn answer_aggregation_request(
+        &self,
+        request_id: u64,
+        queries: &[AggregationQuery],
+    ) -> Message {
+        let results = queries
+            .iter()
+            .filter_map(|query| match self.aggregate_metric(query) {
+                Ok(result) => Some(result),
+                Err(e) => {
+                    log::warn!("Skipping aggregation query: {}", e);
+                    None
+                }
+            })
+            .collect();
+
+        let event_ids: BTreeSet<EventId> = queries
+            .iter()
+            .flat_map(|query| self.aggregation_inputs(query).0)
+            .collect();
+        let mut event_ids: Vec<EventId> = event_ids.into_iter().collect();
+        event_ids.truncate(MAX_PROOFS_PER_QUERY);
+
+        Message::AggregationResponse {
+            request_id,
+            results,
+            proof: self.merkle_index.generate_batch_proof(&event_ids, request_id),
+        }
+    }
+
+    /// Answer a `SyncRequest`: every stored event with an ID of at least
+    /// `from_event_id`, in ID order, split into `SyncBatch` messages of at
+    /// most `sync_batch_size` events, each with an inclusion proof for its
+    /// events. Batch IDs count up from 0.
+    pub fn sync_batches(&self, from_event_id: EventId) -> Vec<Message> {
+        let mut events: Vec<&CapturedEvent> = self
+            .events
+            .iter()
+            .filter(|event| event.id >= from_event_id)
+            .collect();
+        events.sort_by_key(|event| event.id);
+
+        events
+            .chunks(self.config.sync_batch_size.max(1))
+            .enumerate()
+            .map(|(batch_id, chunk)| {
+                let event_ids: Vec<EventId> = chunk.iter().map(|event| event.id).collect();
+                Message::SyncBatch {
+                    events: chunk.iter().map(|event| (*event).clone()).collect(),
+                    batch_proof: self
+                        .merkle_index
+                        .generate_batch_proof(&event_ids, batch_id as u64),
+                }
+            })
+            .collect()
+    }
+
+    /// Matching events reduced to the requested fields.
+    ///
+    /// Each event becomes a JSON object holding only `fields`; unknown field
+    /// names are rejected.
+    pub fn query_events_projected(
+        &self,
+        filters: &EventFilters,
+        pagination: &Pagination,
+        fields: &[String],
+    ) -> Result<Vec<serde_json::Value>> {
+        if let Some(unknown) = fields
+            .iter()
+            .find(|field| !PROJECTABLE_EVENT_FIELDS.contains(&field.as_str()))
+        {
+            return Err(AnalyticsError::QueryError(format!(
+                "Unknown event field: {}",
+                unknown
+            )));
+        }
+
+        self.query_events(filters, pagination)
+            .into_iter()
+            .map(|event| {
+                let serde_json::Value::Object(mut full) = serde_json::to_value(event)? else {
+                    return Ok(serde_json::Value::Null);
+                };
+                let projected: serde_json::Map<String, serde_json::Value> = fields
+                    .iter()
+                    .filter_map(|field| full.remove_entry(field))
+                    .collect();
+                Ok(serde_json::Value::Object(projected))
+            })
+            .collect()
+    }
+
+    /// Inclusion proofs for up to `MAX_PROOFS_PER_QUERY` events against the current root
+    pub fn event_proofs(&self, event_ids: &[EventId]) -> Result<EventProofBatch> {
+        if event_ids.len() > MAX_PROOFS_PER_QUERY {
+            return Err(AnalyticsError::QueryError(format!(
+                "At most {} event IDs per proof query, got {}",
+                MAX_PROOFS_PER_QUERY,
+                event_ids.len()
+            )));
+        }
+
+        let proof = self
+            .merkle_index
+            .generate_batch_proof(event_ids, self.current_block);
+        let proven: BTreeSet<EventId> = proof
+            .iter()
+            .flat_map(|batch| batch.proofs.iter().map(|proof| proof.event_id))
+            .collect();
+        Ok(EventProofBatch {
+            root: self.merkle_index.get_root(),
+            proof,
+            missing: event_ids
+                .iter()
+                .copied()
+                .filter(|id| !proven.contains(id))
+                .collect(),
+        })
+    }
+
+    /// Bucket start timestamps covering `time_range` at `granularity_ms`
+    fn bucket_grid(time_range: &TimeRange, granularity_ms: u64) -> Result<Vec<Timestamp>> {
+        if granularity_ms == 0 {
+            return Err(AnalyticsError::QueryError(
+                "granularity_ms must be greater than zero".to_string(),
+            ));
+        }
+        if time_range.end < time_range.start {
+            return Err(AnalyticsError::QueryError(
+                "time_range end precedes start".to_string(),
+            ));
+        }
+
+        let mut starts = Vec::new();
+        let mut current = time_range.start;
+        while current <= time_range.end {
+            starts.push(current);
+            current = match current.checked_add(granularity_ms) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+        Ok(starts)
+    }
+
+    /// Correlate per-bucket event volume of `event_type` with per-bucket metric values
+    pub fn correlate_volume_with_metric(
+        &self,
+        event_type: &str,
+        metric: &str,
+        time_range: &TimeRange,
+        granularity_ms: u64,
+    ) -> Result<VolumeCorrelation> {
+        let bucket_starts = Self::bucket_grid(time_range, granularity_ms)?;
+        let mut volume_series = vec![0.0; bucket_starts.len()];
+        let mut metric_series = vec![0.0; bucket_starts.len()];
+
+        for event in self.get_events_in_range(time_range.start, time_range.end) {
+            let bucket = ((event.timestamp - time_range.start) / granularity_ms) as usize;
+            if bucket >= bucket_starts.len() {
+                continue;
+            }
+            if event.event_type == event_type {
+                volume_series[bucket] += 1.0;
+            }
+            if let Some(value) = self.extract_event_metric(event, metric) {
+                metric_series[bucket] += value;
+            }
+        }
+
+        Ok(VolumeCorrelation {
+            event_type: event_type.to_string(),
+            metric: metric.to_string(),
+            coefficient: AggregationEngine::correlation(&volume_series, &metric_series),
+            bucket_starts,
+            volume_series,
+            metric_series,
+        })
+    }
+}
+
+/// Whether a metric key belongs to `application_id`.
+///
+/// Metric keys are namespaced by convention as `{application_id}_{name}`.
+fn owns_metric(application_id: &ApplicationId, metric: &str) -> bool {
+    let prefix = application_id.to_string();
+    metric
+        .strip_prefix(prefix.as_str())
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('_'))
+}
+
+/// CBOR-encoded size of a value, zero if it cannot be encoded
+fn encoded_len<T: Serialize + ?Sized>(value: &T) -> u64 {
+    let mut bytes = Vec::new();
+    ciborium::into_writer(value, &mut bytes).map_or(0, |_| bytes.len() as u64)
+}
+
+/// Estimated CBOR size of a collection: its length times the average size
+/// of up to `STORAGE_ESTIMATE_SAMPLE_SIZE` evenly spaced items
+fn estimated_len<'a, C>(collection: &'a C) -> u64
+where
+    &'a C: IntoIterator,
+    <&'a C as IntoIterator>::IntoIter: ExactSizeIterator,
+    <&'a C as IntoIterator>::Item: Serialize,
+{
+    let items = collection.into_iter();
+    let count = items.len();
+    if count == 0 {
+        return 0;
+    }
+    let step = count.div_ceil(STORAGE_ESTIMATE_SAMPLE_SIZE);
+    let (sampled, sampled_bytes) = items
+        .step_by(step)
+        .fold((0u64, 0u64), |(n, bytes), item| (n + 1, bytes + encoded_len(&item)));
+    sampled_bytes * count as u64 / sampled
+}
@@ -0,0 +1,204 @@
+//! Query Result Cache for Pine Analytics
+//!
+//! Serves repeated identical read queries without recomputing them.
+
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+use crate::{Request, Response};
+
+/// Default number of cached responses
+pub const DEFAULT_QUERY_CACHE_CAPACITY: usize = 64;
+
+/// A cached response and the serialized request it answers
+#[derive(Debug)]
+struct CacheEntry {
+    request: String,
+    response: Response,
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheInner {
+    entries: BTreeMap<u64, CacheEntry>,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+/// Bounded least-recently-used cache of query responses.
+///
+/// The service reads a snapshot of the state taken when it starts, and the
+/// cache lives no longer than that snapshot, so a cached response stays
+/// valid for as long as it is kept. Entries are found by a hash of the
+/// serialized request and only served if the request itself matches. Like
+/// `QueryStats`, the cache lives behind a lock so it can be used from
+/// `&self`.
+#[derive(Debug)]
+pub struct QueryCache {
+    capacity: usize,
+    inner: Mutex<CacheInner>,
+}
+
+impl Default for QueryCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_QUERY_CACHE_CAPACITY)
+    }
+}
+
+impl QueryCache {
+    /// Create a cache holding at most `capacity` responses
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(CacheInner::default()),
+        }
+    }
+
+    /// Whether responses to this request may be cached.
+    ///
+    /// Everything answered from the state snapshot is; usage statistics
+    /// change with every query, so those are always recomputed.
+    pub fn is_cacheable(request: &Request) -> bool {
+        !matches!(request, Request::GetQueryStats)
+    }
+
+    /// Serialized request and the hash it is filed under
+    fn key(request: &Request) -> (u64, String) {
+        let encoded = serde_json::to_string(request).unwrap_or_default();
+        let mut hasher = DefaultHasher::new();
+        encoded.hash(&mut hasher);
+        (hasher.finish(), encoded)
+    }
+
+    /// Look up the response to `request`
+    pub fn get(&self, request: &Request) -> Option<Response> {
+        let (key, encoded) = Self::key(request);
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.clock += 1;
+        let now = inner.clock;
+
+        let hit = match inner.entries.get_mut(&key) {
+            Some(entry) if entry.request == encoded => {
+                entry.last_used = now;
+                Some(entry.response.clone())
+            }
+            _ => None,
+        };
+        if hit.is_some() {
+            inner.hits += 1;
+        } else {
+            inner.misses += 1;
+        }
+        hit
+    }
+
+    /// Store the response to `request`, evicting the least recently used
+    /// entry when full
+    pub fn insert(&self, request: &Request, response: Response) {
+        if self.capacity == 0 {
+            return;
+        }
+        let (key, encoded) = Self::key(request);
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.clock += 1;
+        let now = inner.clock;
+
+        if !inner.entries.contains_key(&key) && inner.entries.len() >= self.capacity {
+            let oldest = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                inner.entries.remove(&oldest);
+            }
+        }
+
+        inner.entries.insert(
+            key,
+            CacheEntry {
+                request: encoded,
+                response,
+                last_used: now,
+            },
+        );
+    }
+
+    /// Snapshot of cache counters
+    pub fn stats(&self) -> QueryCacheStats {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        QueryCacheStats {
+            hits: inner.hits,
+            misses: inner.misses,
+            entries: inner.entries.len(),
+        }
+    }
+}
+
+/// Query cache counters
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct QueryCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Responses currently cached
+    pub entries: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EventFilters, Pagination};
+
+    fn events_request(offset: usize) -> Request {
+        Request::GetEvents {
+            filters: EventFilters::default(),
+            pagination: Pagination {
+                offset,
+                ..Pagination::default()
+            },
+            fields: None,
+        }
+    }
+
+    #[test]
+    fn test_repeated_query_served_from_cache() {
+        let cache = QueryCache::new(4);
+        assert!(cache.get(&events_request(0)).is_none());
+        cache.insert(&events_request(0), Response::Events(vec![]));
+        assert!(cache.get(&events_request(0)).is_some());
+        assert!(cache.get(&events_request(0)).is_some());
+        assert!(cache.get(&events_request(1)).is_none());
+        assert_eq!(cache.stats().hits, 2);
+        assert_eq!(cache.stats().misses, 2);
+    }
+
+    #[test]
+    fn test_colliding_key_not_served_for_another_request() {
+        let cache = QueryCache::new(4);
+        cache.insert(&events_request(0), Response::Events(vec![]));
+        // File a different request under the first one's hash
+        let (key, _) = QueryCache::key(&events_request(0));
+        let (_, other) = QueryCache::key(&events_request(1));
+        cache.inner.lock().unwrap().entries.get_mut(&key).unwrap().request = other;
+        assert!(cache.get(&events_request(0)).is_none());
+    }
+
+    #[test]
+    fn test_least_recently_used_entry_evicted() {
+        let cache = QueryCache::new(2);
+        let (a, b, c) = (events_request(0), events_request(1), events_request(2));
+        cache.insert(&a, Response::Events(vec![]));
+        cache.insert(&b, Response::Events(vec![]));
+        assert!(cache.get(&a).is_some());
+
+        cache.insert(&c, Response::Events(vec![]));
+        assert!(cache.get(&a).is_some());
+        assert!(cache.get(&b).is_none());
+        assert!(cache.get(&c).is_some());
+        assert_eq!(cache.stats().entries, 2);
+    }
+}
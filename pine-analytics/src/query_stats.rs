@@ -0,0 +1,93 @@
+//! Query Usage Statistics for Pine Analytics
+//!
+//! Tracks how heavily the service read path is used, per query kind.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::Request;
+
+/// Per-kind counters for served queries.
+///
+/// The service only holds shared references to its state, so counters live
+/// behind a lock and can be bumped from `&self`.
+#[derive(Debug, Default)]
+pub struct QueryStats {
+    counters: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl QueryStats {
+    /// Create empty statistics
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that a query has been served
+    pub fn record(&self, request: &Request) {
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        *counters.entry(request.kind()).or_insert(0) += 1;
+    }
+
+    /// Number of queries served for a given kind
+    pub fn count(&self, kind: &str) -> u64 {
+        let counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        counters.get(kind).copied().unwrap_or(0)
+    }
+
+    /// Snapshot of all counters
+    pub fn snapshot(&self) -> QueryStatsResponse {
+        let counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        QueryStatsResponse {
+            total_queries: counters.values().sum(),
+            per_kind: counters
+                .iter()
+                .map(|(kind, count)| (kind.to_string(), *count))
+                .collect(),
+        }
+    }
+}
+
+/// Query usage statistics response
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct QueryStatsResponse {
+    /// Total queries served across all kinds
+    pub total_queries: u64,
+    /// Queries served per kind, ordered by kind name
+    pub per_kind: Vec<(String, u64)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EventFilters, Pagination, TimeRange};
+
+    #[test]
+    fn test_counters_increment_per_kind() {
+        let stats = QueryStats::new();
+
+        for _ in 0..3 {
+            stats.record(&Request::GetEvents {
+                filters: EventFilters::default(),
+                pagination: Pagination::default(),
+                fields: None,
+            });
+        }
+        stats.record(&Request::GetCorrelation {
+            metrics: vec!["a".to_string(), "b".to_string()],
+            time_range: TimeRange::new(0, 10),
+            max_samples: None,
+            round_to: None,
+        });
+        stats.record(&Request::GetQueryStats);
+
+        assert_eq!(stats.count("GetEvents"), 3);
+        assert_eq!(stats.count("GetCorrelation"), 1);
+        assert_eq!(stats.count("GetQueryStats"), 1);
+        assert_eq!(stats.count("GetMerkleRoot"), 0);
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.total_queries, 5);
+        assert_eq!(snapshot.per_kind.len(), 3);
+    }
+}
@@ -3,9 +3,11 @@
 //! Protects against DoS attacks and manages high-throughput scenarios.
 
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, VecDeque};
 
-use crate::state::ApplicationId;
+use crate::aggregations::AggregationEngine;
+use crate::error::AnalyticsError;
+use crate::state::{ApplicationId, Timestamp};
 
 /// Rate limit configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,8 +20,51 @@ pub struct RateLimitConfig {
     pub burst_multiplier: f64,
     /// Cooldown blocks after rate limit is hit
     pub cooldown_blocks: u64,
+    /// Upper bound for escalated cooldowns of repeat offenders
+    #[serde(default = "default_max_cooldown_blocks")]
+    pub max_cooldown_blocks: u64,
+    /// Blocks of good behavior after an unblock that reset escalation
+    #[serde(default = "default_escalation_reset_blocks")]
+    pub escalation_reset_blocks: u64,
+    /// Extra per-app limit per point of `AppConfig::priority`: the default
+    /// limit is multiplied by `1 + priority * priority_factor`
+    #[serde(default = "default_priority_factor")]
+    pub priority_factor: f64,
     /// Whether rate limiting is enabled
     pub enabled: bool,
+    /// Derive per-app limits from observed traffic instead of using
+    /// `max_events_per_app_per_block`
+    #[serde(default)]
+    pub adaptive: Option<AdaptiveLimitConfig>,
+    /// How per-app limits are enforced
+    #[serde(default)]
+    pub algorithm: RateLimitAlgorithm,
+}
+
+/// How per-app limits are enforced
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum RateLimitAlgorithm {
+    /// Count events per block, allowing the full burst limit every block
+    #[default]
+    FixedWindow,
+    /// Each app holds up to its burst limit in tokens and regains its base
+    /// limit in tokens per block, so a burst cannot repeat block after block
+    TokenBucket,
+    /// Count each app's events over the last `window_ms` milliseconds of
+    /// capture time, with no reset at block boundaries
+    SlidingWindow { window_ms: u64 },
+}
+
+fn default_max_cooldown_blocks() -> u64 {
+    80
+}
+
+fn default_escalation_reset_blocks() -> u64 {
+    100
+}
+
+fn default_priority_factor() -> f64 {
+    0.1
 }
 
 impl Default for RateLimitConfig {
@@ -29,11 +74,116 @@ impl Default for RateLimitConfig {
             max_total_events_per_block: 1000,
             burst_multiplier: 1.5,
             cooldown_blocks: 5,
+            max_cooldown_blocks: default_max_cooldown_blocks(),
+            escalation_reset_blocks: default_escalation_reset_blocks(),
+            priority_factor: default_priority_factor(),
             enabled: true,
+            adaptive: None,
+            algorithm: RateLimitAlgorithm::FixedWindow,
         }
     }
 }
 
+/// Adaptive per-app limits: each app's limit is the given percentile of its
+/// recent per-block event counts times `factor`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AdaptiveLimitConfig {
+    /// Percentile of recent per-block counts, in `[0, 1]`
+    pub percentile: f64,
+    /// Headroom multiplied onto the percentile
+    pub factor: f64,
+    /// Completed blocks of history kept per app
+    pub history_blocks: usize,
+    /// Blocks between recomputations of the derived limits
+    pub recompute_interval_blocks: u64,
+    /// Floor for a derived limit, so a quiet app is never shut out
+    pub min_limit: u64,
+}
+
+impl Default for AdaptiveLimitConfig {
+    fn default() -> Self {
+        Self {
+            percentile: 0.95,
+            factor: 2.0,
+            history_blocks: 100,
+            recompute_interval_blocks: 10,
+            min_limit: 10,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Reject configurations that would stall or break ingestion
+    pub fn validate(&self) -> crate::error::Result<()> {
+        let problem = if self.enabled && self.max_events_per_app_per_block == 0 {
+            Some("max_events_per_app_per_block must be greater than zero")
+        } else if self.enabled && self.max_total_events_per_block == 0 {
+            Some("max_total_events_per_block must be greater than zero")
+        } else if !self.burst_multiplier.is_finite() || self.burst_multiplier < 1.0 {
+            Some("burst_multiplier must be a finite value of at least 1.0")
+        } else if !self.priority_factor.is_finite() || self.priority_factor < 0.0 {
+            Some("priority_factor must be a finite, non-negative value")
+        } else if self.algorithm == (RateLimitAlgorithm::SlidingWindow { window_ms: 0 }) {
+            Some("sliding window_ms must be greater than zero")
+        } else if self.max_cooldown_blocks < self.cooldown_blocks {
+            Some("max_cooldown_blocks must not be less than cooldown_blocks")
+        } else if let Some(adaptive) = &self.adaptive {
+            if !(0.0..=1.0).contains(&adaptive.percentile) {
+                Some("adaptive percentile must be between 0 and 1")
+            } else if !adaptive.factor.is_finite() || adaptive.factor <= 0.0 {
+                Some("adaptive factor must be a finite positive value")
+            } else if adaptive.history_blocks == 0 || adaptive.recompute_interval_blocks == 0 {
+                Some("adaptive history and recompute interval must be greater than zero")
+            } else if self.enabled && adaptive.min_limit == 0 {
+                Some("adaptive min_limit must be greater than zero")
+            } else {
+                None
+            }
+        } else {
+            None
+        };
+        match problem {
+            Some(problem) => Err(AnalyticsError::ConfigError(problem.to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Per-application replacement for the default per-app limit
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AppRateLimitOverride {
+    /// Maximum events per block for this application
+    pub max_events_per_block: u64,
+}
+
+/// Repeat-offense tracking for cooldown escalation
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct OffenseRecord {
+    /// Consecutive blocks without an intervening reset
+    pub count: u32,
+    /// Block at which the most recent cooldown ends
+    pub last_unblock_at: u64,
+}
+
+/// Rejections kept in `RateLimiterState::recent_rejections`
+pub const MAX_RECENT_REJECTIONS: usize = 100;
+
+/// A rejected ingestion attempt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitRejection {
+    pub app_id: ApplicationId,
+    pub block_height: u64,
+    pub reason: RateLimitError,
+}
+
+/// Tokens an app has left under `RateLimitAlgorithm::TokenBucket`
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct TokenBucket {
+    pub tokens: u64,
+    /// Block up to which refills have been applied
+    pub refilled_at: u64,
+}
+
 /// Per-block event counter
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct BlockEventCount {
@@ -50,10 +200,39 @@ pub struct RateLimiterState {
     pub global_counter: BlockEventCount,
     /// Blocked apps with unblock block height
     pub blocked_apps: BTreeMap<ApplicationId, u64>,
+    /// Repeat-offense history per app
+    #[serde(default)]
+    pub offenses: BTreeMap<ApplicationId, OffenseRecord>,
+    /// Per-app limit overrides
+    #[serde(default)]
+    pub overrides: BTreeMap<ApplicationId, AppRateLimitOverride>,
     /// Current configuration
     pub config: RateLimitConfig,
     /// Ingestion paused globally
     pub paused: bool,
+    /// Most recent rejections, oldest first
+    #[serde(default)]
+    pub recent_rejections: VecDeque<RateLimitRejection>,
+    /// Event counts of each app's recent completed blocks, oldest first;
+    /// recorded only in adaptive mode
+    #[serde(default)]
+    pub traffic_history: BTreeMap<ApplicationId, VecDeque<u64>>,
+    /// Per-app base limits derived in adaptive mode
+    #[serde(default)]
+    pub adaptive_limits: BTreeMap<ApplicationId, u64>,
+    /// Block at which adaptive limits were last recomputed
+    #[serde(default)]
+    pub adaptive_computed_at: Option<u64>,
+    /// Per-app token buckets, used by `RateLimitAlgorithm::TokenBucket`
+    #[serde(default)]
+    pub token_buckets: BTreeMap<ApplicationId, TokenBucket>,
+    /// Capture times of each app's admitted events within the window,
+    /// oldest first, used by `RateLimitAlgorithm::SlidingWindow`
+    #[serde(default)]
+    pub window_log: BTreeMap<ApplicationId, VecDeque<Timestamp>>,
+    /// Latest capture time seen, in milliseconds
+    #[serde(default)]
+    pub clock_ms: Timestamp,
 }
 
 impl RateLimiterState {
@@ -65,10 +244,44 @@ impl RateLimiterState {
         }
     }
 
-    /// Check if an app can submit an event and increment counter
+    /// Check if an app can submit an event and increment counter,
+    /// recording any rejection in `recent_rejections`. `priority` is the
+    /// app's `AppConfig::priority`, or 0 for an unmonitored app.
     pub fn check_and_increment(
         &mut self,
         app_id: &ApplicationId,
+        priority: u8,
+        current_block: u64,
+    ) -> Result<(), RateLimitError> {
+        let result = self.admit(app_id, priority, current_block);
+        if let Err(reason) = &result {
+            if self.recent_rejections.len() == MAX_RECENT_REJECTIONS {
+                self.recent_rejections.pop_front();
+            }
+            self.recent_rejections.push_back(RateLimitRejection {
+                app_id: *app_id,
+                block_height: current_block,
+                reason: reason.clone(),
+            });
+        }
+        result
+    }
+
+    /// Advance the capture-time clock used by sliding windows; time never
+    /// moves backwards
+    pub fn advance_clock(&mut self, now_ms: Timestamp) {
+        self.clock_ms = self.clock_ms.max(now_ms);
+    }
+
+    /// Up to `limit` most recent rejections, newest first
+    pub fn recent_rejections(&self, limit: usize) -> Vec<RateLimitRejection> {
+        self.recent_rejections.iter().rev().take(limit).cloned().collect()
+    }
+
+    fn admit(
+        &mut self,
+        app_id: &ApplicationId,
+        priority: u8,
         current_block: u64,
     ) -> Result<(), RateLimitError> {
         // Check global pause
@@ -96,10 +309,18 @@ impl RateLimiterState {
 
         // Reset counters if new block
         self.reset_if_new_block(current_block);
+        // Derive adaptive limits when due, or drop them after leaving adaptive mode
+        let recompute_due = match (&self.config.adaptive, self.adaptive_computed_at) {
+            (Some(adaptive), Some(at)) => current_block >= at + adaptive.recompute_interval_blocks,
+            (Some(_), None) | (None, Some(_)) => true,
+            (None, None) => false,
+        };
+        if recompute_due {
+            self.recompute_adaptive_limits(current_block);
+        }
 
         // Check global limit
-        let max_global = (self.config.max_total_events_per_block as f64
-            * self.config.burst_multiplier) as u64;
+        let max_global = self.global_limit();
         if self.global_counter.count >= max_global {
             return Err(RateLimitError::GlobalLimitExceeded {
                 limit: max_global,
@@ -108,32 +329,255 @@ impl RateLimiterState {
         }
 
         // Check per-app limit
-        let app_counter = self.app_counters.entry(app_id.clone()).or_insert(BlockEventCount {
-            block_height: current_block,
-            count: 0,
-        });
-
-        let max_app = (self.config.max_events_per_app_per_block as f64
-            * self.config.burst_multiplier) as u64;
-        
-        if app_counter.count >= max_app {
-            // Block the app
-            self.blocked_apps
-                .insert(app_id.clone(), current_block + self.config.cooldown_blocks);
+        let max_app = self.app_limit(app_id, priority);
+        if self.app_exhausted(app_id, priority, current_block) {
+            // Block the app, escalating the cooldown for repeat offenders
+            let cooldown_blocks = self.next_cooldown(app_id, current_block);
+            let unblock_at = current_block + cooldown_blocks;
+            let offense = self.offenses.entry(*app_id).or_default();
+            if offense.count > 0
+                && current_block >= offense.last_unblock_at + self.config.escalation_reset_blocks
+            {
+                offense.count = 0;
+            }
+            offense.count += 1;
+            offense.last_unblock_at = unblock_at;
+            self.blocked_apps.insert(*app_id, unblock_at);
             return Err(RateLimitError::AppLimitExceeded {
-                app_id: app_id.clone(),
+                app_id: *app_id,
                 limit: max_app,
-                cooldown_blocks: self.config.cooldown_blocks,
+                cooldown_blocks,
             });
         }
 
         // Increment counters
+        let app_counter = self.app_counters.entry(*app_id).or_insert(BlockEventCount {
+            block_height: current_block,
+            count: 0,
+        });
         app_counter.count += 1;
         self.global_counter.count += 1;
+        match self.config.algorithm {
+            RateLimitAlgorithm::FixedWindow => {}
+            RateLimitAlgorithm::TokenBucket => {
+                let tokens = self.available_tokens(app_id, priority, current_block) - 1;
+                self.token_buckets.insert(
+                    *app_id,
+                    TokenBucket {
+                        tokens,
+                        refilled_at: current_block,
+                    },
+                );
+            }
+            RateLimitAlgorithm::SlidingWindow { window_ms } => {
+                let window_start = self.clock_ms.saturating_sub(window_ms);
+                let log = self.window_log.entry(*app_id).or_default();
+                while log.front().is_some_and(|&at| at <= window_start) {
+                    log.pop_front();
+                }
+                log.push_back(self.clock_ms);
+            }
+        }
 
         Ok(())
     }
 
+    /// Effective per-block global limit, including burst allowance
+    pub fn global_limit(&self) -> u64 {
+        (self.config.max_total_events_per_block as f64 * self.config.burst_multiplier) as u64
+    }
+
+    /// Effective per-block limit for an app, including burst allowance
+    pub fn app_limit(&self, app_id: &ApplicationId, priority: u8) -> u64 {
+        (self.app_base_limit(app_id, priority) as f64 * self.config.burst_multiplier) as u64
+    }
+
+    /// Per-block limit for an app before burst allowance.
+    ///
+    /// An override wins over an adaptive limit, which wins over the
+    /// configured default. Only the default is scaled by `priority`.
+    pub fn app_base_limit(&self, app_id: &ApplicationId, priority: u8) -> u64 {
+        self.overrides
+            .get(app_id)
+            .map(|o| o.max_events_per_block)
+            .or_else(|| {
+                self.config.adaptive.as_ref()?;
+                self.adaptive_limits.get(app_id).copied()
+            })
+            .unwrap_or_else(|| {
+                let scale = 1.0 + priority as f64 * self.config.priority_factor;
+                (self.config.max_events_per_app_per_block as f64 * scale) as u64
+            })
+    }
+
+    /// Tokens `app_id` could spend at `current_block`: a bucket starts full
+    /// at `app_limit` and regains `app_base_limit` tokens per block
+    pub fn available_tokens(&self, app_id: &ApplicationId, priority: u8, current_block: u64) -> u64 {
+        let capacity = self.app_limit(app_id, priority);
+        match self.token_buckets.get(app_id) {
+            Some(bucket) => {
+                let elapsed = current_block.saturating_sub(bucket.refilled_at);
+                bucket
+                    .tokens
+                    .saturating_add(self.app_base_limit(app_id, priority).saturating_mul(elapsed))
+                    .min(capacity)
+            }
+            None => capacity,
+        }
+    }
+
+    /// Whether `app_id` has used up its allowance at `current_block`
+    fn app_exhausted(&self, app_id: &ApplicationId, priority: u8, current_block: u64) -> bool {
+        match self.config.algorithm {
+            RateLimitAlgorithm::FixedWindow => self.app_counters.get(app_id).is_some_and(|counter| {
+                counter.block_height == current_block
+                    && counter.count >= self.app_limit(app_id, priority)
+            }),
+            RateLimitAlgorithm::TokenBucket => {
+                self.available_tokens(app_id, priority, current_block) == 0
+            }
+            RateLimitAlgorithm::SlidingWindow { window_ms } => {
+                self.window_occupancy(app_id, window_ms) >= self.app_limit(app_id, priority) as usize
+            }
+        }
+    }
+
+    /// Events `app_id` had admitted in the `window_ms` ending at `clock_ms`
+    fn window_occupancy(&self, app_id: &ApplicationId, window_ms: u64) -> usize {
+        let window_start = self.clock_ms.saturating_sub(window_ms);
+        self.window_log
+            .get(app_id)
+            .map_or(0, |log| log.iter().filter(|&&at| at > window_start).count())
+    }
+
+    /// Derive each app's base limit from its traffic history.
+    ///
+    /// Apps without history keep the configured default. Outside adaptive
+    /// mode this clears any derived limits.
+    pub fn recompute_adaptive_limits(&mut self, current_block: u64) {
+        let Some(adaptive) = &self.config.adaptive else {
+            self.adaptive_limits.clear();
+            self.adaptive_computed_at = None;
+            return;
+        };
+        self.adaptive_limits = self
+            .traffic_history
+            .iter()
+            .filter(|(_, history)| !history.is_empty())
+            .map(|(app_id, history)| {
+                let counts: Vec<f64> = history.iter().map(|&count| count as f64).collect();
+                let observed = AggregationEngine::percentile(&counts, adaptive.percentile);
+                let limit = ((observed * adaptive.factor).ceil() as u64).max(adaptive.min_limit);
+                (*app_id, limit)
+            })
+            .collect();
+        self.adaptive_computed_at = Some(current_block);
+    }
+
+    /// Install per-app overrides.
+    ///
+    /// With `merge` the given entries are added to or replace existing ones;
+    /// otherwise they replace the whole override map. As with the config
+    /// limits, a zero limit is rejected while limiting is enabled, and then
+    /// no override changes.
+    pub fn set_overrides(
+        &mut self,
+        overrides: BTreeMap<ApplicationId, AppRateLimitOverride>,
+        merge: bool,
+    ) -> crate::error::Result<()> {
+        if self.config.enabled {
+            if let Some(app_id) = overrides
+                .iter()
+                .find(|(_, app_override)| app_override.max_events_per_block == 0)
+                .map(|(app_id, _)| app_id)
+            {
+                return Err(AnalyticsError::ConfigError(format!(
+                    "Override for {:?} must allow more than zero events per block",
+                    app_id
+                )));
+            }
+        }
+        if merge {
+            self.overrides.extend(overrides);
+        } else {
+            self.overrides = overrides;
+        }
+        Ok(())
+    }
+
+    /// Remove all per-app overrides
+    pub fn clear_overrides(&mut self) {
+        self.overrides.clear();
+    }
+
+    /// Cooldown the next block of `app_id` would receive.
+    ///
+    /// Doubles `cooldown_blocks` for each prior offense, capped at
+    /// `max_cooldown_blocks`. Offenses are forgotten once the app has gone
+    /// `escalation_reset_blocks` without being blocked since its last unblock.
+    pub fn next_cooldown(&self, app_id: &ApplicationId, current_block: u64) -> u64 {
+        let base = self.config.cooldown_blocks;
+        let prior_offenses = match self.offenses.get(app_id) {
+            Some(offense)
+                if current_block < offense.last_unblock_at + self.config.escalation_reset_blocks =>
+            {
+                offense.count
+            }
+            _ => 0,
+        };
+        let escalated = base.saturating_mul(1u64 << prior_offenses.min(63));
+        escalated.min(self.config.max_cooldown_blocks.max(base))
+    }
+
+    /// Every reason an event from `app_id` would currently be rejected.
+    ///
+    /// Runs the same checks as `check_and_increment` without mutating any
+    /// counters, and reports all applicable reasons rather than the first.
+    pub fn diagnose(
+        &self,
+        app_id: &ApplicationId,
+        priority: u8,
+        current_block: u64,
+    ) -> Vec<RateLimitError> {
+        let mut reasons = Vec::new();
+
+        if self.paused {
+            reasons.push(RateLimitError::IngestionPaused);
+        }
+        if !self.config.enabled {
+            return reasons;
+        }
+
+        if let Some(&unblock_at) = self.blocked_apps.get(app_id) {
+            if current_block < unblock_at {
+                reasons.push(RateLimitError::AppBlocked {
+                    unblock_at,
+                    current_block,
+                });
+            }
+        }
+
+        let max_global = self.global_limit();
+        if self.global_counter.block_height == current_block
+            && self.global_counter.count >= max_global
+        {
+            reasons.push(RateLimitError::GlobalLimitExceeded {
+                limit: max_global,
+                current: self.global_counter.count,
+            });
+        }
+
+        if self.app_exhausted(app_id, priority, current_block) {
+            reasons.push(RateLimitError::AppLimitExceeded {
+                app_id: *app_id,
+                limit: self.app_limit(app_id, priority),
+                cooldown_blocks: self.next_cooldown(app_id, current_block),
+            });
+        }
+
+        reasons
+    }
+
     /// Reset counters if we're in a new block
     fn reset_if_new_block(&mut self, current_block: u64) {
         if self.global_counter.block_height != current_block {
@@ -141,9 +585,18 @@ impl RateLimiterState {
                 block_height: current_block,
                 count: 0,
             };
-            // Reset all app counters
-            for counter in self.app_counters.values_mut() {
+            // Reset all app counters, recording completed blocks for
+            // adaptive limits
+            let history_blocks = self.config.adaptive.as_ref().map(|a| a.history_blocks);
+            for (app_id, counter) in self.app_counters.iter_mut() {
                 if counter.block_height != current_block {
+                    if let Some(history_blocks) = history_blocks {
+                        let history = self.traffic_history.entry(*app_id).or_default();
+                        history.push_back(counter.count);
+                        while history.len() > history_blocks {
+                            history.pop_front();
+                        }
+                    }
                     counter.block_height = current_block;
                     counter.count = 0;
                 }
@@ -166,6 +619,14 @@ impl RateLimiterState {
         self.config = config;
     }
 
+    /// Validate and apply a new configuration, keeping the current one
+    /// if validation fails
+    pub fn try_update_config(&mut self, config: RateLimitConfig) -> crate::error::Result<()> {
+        config.validate()?;
+        self.config = config;
+        Ok(())
+    }
+
     /// Get current stats
     pub fn get_stats(&self) -> RateLimitStats {
         RateLimitStats {
@@ -174,6 +635,14 @@ impl RateLimiterState {
             blocked_apps_count: self.blocked_apps.len(),
             paused: self.paused,
             enabled: self.config.enabled,
+            window_occupancy: match self.config.algorithm {
+                RateLimitAlgorithm::SlidingWindow { window_ms } => self
+                    .window_log
+                    .keys()
+                    .map(|app_id| self.window_occupancy(app_id, window_ms))
+                    .sum(),
+                _ => 0,
+            },
         }
     }
 
@@ -191,6 +660,10 @@ pub struct RateLimitStats {
     pub blocked_apps_count: usize,
     pub paused: bool,
     pub enabled: bool,
+    /// Events admitted across all apps in the current sliding window; zero
+    /// under other algorithms
+    #[serde(default)]
+    pub window_occupancy: usize,
 }
 
 /// Rate limiting errors
@@ -240,12 +713,17 @@ mod tests {
             max_total_events_per_block: 100,
             burst_multiplier: 1.0,
             cooldown_blocks: 5,
+            max_cooldown_blocks: 80,
+            escalation_reset_blocks: 100,
+            priority_factor: 0.1,
             enabled: true,
+            adaptive: None,
+            algorithm: RateLimitAlgorithm::FixedWindow,
         });
 
         let app = test_app_id(1);
         for _ in 0..10 {
-            assert!(limiter.check_and_increment(&app, 1).is_ok());
+            assert!(limiter.check_and_increment(&app, 0, 1).is_ok());
         }
     }
 
@@ -256,27 +734,318 @@ mod tests {
             max_total_events_per_block: 100,
             burst_multiplier: 1.0,
             cooldown_blocks: 5,
+            max_cooldown_blocks: 80,
+            escalation_reset_blocks: 100,
+            priority_factor: 0.1,
             enabled: true,
+            adaptive: None,
+            algorithm: RateLimitAlgorithm::FixedWindow,
         });
 
         let app = test_app_id(1);
         for _ in 0..5 {
-            assert!(limiter.check_and_increment(&app, 1).is_ok());
+            assert!(limiter.check_and_increment(&app, 0, 1).is_ok());
         }
         
         // 6th event should fail
-        let result = limiter.check_and_increment(&app, 1);
+        let result = limiter.check_and_increment(&app, 0, 1);
         assert!(matches!(result, Err(RateLimitError::AppLimitExceeded { .. })));
     }
 
+    #[test]
+    fn test_repeat_offender_cooldown_escalates() {
+        let mut limiter = RateLimiterState::new(RateLimitConfig {
+            max_events_per_app_per_block: 1,
+            max_total_events_per_block: 100,
+            burst_multiplier: 1.0,
+            cooldown_blocks: 5,
+            max_cooldown_blocks: 12,
+            escalation_reset_blocks: 100,
+            priority_factor: 0.1,
+            enabled: true,
+            adaptive: None,
+            algorithm: RateLimitAlgorithm::FixedWindow,
+        });
+        let app = test_app_id(1);
+
+        // Exceed the limit, then retry as soon as each cooldown ends
+        let mut block = 1;
+        let mut unblock_heights = Vec::new();
+        for _ in 0..3 {
+            assert!(limiter.check_and_increment(&app, 0, block).is_ok());
+            assert!(limiter.check_and_increment(&app, 0, block).is_err());
+            let unblock_at = limiter.blocked_apps[&app];
+            unblock_heights.push(unblock_at);
+            block = unblock_at;
+        }
+
+        // 5, then 10, then capped at 12 instead of 20
+        assert_eq!(unblock_heights, vec![1 + 5, 6 + 10, 16 + 12]);
+
+        // Sustained good behavior resets the escalation
+        let later = 28 + 100;
+        assert_eq!(limiter.next_cooldown(&app, later), 5);
+        assert!(limiter.check_and_increment(&app, 0, later).is_ok());
+        assert!(limiter.check_and_increment(&app, 0, later).is_err());
+        assert_eq!(limiter.blocked_apps[&app], later + 5);
+    }
+
+    #[test]
+    fn test_bulk_overrides_enforced_per_app() {
+        let mut limiter = RateLimiterState::new(RateLimitConfig {
+            max_events_per_app_per_block: 5,
+            burst_multiplier: 1.0,
+            ..RateLimitConfig::default()
+        });
+        let apps = [test_app_id(1), test_app_id(2), test_app_id(3)];
+        let overrides = apps
+            .iter()
+            .zip([1, 2, 3])
+            .map(|(app, max)| (*app, AppRateLimitOverride { max_events_per_block: max }))
+            .collect();
+        limiter.set_overrides(overrides, false).unwrap();
+
+        for (app, max) in apps.iter().zip([1, 2, 3]) {
+            for _ in 0..max {
+                assert!(limiter.check_and_increment(app, 0, 1).is_ok());
+            }
+            assert!(matches!(
+                limiter.check_and_increment(app, 0, 1),
+                Err(RateLimitError::AppLimitExceeded { limit, .. }) if limit == max
+            ));
+        }
+
+        // Apps without an override keep the default limit
+        let other = test_app_id(4);
+        for _ in 0..5 {
+            assert!(limiter.check_and_increment(&other, 0, 1).is_ok());
+        }
+
+        limiter.set_overrides(
+            [(apps[0], AppRateLimitOverride { max_events_per_block: 9 })].into(),
+            true,
+        ).unwrap();
+        assert_eq!(limiter.overrides.len(), 3);
+        assert_eq!(limiter.app_limit(&apps[0], 0), 9);
+
+        // A zero limit is rejected and leaves the overrides as they were
+        let result = limiter.set_overrides(
+            [(apps[1], AppRateLimitOverride { max_events_per_block: 0 })].into(),
+            false,
+        );
+        assert!(matches!(result, Err(AnalyticsError::ConfigError(_))));
+        assert_eq!(limiter.overrides.len(), 3);
+        assert_eq!(limiter.app_limit(&apps[1], 0), 2);
+
+        limiter.clear_overrides();
+        assert_eq!(limiter.app_limit(&apps[1], 0), 5);
+    }
+
     #[test]
     fn test_pause_blocks_all() {
         let mut limiter = RateLimiterState::new(RateLimitConfig::default());
         limiter.pause();
 
         let app = test_app_id(1);
-        let result = limiter.check_and_increment(&app, 1);
+        let result = limiter.check_and_increment(&app, 0, 1);
         assert!(matches!(result, Err(RateLimitError::IngestionPaused)));
     }
-}
 
+    #[test]
+    fn test_invalid_config_keeps_previous() {
+        let mut limiter = RateLimiterState::new(RateLimitConfig::default());
+
+        let valid = RateLimitConfig {
+            max_events_per_app_per_block: 20,
+            ..RateLimitConfig::default()
+        };
+        assert!(limiter.try_update_config(valid).is_ok());
+        assert_eq!(limiter.config.max_events_per_app_per_block, 20);
+
+        let invalid = RateLimitConfig {
+            max_total_events_per_block: 0,
+            ..RateLimitConfig::default()
+        };
+        let result = limiter.try_update_config(invalid);
+        assert!(matches!(result, Err(AnalyticsError::ConfigError(_))));
+        assert_eq!(limiter.config.max_events_per_app_per_block, 20);
+        assert_eq!(limiter.config.max_total_events_per_block, 1000);
+
+        // Zero limits are harmless while rate limiting is disabled
+        let disabled = RateLimitConfig {
+            max_events_per_app_per_block: 0,
+            enabled: false,
+            ..RateLimitConfig::default()
+        };
+        assert!(disabled.validate().is_ok());
+    }
+
+    #[test]
+    fn test_recent_rejections_record_reasons() {
+        let mut limiter = RateLimiterState::new(RateLimitConfig {
+            max_events_per_app_per_block: 2,
+            burst_multiplier: 1.0,
+            ..Default::default()
+        });
+        let app = test_app_id(1);
+
+        assert!(limiter.check_and_increment(&app, 0, 1).is_ok());
+        assert!(limiter.check_and_increment(&app, 0, 1).is_ok());
+        assert!(limiter.check_and_increment(&app, 0, 1).is_err());
+        limiter.pause();
+        assert!(limiter.check_and_increment(&test_app_id(2), 0, 2).is_err());
+
+        let recent = limiter.recent_rejections(10);
+        assert_eq!(recent.len(), 2);
+        assert!(matches!(recent[0].reason, RateLimitError::IngestionPaused));
+        assert_eq!(recent[0].block_height, 2);
+        assert!(matches!(recent[1].reason, RateLimitError::AppLimitExceeded { limit: 2, .. }));
+        assert_eq!(recent[1].app_id, app);
+        assert_eq!(limiter.recent_rejections(1).len(), 1);
+
+        // The log is bounded
+        for block in 0..(MAX_RECENT_REJECTIONS as u64 + 5) {
+            let _ = limiter.check_and_increment(&app, 0, block);
+        }
+        assert_eq!(limiter.recent_rejections.len(), MAX_RECENT_REJECTIONS);
+    }
+
+    #[test]
+    fn test_adaptive_limit_follows_traffic_percentile() {
+        let mut limiter = RateLimiterState::new(RateLimitConfig {
+            max_events_per_app_per_block: 1000,
+            burst_multiplier: 1.0,
+            adaptive: Some(AdaptiveLimitConfig {
+                percentile: 0.9,
+                factor: 2.0,
+                history_blocks: 10,
+                recompute_interval_blocks: 5,
+                min_limit: 1,
+            }),
+            ..RateLimitConfig::default()
+        });
+        let app = test_app_id(1);
+
+        // Blocks 1..=12 carry 1..=12 events; the first two fall out of the
+        // ten-block history
+        for block in 1..=12u64 {
+            for _ in 0..block {
+                limiter.check_and_increment(&app, 0, block).unwrap();
+            }
+        }
+        assert!(limiter.check_and_increment(&app, 0, 13).is_ok());
+        let history: Vec<u64> = limiter.traffic_history[&app].iter().copied().collect();
+        assert_eq!(history, (3..=12).collect::<Vec<u64>>());
+
+        // Limits were last derived at block 11, from blocks 1..=10
+        let counts: Vec<f64> = (1..=10).map(|count| count as f64).collect();
+        let expected = (AggregationEngine::percentile(&counts, 0.9) * 2.0).ceil() as u64;
+        assert_eq!(limiter.adaptive_computed_at, Some(11));
+        assert_eq!(limiter.app_limit(&app, 0), expected);
+
+        // The next recomputation picks up the newer history
+        limiter.recompute_adaptive_limits(16);
+        let counts: Vec<f64> = (3..=12).map(|count| count as f64).collect();
+        let expected = (AggregationEngine::percentile(&counts, 0.9) * 2.0).ceil() as u64;
+        assert_eq!(limiter.app_limit(&app, 0), expected);
+
+        // Overrides still win, and leaving adaptive mode restores the default
+        limiter.set_overrides([(app, AppRateLimitOverride { max_events_per_block: 3 })].into(), false).unwrap();
+        assert_eq!(limiter.app_limit(&app, 0), 3);
+        limiter.clear_overrides();
+        limiter.config.adaptive = None;
+        limiter.recompute_adaptive_limits(17);
+        assert_eq!(limiter.app_limit(&app, 0), 1000);
+    }
+
+    #[test]
+    fn test_token_bucket_smooths_bursts_across_blocks() {
+        let config = RateLimitConfig {
+            max_events_per_app_per_block: 10,
+            burst_multiplier: 2.0,
+            ..RateLimitConfig::default()
+        };
+        let admitted = |limiter: &mut RateLimiterState, block: u64| {
+            (0..30)
+                .take_while(|_| limiter.check_and_increment(&test_app_id(1), 0, block).is_ok())
+                .count()
+        };
+
+        // Fixed windows allow the full burst in each of two adjacent blocks
+        let mut fixed = RateLimiterState::new(config.clone());
+        assert_eq!(admitted(&mut fixed, 1), 20);
+        fixed.blocked_apps.clear();
+        assert_eq!(admitted(&mut fixed, 2), 20);
+
+        // A token bucket allows the burst once, then the base rate
+        let mut bucket = RateLimiterState::new(RateLimitConfig {
+            algorithm: RateLimitAlgorithm::TokenBucket,
+            ..config
+        });
+        assert_eq!(admitted(&mut bucket, 1), 20);
+        bucket.blocked_apps.clear();
+        assert_eq!(admitted(&mut bucket, 2), 10);
+
+        // Idle blocks refill the bucket, up to its capacity
+        bucket.blocked_apps.clear();
+        assert_eq!(bucket.available_tokens(&test_app_id(1), 0, 3), 10);
+        assert_eq!(bucket.available_tokens(&test_app_id(1), 0, 10), 20);
+    }
+
+    #[test]
+    fn test_priority_raises_app_limit() {
+        let mut limiter = RateLimiterState::new(RateLimitConfig {
+            max_events_per_app_per_block: 10,
+            burst_multiplier: 1.0,
+            priority_factor: 0.2,
+            ..RateLimitConfig::default()
+        });
+        let (low, high) = (test_app_id(1), test_app_id(2));
+        let admitted = |limiter: &mut RateLimiterState, app: &ApplicationId, priority: u8| {
+            (0..50)
+                .take_while(|_| limiter.check_and_increment(app, priority, 1).is_ok())
+                .count()
+        };
+
+        // Priority 5 doubles the default limit with a factor of 0.2
+        assert_eq!(admitted(&mut limiter, &low, 0), 10);
+        assert_eq!(admitted(&mut limiter, &high, 5), 20);
+
+        // Explicit overrides are not scaled
+        limiter.set_overrides([(high, AppRateLimitOverride { max_events_per_block: 4 })].into(), false).unwrap();
+        assert_eq!(limiter.app_limit(&high, 5), 4);
+    }
+
+    #[test]
+    fn test_sliding_window_admits_again_as_window_slides() {
+        let mut limiter = RateLimiterState::new(RateLimitConfig {
+            max_events_per_app_per_block: 3,
+            burst_multiplier: 1.0,
+            cooldown_blocks: 0,
+            algorithm: RateLimitAlgorithm::SlidingWindow { window_ms: 1000 },
+            ..RateLimitConfig::default()
+        });
+        let app = test_app_id(1);
+        let submit = |limiter: &mut RateLimiterState, block: u64, now_ms: u64| {
+            limiter.advance_clock(now_ms);
+            limiter.check_and_increment(&app, 0, block)
+        };
+
+        // Three events spread over two blocks fill the window, so a new
+        // block does not reset it
+        assert!(submit(&mut limiter, 1, 100).is_ok());
+        assert!(submit(&mut limiter, 1, 400).is_ok());
+        assert!(submit(&mut limiter, 2, 800).is_ok());
+        assert_eq!(limiter.get_stats().window_occupancy, 3);
+        assert!(matches!(
+            submit(&mut limiter, 2, 900),
+            Err(RateLimitError::AppLimitExceeded { limit: 3, .. })
+        ));
+
+        // Once the first event leaves the window there is room for one more
+        assert!(submit(&mut limiter, 3, 1100).is_ok());
+        assert!(submit(&mut limiter, 3, 1200).is_err());
+        assert!(submit(&mut limiter, 3, 1500).is_ok());
+        assert_eq!(limiter.get_stats().window_occupancy, 3);
+    }
+}
@@ -3,9 +3,10 @@
 //! Provides multi-tier permission system for enterprise deployments.
 
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use crate::state::Owner;
+use crate::Operation;
 
 /// User roles with different permission levels
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
@@ -20,6 +21,19 @@ pub enum Role {
     DataIngester,
     /// Read-only access via service
     Viewer,
+    /// Role defined at runtime with `DefineCustomRole`
+    Custom(String),
+}
+
+impl Role {
+    /// Every built-in role, from most to least privileged
+    pub const ALL: [Role; 5] = [
+        Role::SuperAdmin,
+        Role::Admin,
+        Role::Operator,
+        Role::DataIngester,
+        Role::Viewer,
+    ];
 }
 
 impl Default for Role {
@@ -29,7 +43,7 @@ impl Default for Role {
 }
 
 /// Granular permissions for operations
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Permission {
     /// Add new monitored applications
     AddApplication,
@@ -49,6 +63,20 @@ pub enum Permission {
     ControlIngestion,
 }
 
+impl Permission {
+    /// Every permission
+    pub const ALL: [Permission; 8] = [
+        Permission::AddApplication,
+        Permission::RemoveApplication,
+        Permission::CaptureEvents,
+        Permission::ModifyMetrics,
+        Permission::ConfigureSystem,
+        Permission::ViewData,
+        Permission::ManageRoles,
+        Permission::ControlIngestion,
+    ];
+}
+
 /// RBAC state for managing roles and permissions
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct RBACState {
@@ -56,6 +84,15 @@ pub struct RBACState {
     pub roles: BTreeMap<Owner, Role>,
     /// Super admin (cannot be demoted)
     pub super_admin: Option<Owner>,
+    /// Permissions granted by each custom role, by name
+    #[serde(default)]
+    pub custom_roles: BTreeMap<String, BTreeSet<Permission>>,
+    /// Block at which each temporary assignment stops applying
+    #[serde(default)]
+    pub role_expiries: BTreeMap<Owner, u64>,
+    /// Block height used to expire temporary assignments
+    #[serde(default)]
+    pub current_block: u64,
 }
 
 impl RBACState {
@@ -66,41 +103,167 @@ impl RBACState {
         Self {
             roles,
             super_admin: Some(super_admin),
+            custom_roles: BTreeMap::new(),
+            role_expiries: BTreeMap::new(),
+            current_block: 0,
         }
     }
 
-    /// Get role for an owner
+    /// Get role for an owner; an expired temporary assignment is `Viewer`
     pub fn get_role(&self, owner: &Owner) -> Role {
+        if self
+            .role_expiries
+            .get(owner)
+            .is_some_and(|&expires_at| self.current_block >= expires_at)
+        {
+            return Role::Viewer;
+        }
         self.roles.get(owner).cloned().unwrap_or(Role::Viewer)
     }
 
+    /// Every explicit role assignment with its effective role, by owner.
+    ///
+    /// The super admin is always listed as `SuperAdmin`, even if missing
+    /// from `roles`.
+    pub fn assignments(&self) -> Vec<(Owner, Role)> {
+        let mut owners: BTreeSet<Owner> = self.roles.keys().copied().collect();
+        owners.extend(self.super_admin);
+        owners
+            .into_iter()
+            .map(|owner| {
+                let role = if Some(&owner) == self.super_admin.as_ref() {
+                    Role::SuperAdmin
+                } else {
+                    self.get_role(&owner)
+                };
+                (owner, role)
+            })
+            .collect()
+    }
+
+    /// Owners currently holding `role`
+    pub fn owners_with_role(&self, role: &Role) -> Vec<Owner> {
+        self.assignments()
+            .into_iter()
+            .filter(|(_, assigned)| assigned == role)
+            .map(|(owner, _)| owner)
+            .collect()
+    }
+
+    /// Number of owners holding each assigned role
+    pub fn role_counts(&self) -> BTreeMap<Role, usize> {
+        let mut counts = BTreeMap::new();
+        for (_, role) in self.assignments() {
+            *counts.entry(role).or_default() += 1;
+        }
+        counts
+    }
+
     /// Assign role to an owner
     pub fn assign_role(&mut self, owner: Owner, role: Role) -> Result<(), RBACError> {
         // Cannot change super admin's role
         if Some(&owner) == self.super_admin.as_ref() && role != Role::SuperAdmin {
             return Err(RBACError::CannotDemoteSuperAdmin);
         }
+        // The super admin role moves only through `TransferSuperAdmin`
+        if Some(&owner) != self.super_admin.as_ref() && role == Role::SuperAdmin {
+            return Err(RBACError::CannotGrantSuperAdmin);
+        }
+        if let Role::Custom(name) = &role {
+            if !self.custom_roles.contains_key(name) {
+                return Err(RBACError::UnknownCustomRole(name.clone()));
+            }
+        }
+        self.role_expiries.remove(&owner);
         self.roles.insert(owner, role);
         Ok(())
     }
 
+    /// Assign a role that reverts to `Viewer` once the current block reaches
+    /// `expires_at_block`
+    pub fn assign_role_until(
+        &mut self,
+        owner: Owner,
+        role: Role,
+        expires_at_block: u64,
+    ) -> Result<(), RBACError> {
+        if Some(&owner) == self.super_admin.as_ref() {
+            return Err(RBACError::CannotDemoteSuperAdmin);
+        }
+        if expires_at_block <= self.current_block {
+            return Err(RBACError::GrantAlreadyExpired {
+                expires_at_block,
+                current_block: self.current_block,
+            });
+        }
+        self.assign_role(owner, role)?;
+        self.role_expiries.insert(owner, expires_at_block);
+        Ok(())
+    }
+
+    /// Advance the block height, dropping temporary assignments that expired
+    pub fn set_current_block(&mut self, block: u64) {
+        self.current_block = block;
+        let expired: Vec<Owner> = self
+            .role_expiries
+            .iter()
+            .filter(|(_, &expires_at)| block >= expires_at)
+            .map(|(owner, _)| *owner)
+            .collect();
+        for owner in expired {
+            self.role_expiries.remove(&owner);
+            self.roles.remove(&owner);
+        }
+    }
+
     /// Remove role assignment (reverts to Viewer)
     pub fn remove_role(&mut self, owner: &Owner) -> Result<(), RBACError> {
         if Some(owner) == self.super_admin.as_ref() {
             return Err(RBACError::CannotDemoteSuperAdmin);
         }
         self.roles.remove(owner);
+        self.role_expiries.remove(owner);
+        Ok(())
+    }
+
+    /// Define a custom role, or replace the permissions of an existing one
+    pub fn define_custom_role(
+        &mut self,
+        name: String,
+        permissions: BTreeSet<Permission>,
+    ) -> Result<(), RBACError> {
+        if name.trim().is_empty() {
+            return Err(RBACError::InvalidCustomRole("name is empty".to_string()));
+        }
+        self.custom_roles.insert(name, permissions);
+        Ok(())
+    }
+
+    /// Delete a custom role; it must no longer be assigned to anyone
+    pub fn delete_custom_role(&mut self, name: &str) -> Result<(), RBACError> {
+        if !self.custom_roles.contains_key(name) {
+            return Err(RBACError::UnknownCustomRole(name.to_string()));
+        }
+        if self
+            .roles
+            .values()
+            .any(|role| matches!(role, Role::Custom(assigned) if assigned == name))
+        {
+            return Err(RBACError::CustomRoleInUse(name.to_string()));
+        }
+        self.custom_roles.remove(name);
         Ok(())
     }
 
     /// Check if owner has a specific permission
     pub fn has_permission(&self, owner: &Owner, permission: &Permission) -> bool {
         let role = self.get_role(owner);
-        Self::role_has_permission(&role, permission)
+        self.role_has_permission(&role, permission)
     }
 
-    /// Check if a role grants a specific permission
-    pub fn role_has_permission(role: &Role, permission: &Permission) -> bool {
+    /// Check if a role grants a specific permission; an undefined custom
+    /// role grants nothing
+    pub fn role_has_permission(&self, role: &Role, permission: &Permission) -> bool {
         match role {
             Role::SuperAdmin => true, // SuperAdmin has all permissions
             Role::Admin => matches!(
@@ -122,25 +285,206 @@ impl RBACState {
             ),
             Role::DataIngester => matches!(permission, Permission::CaptureEvents | Permission::ViewData),
             Role::Viewer => matches!(permission, Permission::ViewData),
+            Role::Custom(name) => self
+                .custom_roles
+                .get(name)
+                .is_some_and(|permissions| permissions.contains(permission)),
+        }
+    }
+
+    /// All permissions granted by a role
+    pub fn permissions_for(&self, role: &Role) -> Vec<Permission> {
+        Permission::ALL
+            .into_iter()
+            .filter(|permission| self.role_has_permission(role, permission))
+            .collect()
+    }
+
+    /// Permissions granted by every built-in role, then every custom role
+    pub fn permission_matrix(&self) -> Vec<(Role, Vec<Permission>)> {
+        Role::ALL
+            .into_iter()
+            .chain(self.custom_roles.keys().cloned().map(Role::Custom))
+            .map(|role| {
+                let permissions = self.permissions_for(&role);
+                (role, permissions)
+            })
+            .collect()
+    }
+
+    /// Replace all role assignments with a previously exported snapshot.
+    ///
+    /// The snapshot must name the current super admin and keep them in the
+    /// `SuperAdmin` role; changing the super admin goes through
+    /// `TransferSuperAdmin` instead. Every custom role it assigns must be
+    /// defined in the snapshot.
+    pub fn import(&mut self, snapshot: RBACState) -> Result<(), RBACError> {
+        let Some(super_admin) = snapshot.super_admin.as_ref() else {
+            return Err(RBACError::InvalidSnapshot("no super admin set".to_string()));
+        };
+        if self.super_admin.as_ref() != Some(super_admin) {
+            return Err(RBACError::InvalidSnapshot(
+                "super admin differs from the current one".to_string(),
+            ));
+        }
+        if snapshot.roles.get(super_admin) != Some(&Role::SuperAdmin) {
+            return Err(RBACError::InvalidSnapshot(
+                "super admin lacks the SuperAdmin role".to_string(),
+            ));
+        }
+        if let Some(Role::Custom(name)) = snapshot.roles.values().find(|role| {
+            matches!(role, Role::Custom(name) if !snapshot.custom_roles.contains_key(name))
+        }) {
+            return Err(RBACError::InvalidSnapshot(format!(
+                "custom role {} is not defined",
+                name
+            )));
+        }
+        *self = RBACState {
+            current_block: self.current_block,
+            ..snapshot
+        };
+        Ok(())
+    }
+
+    /// Import a snapshot replicated from another chain.
+    ///
+    /// Each chain keeps the super admin it was instantiated with: the
+    /// snapshot's super admin is swapped for the local one and gets no role
+    /// here, so a standby with a different super admin can still follow its
+    /// primary's role changes.
+    pub fn import_replicated(&mut self, mut snapshot: RBACState) -> Result<(), RBACError> {
+        if let (Some(local), Some(remote)) = (self.super_admin, snapshot.super_admin) {
+            if local != remote && snapshot.roles.get(&remote) == Some(&Role::SuperAdmin) {
+                snapshot.roles.remove(&remote);
+                snapshot.role_expiries.remove(&remote);
+                snapshot.roles.insert(local, Role::SuperAdmin);
+                snapshot.role_expiries.remove(&local);
+                snapshot.super_admin = Some(local);
+            }
+        }
+        self.import(snapshot)
+    }
+
+    /// Check that `caller` may submit `operation`.
+    ///
+    /// A batch needs no permission of its own; each of its operations is
+    /// authorized as it runs.
+    pub fn authorize(&self, caller: &Owner, operation: &Operation) -> Result<(), RBACError> {
+        match operation.required_permission() {
+            Some(permission) if !self.has_permission(caller, &permission) => {
+                Err(RBACError::InsufficientPermissions)
+            }
+            _ => Ok(()),
         }
     }
 
     /// Validate that caller can perform an action on target
     pub fn can_manage(&self, caller: &Owner, target: &Owner) -> bool {
-        let caller_role = self.get_role(caller);
-        let target_role = self.get_role(target);
-        
-        // SuperAdmin can manage anyone except themselves being demoted
-        if caller_role == Role::SuperAdmin {
-            return true;
+        self.outranks(&self.get_role(caller), &self.get_role(target))
+    }
+
+    /// Validate that caller can grant `role` to someone they manage.
+    ///
+    /// `SuperAdmin` is never granted this way, only transferred. Any other
+    /// role must be below the caller's and grant only permissions the
+    /// caller holds.
+    pub fn can_grant(&self, caller: &Owner, role: &Role) -> bool {
+        *role != Role::SuperAdmin
+            && self.outranks(&self.get_role(caller), role)
+            && self
+                .permissions_for(role)
+                .iter()
+                .all(|permission| self.has_permission(caller, permission))
+    }
+
+    /// Validate that caller can define custom role `name` with `permissions`.
+    ///
+    /// The caller must hold every permission of the new set and, when
+    /// redefining, of the current one, so a role cannot be strengthened
+    /// or taken over by someone weaker than it.
+    pub fn can_define_custom_role(
+        &self,
+        caller: &Owner,
+        name: &str,
+        permissions: &BTreeSet<Permission>,
+    ) -> bool {
+        let current = self.custom_roles.get(name).into_iter().flatten();
+        permissions
+            .iter()
+            .chain(current)
+            .all(|permission| self.has_permission(caller, permission))
+    }
+
+    /// Validate that caller can delete custom role `name`: they must hold
+    /// every permission it grants
+    pub fn can_delete_custom_role(&self, caller: &Owner, name: &str) -> bool {
+        self.custom_roles
+            .get(name)
+            .into_iter()
+            .flatten()
+            .all(|permission| self.has_permission(caller, permission))
+    }
+
+    /// Whether a caller in `caller_role` ranks above holders of `role`
+    fn outranks(&self, caller_role: &Role, role: &Role) -> bool {
+        match caller_role {
+            // SuperAdmin can manage anyone except themselves being demoted
+            Role::SuperAdmin => true,
+            // Admin can manage Operators and below, and custom roles
+            // granting nothing beyond what an Admin has
+            Role::Admin => match role {
+                Role::Operator | Role::DataIngester | Role::Viewer => true,
+                Role::Custom(_) => self
+                    .permissions_for(role)
+                    .iter()
+                    .all(|permission| self.role_has_permission(&Role::Admin, permission)),
+                Role::SuperAdmin | Role::Admin => false,
+            },
+            _ => false,
         }
-        
-        // Admin can manage Operators and below
-        if caller_role == Role::Admin {
-            return matches!(target_role, Role::Operator | Role::DataIngester | Role::Viewer);
+    }
+}
+
+impl Operation {
+    /// Permission the caller needs to submit this operation
+    pub fn required_permission(&self) -> Option<Permission> {
+        match self {
+            Operation::AddMonitoredApp { .. }
+            | Operation::UpdateAppConfig { .. }
+            | Operation::PatchAppConfig { .. } => Some(Permission::AddApplication),
+            Operation::RemoveMonitoredApp { .. } => Some(Permission::RemoveApplication),
+            Operation::CaptureEvent { .. }
+            | Operation::CaptureTransaction { .. }
+            | Operation::CaptureEventBatch { .. } => Some(Permission::CaptureEvents),
+            Operation::UpdateMetric { .. }
+            | Operation::DefineMetric { .. }
+            | Operation::UpdateMetricDefinition { .. }
+            | Operation::SaveQuery { .. } => Some(Permission::ModifyMetrics),
+            Operation::AssignRole { .. }
+            | Operation::AssignRoleTemporary { .. }
+            | Operation::RemoveRole { .. }
+            | Operation::DefineCustomRole { .. }
+            | Operation::DeleteCustomRole { .. } => Some(Permission::ManageRoles),
+            Operation::UpdateRateLimitConfig { .. }
+            | Operation::PauseIngestion
+            | Operation::ResumeIngestion
+            | Operation::UnblockApp { .. }
+            | Operation::SetRateLimitOverrides { .. }
+            | Operation::ClearRateLimitOverrides => Some(Permission::ControlIngestion),
+            // Operations that rewrite stored history, replace role
+            // assignments, or change contract-wide settings and replication
+            // are reserved for the super admin
+            Operation::MergeApplications { .. }
+            | Operation::PinEvent { .. }
+            | Operation::ImportEvents { .. }
+            | Operation::AdminAction { .. }
+            | Operation::ImportRBAC { .. }
+            | Operation::ReplicateOperationLog { .. }
+            | Operation::SetReplicationSource { .. }
+            | Operation::TruncateOperationLog { .. } => Some(Permission::ConfigureSystem),
+            Operation::Batch { .. } => None,
         }
-        
-        false
     }
 }
 
@@ -153,6 +497,18 @@ pub enum RBACError {
     InsufficientPermissions,
     #[error("Cannot manage users with equal or higher role")]
     CannotManageHigherRole,
+    #[error("The SuperAdmin role can only be transferred")]
+    CannotGrantSuperAdmin,
+    #[error("Invalid RBAC snapshot: {0}")]
+    InvalidSnapshot(String),
+    #[error("Custom role not defined: {0}")]
+    UnknownCustomRole(String),
+    #[error("Custom role {0} is still assigned")]
+    CustomRoleInUse(String),
+    #[error("Invalid custom role: {0}")]
+    InvalidCustomRole(String),
+    #[error("Grant expiring at block {expires_at_block} has already expired (current: {current_block})")]
+    GrantAlreadyExpired { expires_at_block: u64, current_block: u64 },
 }
 
 #[cfg(test)]
@@ -185,6 +541,22 @@ mod tests {
         assert!(!state.has_permission(&viewer, &Permission::CaptureEvents));
     }
 
+    #[test]
+    fn test_permission_matrix() {
+        let matrix = RBACState::default().permission_matrix();
+        assert_eq!(matrix.len(), Role::ALL.len());
+
+        let lookup = |role: Role| {
+            matrix
+                .iter()
+                .find(|(r, _)| *r == role)
+                .map(|(_, permissions)| permissions.clone())
+                .unwrap()
+        };
+        assert_eq!(lookup(Role::SuperAdmin), Permission::ALL.to_vec());
+        assert_eq!(lookup(Role::Viewer), vec![Permission::ViewData]);
+    }
+
     #[test]
     fn test_cannot_demote_super_admin() {
         let admin = test_owner(1);
@@ -193,4 +565,198 @@ mod tests {
         let result = state.assign_role(admin, Role::Viewer);
         assert!(matches!(result, Err(RBACError::CannotDemoteSuperAdmin)));
     }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let admin = test_owner(1);
+        let mut state = RBACState::new(admin);
+        state.assign_role(test_owner(2), Role::Operator).unwrap();
+        state.assign_role(test_owner(3), Role::DataIngester).unwrap();
+
+        let snapshot = state.clone();
+        state.assign_role(test_owner(2), Role::Viewer).unwrap();
+        state.remove_role(&test_owner(3)).unwrap();
+        state.assign_role(test_owner(4), Role::Admin).unwrap();
+
+        state.import(snapshot.clone()).unwrap();
+        assert_eq!(state.roles, snapshot.roles);
+        assert_eq!(state.get_role(&test_owner(2)), Role::Operator);
+        assert_eq!(state.get_role(&test_owner(4)), Role::Viewer);
+
+        // Snapshots that would lose or replace the super admin are rejected
+        let mut headless = snapshot.clone();
+        headless.super_admin = None;
+        assert!(matches!(state.import(headless), Err(RBACError::InvalidSnapshot(_))));
+        let mut demoted = snapshot.clone();
+        demoted.roles.insert(admin, Role::Admin);
+        assert!(matches!(state.import(demoted), Err(RBACError::InvalidSnapshot(_))));
+        assert!(state.import(RBACState::new(test_owner(9))).is_err());
+        assert_eq!(state.roles, snapshot.roles);
+    }
+
+    #[test]
+    fn test_custom_role_permissions() {
+        let admin = test_owner(1);
+        let auditor = test_owner(2);
+        let mut state = RBACState::new(admin);
+        let billing = Role::Custom("Billing Auditor".to_string());
+
+        // Undefined custom roles cannot be assigned
+        assert!(matches!(
+            state.assign_role(auditor, billing.clone()),
+            Err(RBACError::UnknownCustomRole(_))
+        ));
+
+        let permissions = BTreeSet::from([Permission::ViewData, Permission::ModifyMetrics]);
+        state.define_custom_role("Billing Auditor".to_string(), permissions).unwrap();
+        state.assign_role(auditor, billing.clone()).unwrap();
+        assert!(state.has_permission(&auditor, &Permission::ModifyMetrics));
+        assert!(!state.has_permission(&auditor, &Permission::CaptureEvents));
+        assert_eq!(
+            state.permissions_for(&billing),
+            vec![Permission::ModifyMetrics, Permission::ViewData]
+        );
+        assert!(state.permission_matrix().iter().any(|(role, _)| *role == billing));
+        let other_admin = test_owner(3);
+        state.assign_role(other_admin, Role::Admin).unwrap();
+        assert!(state.can_manage(&other_admin, &auditor));
+
+        // Redefining changes what holders of the role may do
+        let permissions = BTreeSet::from([Permission::ViewData, Permission::ConfigureSystem]);
+        state.define_custom_role("Billing Auditor".to_string(), permissions).unwrap();
+        assert!(!state.has_permission(&auditor, &Permission::ModifyMetrics));
+        assert!(state.has_permission(&auditor, &Permission::ConfigureSystem));
+        // ...and an Admin cannot manage holders of a role stronger than theirs
+        assert!(!state.can_manage(&other_admin, &auditor));
+
+        // A role in use cannot be deleted
+        assert!(matches!(
+            state.delete_custom_role("Billing Auditor"),
+            Err(RBACError::CustomRoleInUse(_))
+        ));
+        state.remove_role(&auditor).unwrap();
+        state.delete_custom_role("Billing Auditor").unwrap();
+        assert!(matches!(
+            state.delete_custom_role("Billing Auditor"),
+            Err(RBACError::UnknownCustomRole(_))
+        ));
+        assert!(state.define_custom_role(" ".to_string(), BTreeSet::new()).is_err());
+    }
+
+    #[test]
+    fn test_admin_cannot_grant_admin_or_super_admin() {
+        let admin = test_owner(1);
+        let other_admin = test_owner(2);
+        let viewer = test_owner(3);
+        let mut state = RBACState::new(admin);
+        state.assign_role(other_admin, Role::Admin).unwrap();
+
+        // The viewer is manageable, but not every role may be granted to them
+        assert!(state.can_manage(&other_admin, &viewer));
+        assert!(!state.can_grant(&other_admin, &Role::SuperAdmin));
+        assert!(!state.can_grant(&other_admin, &Role::Admin));
+        assert!(state.can_grant(&other_admin, &Role::Operator));
+
+        // Custom roles beyond the Admin's permissions cannot be granted either
+        let all = BTreeSet::from(Permission::ALL);
+        state.define_custom_role("all".to_string(), all).unwrap();
+        assert!(!state.can_grant(&other_admin, &Role::Custom("all".to_string())));
+        let metrics = BTreeSet::from([Permission::ModifyMetrics]);
+        state.define_custom_role("metrics".to_string(), metrics).unwrap();
+        assert!(state.can_grant(&other_admin, &Role::Custom("metrics".to_string())));
+
+        // Only the super admin grants Admin, and nobody grants SuperAdmin
+        assert!(state.can_grant(&admin, &Role::Admin));
+        assert!(!state.can_grant(&admin, &Role::SuperAdmin));
+        assert!(matches!(
+            state.assign_role(viewer, Role::SuperAdmin),
+            Err(RBACError::CannotGrantSuperAdmin)
+        ));
+        assert!(state.assign_role_until(viewer, Role::SuperAdmin, 5).is_err());
+        assert_eq!(state.get_role(&viewer), Role::Viewer);
+    }
+
+    #[test]
+    fn test_custom_roles_cannot_be_taken_over_by_weaker_callers() {
+        let admin = test_owner(1);
+        let other_admin = test_owner(2);
+        let manager = test_owner(3);
+        let mut state = RBACState::new(admin);
+        state.assign_role(other_admin, Role::Admin).unwrap();
+        let all = BTreeSet::from(Permission::ALL);
+        let weak = BTreeSet::from([Permission::ViewData]);
+
+        // A role the super admin defined with every permission is out of an
+        // Admin's reach: it cannot be redefined, deleted, or granted
+        state.define_custom_role("all".to_string(), all.clone()).unwrap();
+        assert!(state.can_define_custom_role(&admin, "all", &weak));
+        assert!(!state.can_define_custom_role(&other_admin, "all", &weak));
+        assert!(!state.can_delete_custom_role(&other_admin, "all"));
+        assert!(!state.can_grant(&other_admin, &Role::Custom("all".to_string())));
+
+        // A custom role with ManageRoles cannot raise itself
+        let managing = BTreeSet::from([Permission::ManageRoles, Permission::ViewData]);
+        state.define_custom_role("manager".to_string(), managing.clone()).unwrap();
+        state.assign_role(manager, Role::Custom("manager".to_string())).unwrap();
+        let mut raised = managing.clone();
+        raised.insert(Permission::ConfigureSystem);
+        assert!(!state.can_define_custom_role(&manager, "manager", &raised));
+        assert!(state.can_define_custom_role(&manager, "manager", &weak));
+        assert!(state.can_delete_custom_role(&other_admin, "manager"));
+    }
+
+    #[test]
+    fn test_temporary_role_expires_by_block() {
+        let admin = test_owner(1);
+        let maintainer = test_owner(2);
+        let mut state = RBACState::new(admin);
+        state.set_current_block(10);
+
+        assert!(matches!(
+            state.assign_role_until(maintainer, Role::Admin, 10),
+            Err(RBACError::GrantAlreadyExpired { .. })
+        ));
+        assert!(state.assign_role_until(admin, Role::SuperAdmin, 20).is_err());
+        state.assign_role_until(maintainer, Role::Admin, 20).unwrap();
+
+        // Before expiry the grant applies
+        state.set_current_block(19);
+        assert_eq!(state.get_role(&maintainer), Role::Admin);
+        assert!(state.has_permission(&maintainer, &Permission::ManageRoles));
+
+        // At the expiry block it no longer does, even before the block is applied
+        state.current_block = 20;
+        assert_eq!(state.get_role(&maintainer), Role::Viewer);
+        assert!(!state.has_permission(&maintainer, &Permission::ManageRoles));
+
+        // After expiry the assignment is gone
+        state.set_current_block(21);
+        assert!(!state.roles.contains_key(&maintainer));
+        assert!(state.role_expiries.is_empty());
+
+        // A permanent assignment clears an earlier expiry
+        state.assign_role_until(maintainer, Role::Operator, 30).unwrap();
+        state.assign_role(maintainer, Role::Operator).unwrap();
+        state.set_current_block(40);
+        assert_eq!(state.get_role(&maintainer), Role::Operator);
+    }
+
+    #[test]
+    fn test_owners_listed_by_role() {
+        let admin = test_owner(1);
+        let mut state = RBACState::new(admin);
+        state.assign_role(test_owner(2), Role::Admin).unwrap();
+        state.assign_role(test_owner(3), Role::Admin).unwrap();
+        state.assign_role(test_owner(4), Role::Operator).unwrap();
+
+        assert_eq!(state.owners_with_role(&Role::Admin), vec![test_owner(2), test_owner(3)]);
+        assert_eq!(state.role_counts()[&Role::Admin], 2);
+        assert!(!state.role_counts().contains_key(&Role::Viewer));
+
+        // The super admin is listed even without an explicit assignment
+        state.roles.remove(&admin);
+        assert_eq!(state.owners_with_role(&Role::SuperAdmin), vec![admin]);
+        assert_eq!(state.role_counts()[&Role::SuperAdmin], 1);
+        assert_eq!(state.assignments().len(), 4);
+    }
 }
@@ -7,15 +7,18 @@ use linera_sdk::linera_base_types::CryptoHash;
 use linera_sdk::{Service, ServiceRuntime};
 use pine_analytics::{
     AggregatedResult, AggregationEngine, AggregationQuery, AnalyticsAbi, AnalyticsState,
-    AnomalyEvent, AppConfig, CapturedEvent, CorrelationMatrix, EventFilters, MerkleIndex,
-    MerkleProof, MetricValue, MovingAveragePoint, Owner, Pagination, Permission,
-    RBACInfoResponse, Request, Response, SystemHealthResponse, TimeRange, TimeSeriesPoint,
+    AnalyticsStorage, AnomalyEvent, ApplicationId, BinaryPayload, CapturedEvent, CorrelationMatrix, EventFilters,
+    MerkleIndex, MerkleProof, MetricValue, MovingAveragePoint, Owner, Pagination, ProofLookup,
+    QueryCache, QueryStats, RBACInfoResponse, Request, Response, SystemHealthResponse, TimeRange,
+    TimeSeriesPoint, DEFAULT_FLATLINE_THRESHOLD,
 };
 use std::sync::Arc;
 
 /// Analytics service with enhanced query capabilities
 pub struct AnalyticsService {
     state: Arc<AnalyticsState>,
+    query_stats: Arc<QueryStats>,
+    query_cache: Arc<QueryCache>,
 }
 
 linera_sdk::service!(AnalyticsService);
@@ -27,19 +30,77 @@ impl WithServiceAbi for AnalyticsService {
 impl Service for AnalyticsService {
     type Parameters = ();
 
-    async fn new(_runtime: ServiceRuntime<Self>) -> Self {
-        let state = AnalyticsState::default();
+    async fn new(runtime: ServiceRuntime<Self>) -> Self {
+        let mut storage = AnalyticsStorage::load(runtime.root_view_storage_context())
+            .await
+            .expect("Failed to load state");
+        // Reads only, so state that failed to load is served as partial
+        let state = storage
+            .read_state_partial()
+            .await
+            .expect("Failed to read state");
         Self {
             state: Arc::new(state),
+            query_stats: Arc::new(QueryStats::new()),
+            query_cache: Arc::new(QueryCache::default()),
         }
     }
 
     async fn handle_query(&self, request: Request) -> Response {
+        self.query_stats.record(&request);
+
+        // Saved queries run as the request they stand for
+        let request = match request {
+            Request::RunSavedQuery { name, time_range } => {
+                match self.state.resolve_saved_query(&name, time_range.as_ref()) {
+                    Ok(request) => request,
+                    Err(e) => return Response::Error(e.to_string()),
+                }
+            }
+            request => request,
+        };
+
+        let is_health_check = matches!(request, Request::GetSystemHealth);
+        let response = if QueryCache::is_cacheable(&request) {
+            self.cached_query(request).await
+        } else {
+            self.execute_query(request).await
+        };
+
+        // Flag answers computed from partially loaded state
+        if self.state.unloaded_parts.is_empty() || is_health_check {
+            response
+        } else {
+            Response::Partial {
+                unloaded_parts: self.state.unloaded_parts.clone(),
+                response: Box::new(response),
+            }
+        }
+    }
+}
+
+// Query Dispatch
+impl AnalyticsService {
+    /// Answer a query from the cache, computing and caching it on a miss
+    async fn cached_query(&self, request: Request) -> Response {
+        if let Some(response) = self.query_cache.get(&request) {
+            return response;
+        }
+        let response = self.execute_query(request.clone()).await;
+        self.query_cache.insert(&request, response.clone());
+        response
+    }
+
+    /// Compute the response to a query
+    async fn execute_query(&self, request: Request) -> Response {
         match request {
             // === Basic Queries ===
-            Request::GetMonitoredApplications => {
-                let apps = self.get_monitored_applications().await;
-                Response::MonitoredApplications(apps)
+            Request::GetMonitoredApplications {
+                filters,
+                pagination,
+            } => {
+                let page = self.state.query_applications(&filters, &pagination);
+                Response::MonitoredApplications(page)
             }
             Request::GetApplicationMetrics {
                 application_id,
@@ -50,23 +111,136 @@ impl Service for AnalyticsService {
                     .await;
                 Response::ApplicationMetrics(metrics)
             }
+            Request::GetAllMetrics { filter } => {
+                Response::AllMetrics(self.state.all_metrics(filter.as_ref()))
+            }
+            Request::GetMetricsChangedSince { block } => {
+                Response::AllMetrics(self.state.metrics_changed_since(block))
+            }
             Request::GetEvents {
                 filters,
                 pagination,
+                fields: Some(fields),
+            } => match self
+                .state
+                .query_events_projected(&filters, &pagination, &fields)
+            {
+                Ok(events) => Response::ProjectedEvents(events),
+                Err(e) => Response::Error(e.to_string()),
+            },
+            Request::GetEvents {
+                filters,
+                pagination,
+                fields: None,
             } => {
                 let events = self.get_events(filters, pagination).await;
                 Response::Events(events)
             }
+            Request::GetEventPage {
+                filters,
+                pagination,
+            } => Response::EventPage(self.state.query_event_page(&filters, &pagination)),
+            Request::GetEventsBinary {
+                filters,
+                pagination,
+                format,
+            } => {
+                let events = self.get_events(filters, pagination).await;
+                match BinaryPayload::encode(format, &events) {
+                    Ok(payload) => Response::EventsBinary(payload),
+                    Err(e) => Response::Error(e.to_string()),
+                }
+            }
             Request::GetTimeSeries {
                 metric,
                 time_range,
                 granularity_ms,
+                application_id,
             } => {
                 let series = self
-                    .get_time_series(&metric, time_range, granularity_ms)
+                    .get_time_series(&metric, time_range, granularity_ms, application_id)
                     .await;
                 Response::TimeSeries(series)
             }
+            Request::GetMetricOwner { metric } => {
+                Response::MetricOwners(self.state.metric_owners(&metric))
+            }
+            Request::GetMetricsByTag { tag, time_range } => {
+                Response::TagMetrics(self.state.tag_metrics(&tag, time_range.as_ref()))
+            }
+            Request::GetMetricNames { sort } => Response::MetricNames(self.state.metric_names(sort)),
+            Request::GetDefinedMetricValue { name, time_range } => {
+                Response::DefinedMetricValue(self.state.defined_metric_value(&name, &time_range))
+            }
+            Request::GetMetricDefinitions { sort } => {
+                Response::MetricDefinitions(self.state.metric_definitions_sorted(sort))
+            }
+            Request::GetEventTree { root_event_id } => match self.state.event_tree(root_event_id) {
+                Some(tree) => Response::EventTree(tree),
+                None => Response::Error(format!("Event {} not found", root_event_id)),
+            },
+            Request::GetSevereEventsSince {
+                after_event_id,
+                min_severity,
+            } => Response::SevereEvents(
+                self.state
+                    .severe_events_since(after_event_id, &min_severity),
+            ),
+            Request::ValidateEvent { event } => {
+                Response::EventValidation(self.state.validate_event(&event))
+            }
+            Request::GetTransactions {
+                filters,
+                pagination,
+            } => {
+                let page = self.state.query_transactions(&filters, &pagination);
+                Response::Transactions(page)
+            }
+            Request::GetGasStats {
+                chain_id,
+                time_range,
+            } => {
+                let stats = self.state.gas_stats(chain_id, time_range);
+                Response::GasStats(stats)
+            }
+            Request::GetEventIdRange => Response::EventIdRange(self.state.event_id_range()),
+            Request::GetClearEventsToken => {
+                Response::ClearEventsToken(self.state.clear_events_token())
+            }
+            Request::GetSourceChains => Response::SourceChains(self.state.source_chains()),
+            Request::GetCheckpointDelta {
+                from_checkpoint,
+                to_checkpoint,
+            } => match self.state.checkpoint_delta(from_checkpoint, to_checkpoint) {
+                Ok(delta) => Response::CheckpointDelta(delta),
+                Err(e) => Response::Error(e.to_string()),
+            },
+            Request::GetAppUptime {
+                application_id,
+                heartbeat_type,
+                expected_interval_ms,
+                time_range,
+            } => match self.state.app_uptime(
+                &application_id,
+                &heartbeat_type,
+                expected_interval_ms,
+                &time_range,
+            ) {
+                Ok(uptime) => Response::AppUptime(uptime),
+                Err(e) => Response::Error(e.to_string()),
+            },
+            Request::GetOrphanEvents { time_range } => {
+                Response::Events(self.state.orphan_events(&time_range))
+            }
+            Request::GetIngestionLagStats { time_range } => {
+                Response::IngestionLagStats(self.state.ingestion_lag_stats(&time_range))
+            }
+            Request::GetEventSizeStats { application_id } => {
+                Response::EventSizeStats(self.state.event_size_stats(application_id.as_ref()))
+            }
+            Request::GetEventTypeCounts => {
+                Response::EventTypeCounts(self.state.event_type_counts.clone())
+            }
 
             // === Advanced Analytics ===
             Request::GetMovingAverage {
@@ -79,6 +253,14 @@ impl Service for AnalyticsService {
                     .await;
                 Response::MovingAverage(ma)
             }
+            Request::GetEWMA {
+                metric,
+                alpha,
+                time_range,
+            } => match self.state.metric_ewma(&metric, alpha, &time_range) {
+                Ok(points) => Response::MovingAverage(points),
+                Err(e) => Response::Error(e.to_string()),
+            },
             Request::DetectAnomalies {
                 metric,
                 sensitivity,
@@ -89,23 +271,96 @@ impl Service for AnalyticsService {
                     .await;
                 Response::Anomalies(anomalies)
             }
-            Request::GetAggregation { query } => {
-                let result = self.get_aggregation(query).await;
-                Response::Aggregation(result)
+            Request::GetAnomaliesWithSeries {
+                metric,
+                sensitivity,
+                time_range,
+                granularity_ms,
+            } => Response::AnomaliesWithSeries(self.state.anomalies_with_series(
+                &metric,
+                sensitivity,
+                &time_range,
+                granularity_ms,
+            )),
+            Request::DetectFlatlines {
+                metric,
+                window,
+                time_range,
+                threshold,
+            } => Response::Flatlines(self.state.detect_flatlines(
+                &metric,
+                window,
+                &time_range,
+                threshold.unwrap_or(DEFAULT_FLATLINE_THRESHOLD),
+            )),
+            Request::GetAnomalousMetrics { sensitivity, top_n } => {
+                Response::AnomalousMetrics(self.state.anomalous_metrics(sensitivity, top_n))
+            }
+            Request::GetAggregation { query, round_to } => {
+                match self.get_aggregation(query).await {
+                    Ok(mut result) => {
+                        if let Some(places) = round_to {
+                            result.round(places);
+                        }
+                        Response::Aggregation(result)
+                    }
+                    Err(e) => Response::Error(e.to_string()),
+                }
+            }
+            Request::GetVerifiableAggregation { query, round_to } => {
+                let mut aggregation = self.state.verifiable_aggregation(&query);
+                if let Some(places) = round_to {
+                    aggregation.result.round(places);
+                }
+                Response::VerifiableAggregation(aggregation)
             }
             Request::GetCorrelation {
                 metrics,
                 time_range,
+                max_samples,
+                round_to,
             } => {
-                let correlation = self.get_correlation(metrics, time_range).await;
+                let mut correlation = self
+                    .get_correlation(metrics, time_range, max_samples)
+                    .await;
+                if let Some(places) = round_to {
+                    correlation.round(places);
+                }
                 Response::Correlation(correlation)
             }
+            Request::CorrelateVolumeWithMetric {
+                event_type,
+                metric,
+                time_range,
+                granularity_ms,
+                round_to,
+            } => match self.state.correlate_volume_with_metric(
+                &event_type,
+                &metric,
+                &time_range,
+                granularity_ms,
+            ) {
+                Ok(mut correlation) => {
+                    if let Some(places) = round_to {
+                        correlation.round(places);
+                    }
+                    Response::VolumeCorrelation(correlation)
+                }
+                Err(e) => Response::Error(e.to_string()),
+            },
 
             // === Merkle Proofs ===
             Request::GetEventProof { event_id } => {
                 let proof = self.get_event_proof(event_id).await;
                 Response::EventProof(proof)
             }
+            Request::ExplainProof { event_id } => {
+                Response::ProofExplanation(self.state.merkle_index.explain_proof(event_id))
+            }
+            Request::GetEventProofs { event_ids } => match self.state.event_proofs(&event_ids) {
+                Ok(batch) => Response::EventProofs(batch),
+                Err(e) => Response::Error(e.to_string()),
+            },
             Request::VerifyEventProof {
                 proof,
                 expected_root,
@@ -113,38 +368,71 @@ impl Service for AnalyticsService {
                 let valid = self.verify_event_proof(&proof, &expected_root).await;
                 Response::ProofVerification(valid)
             }
+            Request::VerifyBatchProof {
+                batch,
+                expected_root,
+            } => Response::ProofVerification(MerkleIndex::verify_batch_proof(&expected_root, &batch)),
+            Request::VerifyEventProofWithTolerance { proof, root } => {
+                Response::ToleratedProofVerification(self.state.verify_proof_with_tolerance(&proof, &root))
+            }
+            Request::VerifyEventProofs {
+                proofs,
+                expected_root,
+            } => Response::ProofVerifications(MerkleIndex::verify_proofs(&expected_root, &proofs)),
             Request::GetMerkleRoot => {
                 let root = self.state.merkle_index.get_root();
                 Response::MerkleRoot(root)
             }
+            Request::GetMerkleRootAt { block } => {
+                Response::MerkleRoot(self.state.merkle_root_at(block))
+            }
 
             // === System Status ===
             Request::GetRateLimitStats => {
                 let stats = self.state.rate_limiter.get_stats();
                 Response::RateLimitStats(stats)
             }
+            Request::GetRecentRejections { limit } => {
+                Response::RecentRejections(self.state.rate_limiter.recent_rejections(limit))
+            }
             Request::GetRBACInfo { owner } => {
                 let info = self.get_rbac_info(owner).await;
                 Response::RBACInfo(info)
             }
+            Request::GetPermissionMatrix => {
+                Response::PermissionMatrix(self.state.rbac.permission_matrix())
+            }
+            Request::ListRoleAssignments { role } => {
+                let mut assignments = self.state.rbac.assignments();
+                if let Some(role) = role {
+                    assignments.retain(|(_, assigned)| *assigned == role);
+                }
+                Response::RoleAssignments(assignments)
+            }
+            Request::ExportRBAC => Response::RBACSnapshot(self.state.rbac.clone()),
+            Request::GetAuditLog {
+                pagination,
+                actor_filter,
+            } => Response::AuditLog(self.state.audit_entries(&pagination, actor_filter.as_ref())),
             Request::GetSystemHealth => {
                 let health = self.get_system_health().await;
                 Response::SystemHealth(health)
             }
+            Request::GetStorageEstimate => Response::StorageEstimate(self.state.storage_estimate()),
+            Request::CheckInvariants => Response::InvariantReport(self.state.check_invariants()),
+            Request::DiagnoseApp { application_id } => {
+                Response::AppDiagnosis(self.state.diagnose_app(&application_id))
+            }
+            Request::GetQueryStats => Response::QueryStats(self.query_stats.snapshot()),
+            Request::RunSavedQuery { name, .. } => {
+                Response::Error(format!("Saved query '{}' cannot be nested", name))
+            }
         }
     }
 }
 
 // Basic Query Methods
 impl AnalyticsService {
-    async fn get_monitored_applications(&self) -> Vec<AppConfig> {
-        self.state
-            .monitored_applications
-            .values()
-            .cloned()
-            .collect()
-    }
-
     async fn get_application_metrics(
         &self,
         application_id: &str,
@@ -159,88 +447,18 @@ impl AnalyticsService {
     }
 
     async fn get_events(&self, filters: EventFilters, pagination: Pagination) -> Vec<CapturedEvent> {
-        let filtered: Vec<CapturedEvent> = self
-            .state
-            .events
-            .iter()
-            .filter(|event| {
-                // Filter by application IDs
-                if let Some(ref app_ids) = filters.application_ids {
-                    if !app_ids.contains(&event.source_app) {
-                        return false;
-                    }
-                }
-                // Filter by event types
-                if let Some(ref event_types) = filters.event_types {
-                    if !event_types.contains(&event.event_type) {
-                        return false;
-                    }
-                }
-                // Filter by time range
-                if let Some(ref range) = filters.time_range {
-                    if !range.contains(event.timestamp) {
-                        return false;
-                    }
-                }
-                // Filter by severity
-                if let Some(ref severity) = filters.severity {
-                    if &event.severity != severity {
-                        return false;
-                    }
-                }
-                // Filter by search text
-                if let Some(ref text) = filters.search_text {
-                    let data_str = serde_json::to_string(&event.data).unwrap_or_default();
-                    if !data_str.to_lowercase().contains(&text.to_lowercase()) {
-                        return false;
-                    }
-                }
-                true
-            })
-            .cloned()
-            .collect();
-
-        // Apply pagination
-        filtered
-            .into_iter()
-            .skip(pagination.offset)
-            .take(pagination.limit)
-            .collect()
+        self.state.query_events(&filters, &pagination)
     }
 
     async fn get_time_series(
         &self,
-        _metric: &str,
+        metric: &str,
         time_range: TimeRange,
         granularity_ms: u64,
+        application_id: Option<ApplicationId>,
     ) -> Vec<TimeSeriesPoint> {
-        // Bucket events by time and compute metric values
-        let events: Vec<&CapturedEvent> = self
-            .state
-            .events
-            .iter()
-            .filter(|e| time_range.contains(e.timestamp))
-            .collect();
-
-        let mut points = Vec::new();
-        let mut current = time_range.start;
-
-        while current <= time_range.end {
-            let bucket_end = current + granularity_ms;
-            let bucket_events: Vec<_> = events
-                .iter()
-                .filter(|e| e.timestamp >= current && e.timestamp < bucket_end)
-                .collect();
-
-            let value = MetricValue::Counter(bucket_events.len() as u64);
-            points.push(TimeSeriesPoint {
-                timestamp: current,
-                value,
-            });
-            current = bucket_end;
-        }
-
-        points
+        self.state
+            .time_series(metric, &time_range, granularity_ms, application_id.as_ref())
     }
 }
 
@@ -282,27 +500,16 @@ impl AnalyticsService {
         AggregationEngine::detect_anomalies(&values, sensitivity)
     }
 
-    async fn get_aggregation(&self, query: AggregationQuery) -> AggregatedResult {
-        let values: Vec<f64> = self
-            .state
-            .aggregated_metrics
-            .iter()
-            .filter(|(k, _)| k.contains(&query.metric))
-            .map(|(_, v)| v.as_f64())
-            .collect();
-
-        let value = AggregationEngine::aggregate(&values, &query.aggregation);
-
-        AggregatedResult {
-            metric: query.metric,
-            aggregation: query.aggregation,
-            value,
-            bucket: None,
-            sample_count: values.len(),
-        }
+    async fn get_aggregation(&self, query: AggregationQuery) -> pine_analytics::Result<AggregatedResult> {
+        self.state.aggregate_metric(&query)
     }
 
-    async fn get_correlation(&self, metrics: Vec<String>, _time_range: TimeRange) -> CorrelationMatrix {
+    async fn get_correlation(
+        &self,
+        metrics: Vec<String>,
+        _time_range: TimeRange,
+        max_samples: Option<usize>,
+    ) -> CorrelationMatrix {
         // Collect values for each metric
         let metric_values: Vec<Vec<f64>> = metrics
             .iter()
@@ -316,37 +523,14 @@ impl AnalyticsService {
             })
             .collect();
 
-        // Compute correlation matrix
-        let n = metrics.len();
-        let mut coefficients = Vec::with_capacity(n * n);
-
-        for i in 0..n {
-            for j in 0..n {
-                if i == j {
-                    coefficients.push(1.0);
-                } else if metric_values[i].len() == metric_values[j].len() && !metric_values[i].is_empty() {
-                    coefficients.push(AggregationEngine::correlation(
-                        &metric_values[i],
-                        &metric_values[j],
-                    ));
-                } else {
-                    coefficients.push(0.0);
-                }
-            }
-        }
-
-        CorrelationMatrix {
-            chains: metrics,
-            coefficients,
-            metric: "correlation".to_string(),
-        }
+        CorrelationMatrix::from_series(metrics, "correlation".to_string(), &metric_values, max_samples)
     }
 }
 
 // Merkle Proof Methods
 impl AnalyticsService {
-    async fn get_event_proof(&self, event_id: u64) -> Option<MerkleProof> {
-        self.state.merkle_index.generate_proof(event_id)
+    async fn get_event_proof(&self, event_id: u64) -> ProofLookup {
+        self.state.merkle_index.lookup_proof(event_id)
     }
 
     async fn verify_event_proof(&self, proof: &MerkleProof, expected_root: &CryptoHash) -> bool {
@@ -360,22 +544,7 @@ impl AnalyticsService {
         let target = owner.unwrap_or_else(|| self.state.admin_owner.clone());
         let role = self.state.rbac.get_role(&target);
         
-        // Get all permissions for this role
-        let all_permissions = vec![
-            Permission::AddApplication,
-            Permission::RemoveApplication,
-            Permission::CaptureEvents,
-            Permission::ModifyMetrics,
-            Permission::ConfigureSystem,
-            Permission::ViewData,
-            Permission::ManageRoles,
-            Permission::ControlIngestion,
-        ];
-
-        let permissions: Vec<Permission> = all_permissions
-            .into_iter()
-            .filter(|p| pine_analytics::RBACState::role_has_permission(&role, p))
-            .collect();
+        let permissions = self.state.rbac.permissions_for(&role);
 
         RBACInfoResponse { role, permissions }
     }
@@ -383,10 +552,14 @@ impl AnalyticsService {
     async fn get_system_health(&self) -> SystemHealthResponse {
         SystemHealthResponse {
             total_events: self.state.total_events_captured,
+            total_events_seen: self.state.total_events_seen,
             total_applications: self.state.monitored_applications.len(),
             merkle_root: self.state.merkle_index.get_root(),
             rate_limit_enabled: self.state.rate_limiter.config.enabled,
             ingestion_paused: self.state.rate_limiter.paused,
+            maintenance_mode: self.state.maintenance_mode,
+            partial: !self.state.unloaded_parts.is_empty(),
+            unloaded_parts: self.state.unloaded_parts.clone(),
         }
     }
 }
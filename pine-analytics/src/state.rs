@@ -2,12 +2,19 @@
 //!
 //! Provides comprehensive state management with RBAC, rate limiting, and Merkle indexing.
 
+use linera_sdk::linera_base_types::CryptoHash;
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
 
-use crate::merkle::MerkleIndex;
+use crate::aggregations::{AggregationEngine, AggregationType};
+use crate::audit::AuditEntry;
+use crate::error::{AnalyticsError, Result};
+use crate::merkle::{EmptyRoot, MerkleIndex};
+use crate::queries::MAX_ANOMALY_ALERT_SCAN_EVENTS;
+use crate::oplog::OperationLogEntry;
 use crate::rate_limit::RateLimiterState;
 use crate::rbac::RBACState;
+use crate::{AdminOperation, Operation, Request};
 
 // Use Linera SDK types
 pub type ApplicationId = linera_sdk::linera_base_types::ApplicationId;
@@ -31,13 +38,36 @@ pub struct AnalyticsState {
     pub events: Vec<CapturedEvent>,
     /// Aggregated metrics
     pub aggregated_metrics: BTreeMap<MetricKey, MetricValue>,
-    /// Event index by timestamp
+    /// Samples ever offered to each histogram metric
+    pub histogram_samples_seen: BTreeMap<MetricKey, u64>,
+    /// Block at which each metric was last updated
+    pub metric_updated_at: BTreeMap<MetricKey, u64>,
+    /// Event index by timestamp; this and the other event and transaction
+    /// indexes are not stored but rebuilt on load, see `rebuild_indexes`
+    #[serde(skip)]
     pub event_index: BTreeMap<Timestamp, Vec<EventId>>,
     /// Event index by application
+    #[serde(skip)]
     pub app_index: BTreeMap<ApplicationId, Vec<EventId>>,
+    /// Event index by source chain
+    #[serde(skip)]
+    pub chain_index: BTreeMap<ChainId, Vec<EventId>>,
+    /// Child event IDs by parent event ID
+    #[serde(skip)]
+    pub child_index: BTreeMap<EventId, Vec<EventId>>,
     /// Next event ID (auto-increment)
     pub next_event_id: EventId,
 
+    // === Transactions ===
+    /// All captured transaction records
+    pub transactions: Vec<TransactionRecord>,
+    /// Transaction position by hash
+    #[serde(skip)]
+    pub transaction_index: BTreeMap<String, usize>,
+    /// Transaction positions by chain
+    #[serde(skip)]
+    pub chain_transaction_index: BTreeMap<ChainId, Vec<usize>>,
+
     // === Deduplication ===
     /// Transaction hash set for deduplication
     pub tx_hash_index: BTreeSet<String>,
@@ -53,16 +83,53 @@ pub struct AnalyticsState {
     // === Merkle Indexing ===
     /// Merkle tree for verifiable queries
     pub merkle_index: MerkleIndex,
+    /// Merkle mutations since the last full rebuild
+    pub merkle_mutations: u64,
+    /// Recorded Merkle checkpoints, indexed by checkpoint ID
+    pub checkpoints: Vec<MerkleCheckpoint>,
 
     // === Metric Definitions ===
     /// Custom metric definitions
     pub metric_definitions: BTreeMap<String, MetricDefinition>,
 
+    // === Configuration ===
+    /// Contract-wide configuration
+    pub config: AnalyticsConfig,
+    /// Named query templates, run with `RunSavedQuery`
+    pub saved_queries: BTreeMap<String, serde_json::Value>,
+
+    // === Replication ===
+    /// Append-only log of applied operations, from `operation_log_start`
+    pub operation_log: Vec<OperationLogEntry>,
+    /// Index of the first entry in `operation_log`; earlier entries were
+    /// truncated
+    #[serde(default)]
+    pub operation_log_start: u64,
+    /// Role changes and admin operations, oldest first
+    pub audit_log: Vec<AuditEntry>,
+    /// Chain whose replicated operation log is applied here
+    pub replication_source: Option<ChainId>,
+
+    /// Whether operations are rejected for maintenance
+    pub maintenance_mode: bool,
+
     // === Statistics ===
     /// Total events ever captured (including cleared)
     pub total_events_captured: u64,
+    /// Events that passed ingestion checks, including those dropped by
+    /// an app's `sample_rate`
+    #[serde(default)]
+    pub total_events_seen: u64,
+    /// Stored events per event type, kept while
+    /// `track_event_type_counts` is on
+    #[serde(skip)]
+    pub event_type_counts: BTreeMap<String, u64>,
     /// Current block height (for rate limiting)
     pub current_block: u64,
+
+    /// Fields that fell back to defaults in `load_partial`
+    #[serde(skip)]
+    pub unloaded_parts: Vec<String>,
 }
 
 impl Default for AnalyticsState {
@@ -75,16 +142,35 @@ impl Default for AnalyticsState {
             admin_owner: admin_owner.clone(),
             events: Vec::new(),
             aggregated_metrics: BTreeMap::new(),
+            histogram_samples_seen: BTreeMap::new(),
+            metric_updated_at: BTreeMap::new(),
             event_index: BTreeMap::new(),
             app_index: BTreeMap::new(),
+            chain_index: BTreeMap::new(),
+            child_index: BTreeMap::new(),
             next_event_id: 0,
+            transactions: Vec::new(),
+            transaction_index: BTreeMap::new(),
+            chain_transaction_index: BTreeMap::new(),
             tx_hash_index: BTreeSet::new(),
             rbac: RBACState::new(admin_owner),
             rate_limiter: RateLimiterState::default(),
             merkle_index: MerkleIndex::new(16),
+            merkle_mutations: 0,
+            checkpoints: Vec::new(),
             metric_definitions: BTreeMap::new(),
+            config: AnalyticsConfig::default(),
+            saved_queries: BTreeMap::new(),
+            operation_log: Vec::new(),
+            operation_log_start: 0,
+            audit_log: Vec::new(),
+            replication_source: None,
+            maintenance_mode: false,
             total_events_captured: 0,
+            total_events_seen: 0,
+            event_type_counts: BTreeMap::new(),
             current_block: 0,
+            unloaded_parts: Vec::new(),
         }
     }
 }
@@ -99,6 +185,260 @@ impl AnalyticsState {
         }
     }
 
+    /// Encode each top-level field separately, CBOR-encoded and keyed by
+    /// field name, so parts can be stored and loaded independently
+    pub fn snapshot_parts(&self) -> Result<BTreeMap<String, Vec<u8>>> {
+        let encoding_error = |e: &dyn std::fmt::Display| AnalyticsError::EncodingError(e.to_string());
+        let ciborium::Value::Map(fields) =
+            ciborium::Value::serialized(self).map_err(|e| encoding_error(&e))?
+        else {
+            return Err(AnalyticsError::EncodingError("State is not a map".to_string()));
+        };
+        fields
+            .into_iter()
+            .filter_map(|(name, value)| Some((name.into_text().ok()?, value)))
+            .map(|(name, value)| {
+                let mut bytes = Vec::new();
+                ciborium::into_writer(&value, &mut bytes).map_err(|e| encoding_error(&e))?;
+                Ok((name, bytes))
+            })
+            .collect()
+    }
+
+    /// Load state from `snapshot_parts` output; no parts at all is a fresh
+    /// deployment and gives the default state
+    pub fn from_parts(parts: &BTreeMap<String, Vec<u8>>) -> Self {
+        if parts.is_empty() {
+            Self::default()
+        } else {
+            Self::load_partial(parts)
+        }
+    }
+
+    /// Load state from `snapshot_parts` output, tolerating missing or
+    /// unreadable parts.
+    ///
+    /// Each part that is absent or fails to decode falls back to its default
+    /// and is listed in `unloaded_parts`, so reads return best-effort
+    /// results instead of failing outright. Indexes are rebuilt from
+    /// whatever events and transactions loaded.
+    pub fn load_partial(parts: &BTreeMap<String, Vec<u8>>) -> Self {
+        let Ok(ciborium::Value::Map(defaults)) = ciborium::Value::serialized(&Self::default()) else {
+            return Self::default();
+        };
+        let mut merged = defaults.clone();
+        let mut unloaded_parts = Vec::new();
+        for (index, (name, _)) in defaults.iter().enumerate() {
+            let Some(name) = name.as_text() else {
+                continue;
+            };
+            let value = parts
+                .get(name)
+                .and_then(|bytes| ciborium::from_reader::<ciborium::Value, _>(bytes.as_slice()).ok());
+            // Check the part on its own against the defaults
+            let loads = value.as_ref().is_some_and(|value| {
+                let mut candidate = defaults.clone();
+                candidate[index].1 = value.clone();
+                ciborium::Value::Map(candidate).deserialized::<Self>().is_ok()
+            });
+            match value {
+                Some(value) if loads => merged[index].1 = value,
+                _ => unloaded_parts.push(name.to_string()),
+            }
+        }
+
+        let mut state: Self = ciborium::Value::Map(merged).deserialized().unwrap_or_default();
+        if !unloaded_parts.is_empty() {
+            log::warn!("State partially loaded; using defaults for {:?}", unloaded_parts);
+        }
+        state.unloaded_parts = unloaded_parts;
+        state.rebuild_indexes();
+        state
+    }
+
+    /// Recompute every index derived from the stored events and
+    /// transactions, which are not stored themselves
+    pub fn rebuild_indexes(&mut self) {
+        self.rebuild_event_indexes();
+        self.transaction_index.clear();
+        self.chain_transaction_index.clear();
+        for (position, transaction) in self.transactions.iter().enumerate() {
+            self.transaction_index
+                .insert(transaction.hash.clone(), position);
+            self.chain_transaction_index
+                .entry(transaction.chain_id)
+                .or_default()
+                .push(position);
+        }
+    }
+
+    /// Replace the contract-wide configuration
+    pub fn apply_config(&mut self, config: AnalyticsConfig) {
+        self.merkle_index.set_empty_root(config.empty_merkle_root);
+        self.config = config;
+        self.recount_event_types();
+    }
+
+    /// Reject `operation` while maintenance mode is on.
+    ///
+    /// Only switching maintenance mode and replicating the operation log,
+    /// which leaves local state untouched, remain allowed. Messages are not
+    /// gated, so events arriving from other chains are still captured.
+    pub fn check_maintenance(&self, operation: &Operation) -> Result<()> {
+        let allowed = matches!(
+            operation,
+            Operation::AdminAction {
+                action: AdminOperation::SetMaintenanceMode { .. }
+            } | Operation::ReplicateOperationLog { .. }
+        );
+        if self.maintenance_mode && !allowed {
+            return Err(AnalyticsError::Maintenance);
+        }
+        Ok(())
+    }
+
+    /// Store a named query template, replacing any template of that name.
+    ///
+    /// The template is a serialized `Request` in which the strings
+    /// `"$time_range"`, `"$start"` and `"$end"` stand for the time range
+    /// supplied when the query is run.
+    pub fn save_query(&mut self, name: String, template: serde_json::Value) -> Result<()> {
+        if name.is_empty() {
+            return Err(AnalyticsError::QueryError("Saved query name is empty".to_string()));
+        }
+        let sample = fill_query_placeholders(&template, Some(&TimeRange::new(0, 0)))?;
+        let request: Request = serde_json::from_value(sample)
+            .map_err(|e| AnalyticsError::QueryError(format!("Invalid query template: {}", e)))?;
+        if matches!(request, Request::RunSavedQuery { .. }) {
+            return Err(AnalyticsError::QueryError(
+                "Saved queries cannot run other saved queries".to_string(),
+            ));
+        }
+        self.saved_queries.insert(name, template);
+        Ok(())
+    }
+
+    /// The request a saved query stands for, with placeholders filled in
+    pub fn resolve_saved_query(&self, name: &str, time_range: Option<&TimeRange>) -> Result<Request> {
+        let template = self
+            .saved_queries
+            .get(name)
+            .ok_or_else(|| AnalyticsError::QueryError(format!("No saved query named '{}'", name)))?;
+        let filled = fill_query_placeholders(template, time_range)?;
+        serde_json::from_value(filled)
+            .map_err(|e| AnalyticsError::QueryError(format!("Invalid query template: {}", e)))
+    }
+
+    /// Insert or replace a monitored application's configuration.
+    ///
+    /// New applications are subject to `max_monitored_apps`; replacing the
+    /// configuration of an already-monitored application is always allowed.
+    pub fn upsert_app_config(&mut self, mut config: AppConfig) -> Result<()> {
+        config.tags = self.validate_tags(config.tags)?;
+        if let Some(rate) = config.sample_rate {
+            if !(rate > 0.0 && rate <= 1.0) {
+                return Err(AnalyticsError::ConfigError(format!(
+                    "sample_rate must be in (0, 1], got {}",
+                    rate
+                )));
+            }
+        }
+        let is_new = !self
+            .monitored_applications
+            .contains_key(&config.application_id);
+        if is_new && self.monitored_applications.len() >= self.config.max_monitored_apps {
+            return Err(AnalyticsError::ConfigError(format!(
+                "Maximum of {} monitored applications reached",
+                self.config.max_monitored_apps
+            )));
+        }
+
+        self.monitored_applications
+            .insert(config.application_id, config);
+        Ok(())
+    }
+
+    /// Apply a JSON merge patch (RFC 7386) to a monitored application's config.
+    ///
+    /// The patched config must still deserialize as an `AppConfig` and keep
+    /// the same `application_id`; otherwise the stored config is unchanged.
+    pub fn patch_app_config(
+        &mut self,
+        application_id: &ApplicationId,
+        patch: &serde_json::Value,
+    ) -> Result<()> {
+        let current = self
+            .monitored_applications
+            .get(application_id)
+            .ok_or_else(|| AnalyticsError::ApplicationNotFound(format!("{:?}", application_id)))?;
+
+        let mut document = serde_json::to_value(current)?;
+        apply_merge_patch(&mut document, patch);
+        let patched: AppConfig = serde_json::from_value(document)
+            .map_err(|e| AnalyticsError::ConfigError(format!("Invalid patched config: {}", e)))?;
+
+        if &patched.application_id != application_id {
+            return Err(AnalyticsError::ConfigError(
+                "Patch cannot change application_id".to_string(),
+            ));
+        }
+
+        self.upsert_app_config(patched)
+    }
+
+    /// Apply a JSON merge patch (RFC 7386) to a stored metric definition.
+    ///
+    /// The name cannot change, and the merged extraction path must be usable.
+    pub fn update_metric_definition(&mut self, name: &str, patch: &serde_json::Value) -> Result<()> {
+        let current = self
+            .metric_definitions
+            .get(name)
+            .ok_or_else(|| AnalyticsError::InvalidMetric(format!("{} is not defined", name)))?;
+
+        let mut document = serde_json::to_value(current)?;
+        apply_merge_patch(&mut document, patch);
+        let patched: MetricDefinition = serde_json::from_value(document)
+            .map_err(|e| AnalyticsError::InvalidMetric(format!("Invalid patched definition: {}", e)))?;
+
+        if patched.name != name {
+            return Err(AnalyticsError::InvalidMetric(
+                "Patch cannot change the metric name".to_string(),
+            ));
+        }
+        validate_extraction_path(&patched.extraction_path)?;
+
+        self.metric_definitions.insert(patched.name.clone(), patched);
+        Ok(())
+    }
+
+    /// Deduplicate tags, keeping first occurrences in order, and check them
+    /// against `max_tags` and `max_tag_len`
+    fn validate_tags(&self, tags: Vec<String>) -> Result<Vec<String>> {
+        let mut unique: Vec<String> = Vec::with_capacity(tags.len());
+        for tag in tags {
+            if !unique.contains(&tag) {
+                unique.push(tag);
+            }
+        }
+        if unique.len() > self.config.max_tags {
+            return Err(AnalyticsError::ConfigError(format!(
+                "At most {} tags allowed, got {}",
+                self.config.max_tags,
+                unique.len()
+            )));
+        }
+        if let Some(tag) = unique
+            .iter()
+            .find(|tag| tag.chars().count() > self.config.max_tag_len)
+        {
+            return Err(AnalyticsError::ConfigError(format!(
+                "Tag '{}' exceeds {} characters",
+                tag, self.config.max_tag_len
+            )));
+        }
+        Ok(unique)
+    }
+
     /// Check if a transaction hash is duplicate
     pub fn is_duplicate_tx(&self, tx_hash: &str) -> bool {
         self.tx_hash_index.contains(tx_hash)
@@ -109,32 +449,1229 @@ impl AnalyticsState {
         self.events.iter().find(|e| e.id == event_id)
     }
 
-    /// Get events for an application
+    /// Token confirming a `ClearEvents` against the current event store.
+    ///
+    /// Derived from the capture count, stored count and Merkle root, so it
+    /// goes stale as soon as events are captured or cleared.
+    pub fn clear_events_token(&self) -> String {
+        let root = self
+            .merkle_index
+            .get_root()
+            .map(|root| root.to_string())
+            .unwrap_or_default();
+        format!(
+            "clear:{}:{}:{}",
+            self.total_events_captured,
+            self.events.len(),
+            root
+        )
+    }
+
+    /// Check a `ClearEvents` confirmation token on submission.
+    ///
+    /// Replicated clears are not checked again: the token describes the
+    /// primary's event store, which a standby does not mirror.
+    pub fn check_clear_events_token(&self, token: Option<&str>) -> Result<()> {
+        if token != Some(self.clear_events_token().as_str()) {
+            return Err(AnalyticsError::InvalidOperation(
+                "ClearEvents needs the current confirmation token".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Drop every stored event with its indexes and Merkle leaves
+    pub fn clear_events(&mut self) {
+        self.events.clear();
+        self.event_index.clear();
+        self.app_index.clear();
+        self.chain_index.clear();
+        self.child_index.clear();
+        self.event_type_counts.clear();
+        self.tx_hash_index.clear();
+        self.merkle_index.clear();
+        self.merkle_mutations = 0;
+    }
+
+    /// Pin or unpin a stored event
+    pub fn pin_event(&mut self, event_id: EventId, pinned: bool) -> Result<()> {
+        let event = self
+            .events
+            .iter_mut()
+            .find(|event| event.id == event_id)
+            .ok_or(AnalyticsError::EventNotFound(event_id))?;
+        event.pinned = pinned;
+        Ok(())
+    }
+
+    /// Direct children of an event
+    pub fn get_child_events(&self, parent_id: EventId) -> Vec<&CapturedEvent> {
+        self.child_index
+            .get(&parent_id)
+            .map(|ids| ids.iter().filter_map(|id| self.get_event(*id)).collect())
+            .unwrap_or_default()
+    }
+
+    /// Reattribute every event of `from_id` to `into_id`.
+    ///
+    /// Rebuilds the target's app index, drops the old app's config and
+    /// rate-limiter state, and returns the number of reassigned events.
+    /// Merkle leaves are left as captured so existing proofs keep verifying;
+    /// `RebuildMerkleIndex` rehashes the merged events if needed.
+    pub fn merge_applications(
+        &mut self,
+        from_id: &ApplicationId,
+        into_id: &ApplicationId,
+    ) -> Result<usize> {
+        if from_id == into_id {
+            return Err(AnalyticsError::ConfigError(
+                "Cannot merge an application into itself".to_string(),
+            ));
+        }
+
+        let mut merged = 0;
+        for event in self.events.iter_mut() {
+            if event.source_app == *from_id {
+                event.source_app = *into_id;
+                merged += 1;
+            }
+        }
+
+        self.app_index.remove(from_id);
+        let into_events: Vec<EventId> = self
+            .events
+            .iter()
+            .filter(|event| event.source_app == *into_id)
+            .map(|event| event.id)
+            .collect();
+        if !into_events.is_empty() {
+            self.app_index.insert(*into_id, into_events);
+        }
+
+        self.monitored_applications.remove(from_id);
+        self.rate_limiter.app_counters.remove(from_id);
+        self.rate_limiter.blocked_apps.remove(from_id);
+        self.rate_limiter.offenses.remove(from_id);
+        self.rate_limiter.overrides.remove(from_id);
+        self.rate_limiter.token_buckets.remove(from_id);
+        self.rate_limiter.window_log.remove(from_id);
+
+        Ok(merged)
+    }
+
+    /// Configured priority of an app; unmonitored apps have priority 0
+    pub fn app_priority(&self, application_id: &ApplicationId) -> u8 {
+        self.monitored_applications
+            .get(application_id)
+            .map_or(0, |config| config.priority)
+    }
+
+    /// Mark whether an event conforms to its app's schema for its type
+    pub fn apply_event_schema(&self, event: &mut CapturedEvent) {
+        event.schema_valid = self
+            .monitored_applications
+            .get(&event.source_app)
+            .and_then(|config| config.event_schemas.get(&event.event_type))
+            .map(|schema| schema.validate(&event.data));
+    }
+
+    /// Ensure an event's parent, if any, has already been captured
+    pub fn validate_parent(&self, event: &CapturedEvent) -> Result<()> {
+        match event.parent_event_id {
+            Some(parent_id) if self.get_event(parent_id).is_none() => {
+                Err(AnalyticsError::EventProcessingError(format!(
+                    "Parent event {} not found",
+                    parent_id
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Get events for an application, in canonical order
     pub fn get_app_events(&self, app_id: &ApplicationId) -> Vec<&CapturedEvent> {
-        self.app_index
+        let mut events: Vec<&CapturedEvent> = self
+            .app_index
             .get(app_id)
             .map(|ids| {
                 ids.iter()
                     .filter_map(|id| self.get_event(*id))
                     .collect()
             })
-            .unwrap_or_default()
+            .unwrap_or_default();
+        sort_events(&mut events);
+        events
     }
 
-    /// Get events in a time range
+    /// Get events in a time range, in canonical order
     pub fn get_events_in_range(&self, start: Timestamp, end: Timestamp) -> Vec<&CapturedEvent> {
-        self.event_index
+        let mut events: Vec<&CapturedEvent> = self
+            .event_index
             .range(start..=end)
             .flat_map(|(_, ids)| ids.iter().filter_map(|id| self.get_event(*id)))
+            .collect();
+        sort_events(&mut events);
+        events
+    }
+
+    /// Events matching `filters`, in canonical order, paginated
+    pub fn query_events(&self, filters: &EventFilters, pagination: &Pagination) -> Vec<CapturedEvent> {
+        self.matching_events(filters)
+            .into_iter()
+            .skip(pagination.offset)
+            .take(pagination.limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Same page as `query_events`, noting whether more matches follow it
+    pub fn query_event_page(&self, filters: &EventFilters, pagination: &Pagination) -> EventPage {
+        let matching = self.matching_events(filters);
+        let end = pagination.offset.saturating_add(pagination.limit);
+        EventPage {
+            truncated: matching.len() > end,
+            events: matching
+                .into_iter()
+                .skip(pagination.offset)
+                .take(pagination.limit)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Events matching `filters`, in canonical order
+    fn matching_events(&self, filters: &EventFilters) -> Vec<&CapturedEvent> {
+        let mut matching: Vec<&CapturedEvent> = self
+            .events
+            .iter()
+            .filter(|event| filters.matches(event))
+            .collect();
+        sort_events(&mut matching);
+        matching
+    }
+
+    /// Validate an event timestamp against capture time per `timestamp_policy`
+    pub fn apply_timestamp_policy(&self, event: &mut CapturedEvent, now: Timestamp) -> Result<()> {
+        let tolerance = self.config.timestamp_tolerance_ms;
+        let earliest = now.saturating_sub(tolerance);
+        let latest = now.saturating_add(tolerance);
+        if (earliest..=latest).contains(&event.timestamp) {
+            return Ok(());
+        }
+
+        match self.config.timestamp_policy {
+            TimestampPolicy::Accept => Ok(()),
+            TimestampPolicy::Clamp => {
+                event.timestamp = event.timestamp.clamp(earliest, latest);
+                Ok(())
+            }
+            TimestampPolicy::Reject => Err(AnalyticsError::EventProcessingError(format!(
+                "Event timestamp {} outside accepted window [{}, {}]",
+                event.timestamp, earliest, latest
+            ))),
+        }
+    }
+
+    /// Store a metric value.
+    ///
+    /// Histogram samples are appended to an existing histogram under the
+    /// same key, keeping at most `max_histogram_samples` through reservoir
+    /// sampling. Any other value replaces what was stored.
+    pub fn update_metric(&mut self, key: MetricKey, value: MetricValue) {
+        self.metric_updated_at.insert(key.clone(), self.current_block);
+        let MetricValue::Histogram(new_samples) = value else {
+            self.histogram_samples_seen.remove(&key);
+            self.aggregated_metrics.insert(key, value);
+            return;
+        };
+
+        let cap = self.config.max_histogram_samples;
+        let mut samples = match self.aggregated_metrics.remove(&key) {
+            Some(MetricValue::Histogram(samples)) => samples,
+            _ => {
+                self.histogram_samples_seen.remove(&key);
+                Vec::new()
+            }
+        };
+        let seen = self.histogram_samples_seen.entry(key.clone()).or_insert(0);
+        for sample in new_samples {
+            AggregationEngine::reservoir_insert(&mut samples, *seen, sample, cap);
+            *seen += 1;
+        }
+        self.aggregated_metrics
+            .insert(key, MetricValue::Histogram(samples));
+    }
+
+    /// Run every ingestion check on an event and store it.
+    ///
+    /// Rejects events from disabled apps, applies the timestamp policy and
+    /// schema flag, validates the parent, deduplicates, samples, and rate
+    /// limits. Returns `None` when the app's `sample_rate` drops the event;
+    /// it still counts towards `total_events_seen` but is not stored, so
+    /// its transaction hash is not deduplicated.
+    pub fn capture_event_checked(
+        &mut self,
+        mut event: CapturedEvent,
+        now: Timestamp,
+    ) -> Result<Option<EventId>> {
+        // Reject events from disabled applications or of disallowed types
+        if let Some(config) = self.monitored_applications.get(&event.source_app) {
+            if !config.enabled {
+                return Err(AnalyticsError::EventProcessingError(format!(
+                    "Application {:?} is disabled",
+                    event.source_app
+                )));
+            }
+            if !config.allows_event_type(&event.event_type) {
+                return Err(AnalyticsError::EventProcessingError(format!(
+                    "Event type '{}' is not allowed for application {:?}",
+                    event.event_type, event.source_app
+                )));
+            }
+        }
+
+        // Measure lag against the claimed timestamp, before any clamping;
+        // timestamps are client-supplied, so saturate rather than overflow
+        let lag = now as i128 - event.timestamp as i128;
+        event.ingestion_lag_ms = Some(lag.clamp(i64::MIN as i128, i64::MAX as i128) as i64);
+
+        // Validate the timestamp against capture time
+        self.apply_timestamp_policy(&mut event, now)?;
+
+        // Flag events that do not match their declared schema
+        self.apply_event_schema(&mut event);
+
+        // Parents must be captured before their children
+        self.validate_parent(&event)?;
+
+        // Check for duplicates
+        if self.is_duplicate_tx(&event.transaction_hash) {
+            return Err(AnalyticsError::DuplicateEvent(event.transaction_hash));
+        }
+
+        // Count every event, but store only the app's sample
+        self.total_events_seen += 1;
+        let sampled = self
+            .monitored_applications
+            .get(&event.source_app)
+            .is_none_or(|config| config.samples(&event.transaction_hash));
+        if !sampled {
+            return Ok(None);
+        }
+
+        // Check rate limit
+        let priority = self.app_priority(&event.source_app);
+        self.rate_limiter.advance_clock(now);
+        self.rate_limiter
+            .check_and_increment(&event.source_app, priority, self.current_block)?;
+
+        let event_id = self.insert_event(event);
+        self.emit_anomaly_alerts(event_id);
+        Ok(Some(event_id))
+    }
+
+    /// Capture an `ANOMALY_EVENT_TYPE` event for each defined metric whose
+    /// value in `event_id` is anomalous against its values in the last
+    /// `MAX_ANOMALY_ALERT_SCAN_EVENTS` stored events.
+    ///
+    /// Does nothing unless `anomaly_alerts` is configured. Alert events are
+    /// never scored themselves, so alerts cannot trigger further alerts.
+    fn emit_anomaly_alerts(&mut self, event_id: EventId) {
+        let Some(alerts) = self.config.anomaly_alerts.clone() else {
+            return;
+        };
+        // The event was just stored, so it sits at or near the end
+        let Some(event) = self.events.iter().rev().find(|e| e.id == event_id) else {
+            return;
+        };
+        if event.event_type == ANOMALY_EVENT_TYPE {
+            return;
+        }
+
+        // Only the latest events are searched, however sparse a metric is
+        let recent: Vec<&CapturedEvent> = self
+            .events
+            .iter()
+            .rev()
+            .take(MAX_ANOMALY_ALERT_SCAN_EVENTS)
+            .filter(|e| e.event_type != ANOMALY_EVENT_TYPE)
+            .collect();
+        let mut anomalies = Vec::new();
+        for metric in self.metric_definitions.keys() {
+            let Some(value) = self.extract_event_metric(event, metric) else {
+                continue;
+            };
+            let series: Vec<f64> = recent
+                .iter()
+                .filter_map(|e| self.extract_event_metric(e, metric))
+                .collect();
+            let std_dev = AggregationEngine::std_dev(&series);
+            if std_dev == 0.0 {
+                continue;
+            }
+            let z_score = (value - AggregationEngine::mean(&series)) / std_dev;
+            if z_score.abs() > alerts.sensitivity {
+                anomalies.push((metric.clone(), value, z_score));
+            }
+        }
+
+        let (source_app, source_chain, timestamp) =
+            (event.source_app, event.source_chain, event.timestamp);
+        for (metric, value, z_score) in anomalies {
+            let mut alert = CapturedEvent::new(
+                source_app,
+                source_chain,
+                timestamp,
+                ANOMALY_EVENT_TYPE.to_string(),
+                serde_json::json!({
+                    "metric": metric,
+                    "value": value,
+                    "z_score": z_score,
+                }),
+                format!("anomaly:{}:{}", event_id, metric),
+            );
+            alert.severity = if z_score.abs() >= alerts.error_z_score {
+                EventSeverity::Error
+            } else {
+                EventSeverity::Warning
+            };
+            alert.parent_event_id = Some(event_id);
+            self.insert_event(alert);
+        }
+    }
+
+    /// Capture a batch of events, returning the ID of the last one stored.
+    ///
+    /// Failing events are logged and skipped. Under `strict_mode` the first
+    /// failure instead rolls the whole batch back and returns
+    /// `BatchOperationFailed`; this snapshots the state up front, so strict
+    /// batches cost a full state copy.
+    pub fn capture_event_batch(
+        &mut self,
+        events: Vec<CapturedEvent>,
+        now: Timestamp,
+    ) -> Result<Option<EventId>> {
+        let total = events.len();
+        let mut snapshot = self.config.strict_mode.then(|| self.clone());
+        let mut last_id = None;
+        let mut processed = 0;
+
+        for event in events {
+            match self.capture_event_checked(event, now) {
+                Ok(id) => {
+                    last_id = id.or(last_id);
+                    processed += 1;
+                }
+                Err(e) => {
+                    log::warn!("Batch event failed: {}", e);
+                    if let Some(snapshot) = snapshot.take() {
+                        *self = snapshot;
+                        return Err(AnalyticsError::BatchOperationFailed { processed, total });
+                    }
+                }
+            }
+        }
+
+        Ok(last_id)
+    }
+
+    /// Store an event, assigning its ID and updating all indexes.
+    ///
+    /// Performs no deduplication or rate limiting; callers are expected to
+    /// have run those checks already.
+    pub fn insert_event(&mut self, mut event: CapturedEvent) -> EventId {
+        if self.config.canonical_event_data {
+            event.data = canonical_json(event.data);
+        }
+        event.pinned = false;
+
+        // Assign event ID
+        event.id = match self.config.event_id_strategy {
+            EventIdStrategy::Sequential => {
+                // Skip IDs taken while another strategy was in use
+                let mut id = self.next_event_id;
+                while self.merkle_index.leaves.contains_key(&id) {
+                    id += 1;
+                }
+                self.next_event_id = id + 1;
+                id
+            }
+            EventIdStrategy::ContentHash => {
+                // Step past IDs already taken by a different stored event
+                let mut id = event.content_id();
+                while self.merkle_index.leaves.contains_key(&id) {
+                    id = id.wrapping_add(1);
+                }
+                id
+            }
+        };
+        event.block_height = Some(self.current_block);
+
+        // Add to deduplication index
+        self.tx_hash_index.insert(event.transaction_hash.clone());
+
+        // Update indexes
+        self.event_index
+            .entry(event.timestamp)
+            .or_default()
+            .push(event.id);
+        self.app_index
+            .entry(event.source_app)
+            .or_default()
+            .push(event.id);
+        self.chain_index
+            .entry(event.source_chain)
+            .or_default()
+            .push(event.id);
+        if let Some(parent_id) = event.parent_event_id {
+            self.child_index.entry(parent_id).or_default().push(event.id);
+        }
+
+        // Update Merkle tree
+        let event_hash = CryptoHash::from(event.data_hash());
+        self.merkle_index.insert_hash(event.id, event_hash);
+
+        // Update statistics
+        self.total_events_captured += 1;
+        if self.config.track_event_type_counts {
+            *self
+                .event_type_counts
+                .entry(event.event_type.clone())
+                .or_default() += 1;
+        }
+
+        let event_id = event.id;
+        self.events.push(event);
+        self.record_merkle_mutation();
+        self.enforce_retention();
+        self.enforce_event_capacity();
+        event_id
+    }
+
+    /// Store events under the IDs they carry, resolving IDs that are
+    /// already stored, or repeated within `events`, per `on_conflict`.
+    ///
+    /// Under `Fail` nothing is imported if any ID collides. An overwrite
+    /// replaces the stored event and its Merkle leaf and keeps its pin.
+    /// Retention and capacity apply afterwards as on capture, so imported
+    /// events may be evicted straight away. Returns the outcome for each
+    /// event, in input order.
+    pub fn import_events(
+        &mut self,
+        events: Vec<CapturedEvent>,
+        on_conflict: ImportConflictPolicy,
+    ) -> Result<ImportReport> {
+        if on_conflict == ImportConflictPolicy::Fail {
+            let mut seen = BTreeSet::new();
+            if let Some(event) = events
+                .iter()
+                .find(|event| self.get_event(event.id).is_some() || !seen.insert(event.id))
+            {
+                return Err(AnalyticsError::InvalidOperation(format!(
+                    "Event {} already exists",
+                    event.id
+                )));
+            }
+        }
+
+        let mut outcomes = Vec::with_capacity(events.len());
+        for mut event in events {
+            let stored = self.events.iter().position(|stored| stored.id == event.id);
+            if stored.is_some() && on_conflict == ImportConflictPolicy::Skip {
+                outcomes.push((event.id, ImportOutcome::Skipped));
+                continue;
+            }
+
+            if self.config.canonical_event_data {
+                event.data = canonical_json(event.data);
+            }
+            event.block_height.get_or_insert(self.current_block);
+            event.pinned = stored.is_some_and(|index| self.events[index].pinned);
+            self.merkle_index
+                .insert_hash(event.id, CryptoHash::from(event.data_hash()));
+            self.tx_hash_index.insert(event.transaction_hash.clone());
+            self.next_event_id = self.next_event_id.max(event.id.saturating_add(1));
+
+            let event_id = event.id;
+            let outcome = match stored {
+                Some(index) => {
+                    let replaced = std::mem::replace(&mut self.events[index], event);
+                    if replaced.transaction_hash != self.events[index].transaction_hash {
+                        self.tx_hash_index.remove(&replaced.transaction_hash);
+                    }
+                    ImportOutcome::Overwritten
+                }
+                None => {
+                    self.events.push(event);
+                    self.total_events_captured += 1;
+                    ImportOutcome::Imported
+                }
+            };
+            outcomes.push((event_id, outcome));
+            self.record_merkle_mutation();
+        }
+
+        self.rebuild_event_indexes();
+        self.enforce_retention();
+        self.enforce_event_capacity();
+        Ok(ImportReport { outcomes })
+    }
+
+    /// Evict events once there are more than `max_total_events`, down to
+    /// `EVICTION_BATCH_DIVISOR` below it.
+    ///
+    /// Events from lower-priority apps go first, oldest first within a
+    /// priority; events from unmonitored apps count as priority 0. Pinned
+    /// events are never evicted, even if that leaves the store over
+    /// capacity. Evicted transaction hashes stay in the dedup index.
+    fn enforce_event_capacity(&mut self) {
+        let Some(max_events) = self.config.max_total_events else {
+            return;
+        };
+        if self.events.len() <= max_events {
+            return;
+        }
+        let low_water = max_events - max_events / EVICTION_BATCH_DIVISOR;
+        let excess = self.events.len() - low_water;
+
+        let mut candidates: Vec<(u8, Timestamp, EventId)> = self
+            .events
+            .iter()
+            .filter(|event| !event.pinned)
+            .map(|event| (self.app_priority(&event.source_app), event.timestamp, event.id))
+            .collect();
+        candidates.sort_unstable();
+        let evicted: BTreeSet<EventId> = candidates
+            .into_iter()
+            .take(excess)
+            .map(|(_, _, id)| id)
+            .collect();
+
+        self.remove_events(&evicted);
+        log::info!("Evicted {} events at capacity", evicted.len());
+    }
+
+    /// Evict unpinned events captured more than `retention_blocks` blocks
+    /// before the current one, returning how many were removed.
+    ///
+    /// Their transaction hashes leave the dedup index, so a later resubmit
+    /// is accepted again. `total_events_captured` keeps counting them.
+    pub fn evict_expired_events(&mut self) -> usize {
+        let Some(retention) = self.config.retention_blocks else {
+            return 0;
+        };
+        let cutoff = self.current_block.saturating_sub(retention);
+        let expired: BTreeSet<EventId> = self
+            .events
+            .iter()
+            .filter(|event| !event.pinned && event.block_height.unwrap_or(0) < cutoff)
+            .map(|event| event.id)
+            .collect();
+        if expired.is_empty() {
+            return 0;
+        }
+
+        for event in self.events.iter().filter(|event| expired.contains(&event.id)) {
+            self.tx_hash_index.remove(&event.transaction_hash);
+        }
+        self.remove_events(&expired);
+        log::info!("Evicted {} events past retention", expired.len());
+        expired.len()
+    }
+
+    /// Retention check run on capture. Events are stored in capture order,
+    /// so the full scan only runs once the oldest unpinned event expired.
+    fn enforce_retention(&mut self) {
+        let Some(retention) = self.config.retention_blocks else {
+            return;
+        };
+        let cutoff = self.current_block.saturating_sub(retention);
+        let oldest_expired = self
+            .events
+            .iter()
+            .find(|event| !event.pinned)
+            .is_some_and(|event| event.block_height.unwrap_or(0) < cutoff);
+        if oldest_expired {
+            self.evict_expired_events();
+        }
+    }
+
+    /// Drop stored events and their index entries and Merkle leaves
+    fn remove_events(&mut self, ids: &BTreeSet<EventId>) {
+        self.events.retain(|event| !ids.contains(&event.id));
+        self.rebuild_event_indexes();
+        let ids: Vec<EventId> = ids.iter().copied().collect();
+        self.merkle_index.remove_batch(&ids);
+    }
+
+    /// Recompute the timestamp, app, chain and parent indexes from the
+    /// stored events
+    fn rebuild_event_indexes(&mut self) {
+        self.event_index.clear();
+        self.app_index.clear();
+        self.chain_index.clear();
+        self.child_index.clear();
+        for event in &self.events {
+            self.event_index
+                .entry(event.timestamp)
+                .or_default()
+                .push(event.id);
+            self.app_index
+                .entry(event.source_app)
+                .or_default()
+                .push(event.id);
+            self.chain_index
+                .entry(event.source_chain)
+                .or_default()
+                .push(event.id);
+            if let Some(parent_id) = event.parent_event_id {
+                self.child_index.entry(parent_id).or_default().push(event.id);
+            }
+        }
+        self.recount_event_types();
+    }
+
+    /// Recompute `event_type_counts` from the stored events, or empty it
+    /// while tracking is off
+    fn recount_event_types(&mut self) {
+        self.event_type_counts.clear();
+        if !self.config.track_event_type_counts {
+            return;
+        }
+        for event in &self.events {
+            *self
+                .event_type_counts
+                .entry(event.event_type.clone())
+                .or_default() += 1;
+        }
+    }
+
+    /// Recompute the Merkle index from the stored events
+    pub fn rebuild_merkle_index(&mut self) {
+        self.merkle_index.clear();
+        for event in &self.events {
+            let event_hash = CryptoHash::from(event.data_hash());
+            self.merkle_index.insert_hash(event.id, event_hash);
+        }
+        self.merkle_mutations = 0;
+    }
+
+    /// Count a Merkle mutation, rebuilding the index once
+    /// `merkle_rebuild_threshold` mutations have accumulated
+    fn record_merkle_mutation(&mut self) {
+        self.merkle_mutations += 1;
+        if let Some(threshold) = self.config.merkle_rebuild_threshold {
+            if threshold > 0 && self.merkle_mutations >= threshold {
+                self.rebuild_merkle_index();
+                log::info!("Merkle index rebuilt after {} mutations", threshold);
+            }
+        }
+    }
+
+    /// Store a transaction record and index it by hash and chain
+    pub fn record_transaction(&mut self, transaction: TransactionRecord) -> Result<()> {
+        if self.transaction_index.contains_key(&transaction.hash) {
+            return Err(AnalyticsError::DuplicateEvent(transaction.hash));
+        }
+
+        let position = self.transactions.len();
+        self.transaction_index
+            .insert(transaction.hash.clone(), position);
+        self.chain_transaction_index
+            .entry(transaction.chain_id)
+            .or_default()
+            .push(position);
+        self.transactions.push(transaction);
+        Ok(())
+    }
+
+    /// Get a transaction record by hash
+    pub fn get_transaction(&self, hash: &str) -> Option<&TransactionRecord> {
+        self.transaction_index
+            .get(hash)
+            .and_then(|position| self.transactions.get(*position))
+    }
+
+    /// Transactions matching filters, in capture order
+    pub fn filter_transactions(&self, filters: &TransactionFilters) -> Vec<&TransactionRecord> {
+        let candidates: Vec<&TransactionRecord> = if let Some(ref hash) = filters.hash {
+            self.get_transaction(hash).into_iter().collect()
+        } else if let Some(ref chain_id) = filters.chain_id {
+            self.chain_transaction_index
+                .get(chain_id)
+                .map(|positions| {
+                    positions
+                        .iter()
+                        .filter_map(|position| self.transactions.get(*position))
+                        .collect()
+                })
+                .unwrap_or_default()
+        } else {
+            self.transactions.iter().collect()
+        };
+
+        candidates
+            .into_iter()
+            .filter(|tx| {
+                if let Some(ref chain_id) = filters.chain_id {
+                    if &tx.chain_id != chain_id {
+                        return false;
+                    }
+                }
+                if let Some(ref range) = filters.time_range {
+                    if !range.contains(tx.timestamp) {
+                        return false;
+                    }
+                }
+                true
+            })
             .collect()
     }
 
+    /// Query transactions matching filters, with total gas over all matches
+    pub fn query_transactions(
+        &self,
+        filters: &TransactionFilters,
+        pagination: &Pagination,
+    ) -> TransactionPage {
+        let matching = self.filter_transactions(filters);
+        let total_gas_used = matching.iter().filter_map(|tx| tx.gas_used).sum();
+
+        TransactionPage {
+            total_matching: matching.len(),
+            total_gas_used,
+            transactions: matching
+                .into_iter()
+                .skip(pagination.offset)
+                .take(pagination.limit)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Matching monitored applications in ID order, with the total match count
+    pub fn query_applications(
+        &self,
+        filters: &AppFilters,
+        pagination: &Pagination,
+    ) -> ApplicationPage {
+        let matching: Vec<&AppConfig> = self
+            .monitored_applications
+            .values()
+            .filter(|config| filters.enabled.is_none_or(|enabled| config.enabled == enabled))
+            .filter(|config| {
+                filters
+                    .tag
+                    .as_ref()
+                    .is_none_or(|tag| config.tags.contains(tag))
+            })
+            .collect();
+
+        ApplicationPage {
+            total_matching: matching.len(),
+            applications: matching
+                .into_iter()
+                .skip(pagination.offset)
+                .take(pagination.limit)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Gas usage statistics over matching transactions.
+    ///
+    /// Transactions without a recorded `gas_used` are counted but excluded
+    /// from the total, average and maximum.
+    pub fn gas_stats(&self, chain_id: Option<ChainId>, time_range: TimeRange) -> GasStats {
+        let matching = self.filter_transactions(&TransactionFilters {
+            chain_id,
+            hash: None,
+            time_range: Some(time_range),
+        });
+        let gas: Vec<f64> = matching
+            .iter()
+            .filter_map(|tx| tx.gas_used)
+            .map(|gas| gas as f64)
+            .collect();
+
+        let (max_gas_used, average_gas_used) = if gas.is_empty() {
+            (0, 0.0)
+        } else {
+            (
+                AggregationEngine::aggregate(&gas, &AggregationType::Max) as u64,
+                AggregationEngine::aggregate(&gas, &AggregationType::Average),
+            )
+        };
+
+        GasStats {
+            total_gas_used: matching.iter().filter_map(|tx| tx.gas_used).sum(),
+            average_gas_used,
+            max_gas_used,
+            transaction_count: matching.len(),
+            metered_count: gas.len(),
+        }
+    }
+
+    /// Apply an admin action to the state.
+    ///
+    /// A `ClearEvents` token must already have passed
+    /// `check_clear_events_token`.
+    pub fn execute_admin_action(&mut self, action: AdminOperation) -> Result<()> {
+        match action {
+            AdminOperation::PauseIngestion => {
+                self.rate_limiter.pause();
+                log::info!("Admin: Ingestion paused");
+            }
+            AdminOperation::ResumeIngestion => {
+                self.rate_limiter.resume();
+                log::info!("Admin: Ingestion resumed");
+            }
+            AdminOperation::SetRateLimit {
+                max_events_per_app_per_block,
+                max_total_events_per_block,
+            } => {
+                let mut config = self.rate_limiter.config.clone();
+                config.max_events_per_app_per_block = max_events_per_app_per_block;
+                config.max_total_events_per_block = max_total_events_per_block;
+                self.rate_limiter.try_update_config(config)?;
+                log::info!("Admin: Rate limit updated");
+            }
+            AdminOperation::ClearEvents { .. } => {
+                self.clear_events();
+                log::warn!("Admin: All events cleared!");
+            }
+            AdminOperation::EvictExpiredEvents => {
+                let evicted = self.evict_expired_events();
+                log::info!("Admin: Evicted {} expired events", evicted);
+            }
+            AdminOperation::ResetDedupIndex => {
+                let forgotten = self.tx_hash_index.len();
+                self.tx_hash_index.clear();
+                log::warn!("Admin: Dedup index reset ({} hashes forgotten)", forgotten);
+            }
+            AdminOperation::RebuildMerkleIndex => {
+                self.rebuild_merkle_index();
+                log::info!("Admin: Merkle index rebuilt");
+            }
+            AdminOperation::TransferSuperAdmin { new_admin } => {
+                self.admin_owner = new_admin;
+                self.rbac = RBACState::new(new_admin);
+                log::warn!("Admin: Super admin transferred!");
+            }
+            AdminOperation::UpdateConfig { config } => {
+                self.apply_config(config);
+                log::info!("Admin: Configuration updated");
+            }
+            AdminOperation::SetMaintenanceMode { enabled } => {
+                self.maintenance_mode = enabled;
+                log::warn!("Admin: Maintenance mode {}", if enabled { "enabled" } else { "disabled" });
+            }
+            AdminOperation::CreateCheckpoint => {
+                let checkpoint = MerkleCheckpoint {
+                    root: self.merkle_index.get_root(),
+                    block_height: self.current_block,
+                    event_ids: self.merkle_index.leaves.keys().copied().collect(),
+                };
+                self.checkpoints.push(checkpoint);
+                log::info!("Admin: Checkpoint {} created", self.checkpoints.len() - 1);
+            }
+        }
+        Ok(())
+    }
+
     /// Update block height (call at start of each block)
+    ///
+    /// Moving to a later block snapshots the Merkle root the previous block
+    /// ended with, so proofs issued during it can still be checked, and
+    /// prunes roots older than `root_history_blocks`.
     pub fn set_block_height(&mut self, block: u64) {
+        if block > self.current_block {
+            self.merkle_index.snapshot_root(self.current_block);
+            if let Some(window) = self.config.root_history_blocks {
+                self.merkle_index.prune_root_history(block.saturating_sub(window));
+            }
+        }
         self.current_block = block;
+        self.rbac.set_current_block(block);
+    }
+}
+
+/// `value` with the keys of every object in sorted order, at any depth.
+///
+/// Serializing the result yields canonical JSON: sorted keys and no
+/// insignificant whitespace.
+pub fn canonical_json(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<(String, serde_json::Value)> = map.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            serde_json::Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, canonical_json(value)))
+                    .collect(),
+            )
+        }
+        serde_json::Value::Array(items) => {
+            serde_json::Value::Array(items.into_iter().map(canonical_json).collect())
+        }
+        other => other,
+    }
+}
+
+/// Sort events into canonical order: by timestamp, then by event ID.
+///
+/// Every query returning events uses this order, so events sharing a
+/// timestamp come back in capture order on every call.
+pub fn sort_events(events: &mut [&CapturedEvent]) {
+    events.sort_by_key(|event| (event.timestamp, event.id));
+}
+
+/// Replace time range placeholders in a saved query template
+fn fill_query_placeholders(
+    template: &serde_json::Value,
+    time_range: Option<&TimeRange>,
+) -> Result<serde_json::Value> {
+    match template {
+        serde_json::Value::String(placeholder)
+            if matches!(placeholder.as_str(), "$time_range" | "$start" | "$end") =>
+        {
+            let range = time_range.ok_or_else(|| {
+                AnalyticsError::QueryError("Saved query needs a time_range".to_string())
+            })?;
+            Ok(match placeholder.as_str() {
+                "$start" => serde_json::json!(range.start),
+                "$end" => serde_json::json!(range.end),
+                _ => serde_json::to_value(range)?,
+            })
+        }
+        serde_json::Value::Array(items) => Ok(serde_json::Value::Array(
+            items
+                .iter()
+                .map(|item| fill_query_placeholders(item, time_range))
+                .collect::<Result<_>>()?,
+        )),
+        serde_json::Value::Object(fields) => Ok(serde_json::Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), fill_query_placeholders(value, time_range)?)))
+                .collect::<Result<_>>()?,
+        )),
+        other => Ok(other.clone()),
+    }
+}
+
+/// Check that `path` can select a value: a non-empty top-level key, or a
+/// JSON pointer whose `~` escapes are `~0` or `~1`
+pub fn validate_extraction_path(path: &str) -> Result<()> {
+    let problem = if path.is_empty() {
+        Some("extraction_path is empty")
+    } else if path.starts_with('/')
+        && path
+            .split('~')
+            .skip(1)
+            .any(|rest| !rest.starts_with('0') && !rest.starts_with('1'))
+    {
+        Some("extraction_path has an invalid ~ escape")
+    } else {
+        None
+    };
+    match problem {
+        Some(problem) => Err(AnalyticsError::InvalidMetric(problem.to_string())),
+        None => Ok(()),
+    }
+}
+
+/// Apply a JSON merge patch (RFC 7386) to `target` in place
+pub fn apply_merge_patch(target: &mut serde_json::Value, patch: &serde_json::Value) {
+    let serde_json::Value::Object(patch_fields) = patch else {
+        *target = patch.clone();
+        return;
+    };
+
+    if !target.is_object() {
+        *target = serde_json::Value::Object(serde_json::Map::new());
+    }
+    if let serde_json::Value::Object(target_fields) = target {
+        for (key, value) in patch_fields {
+            if value.is_null() {
+                target_fields.remove(key);
+            } else {
+                apply_merge_patch(
+                    target_fields
+                        .entry(key.clone())
+                        .or_insert(serde_json::Value::Null),
+                    value,
+                );
+            }
+        }
+    }
+}
+
+/// Contract-wide configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalyticsConfig {
+    /// Maximum number of monitored applications
+    pub max_monitored_apps: usize,
+    /// How to handle event timestamps outside `now ± timestamp_tolerance_ms`
+    pub timestamp_policy: TimestampPolicy,
+    /// Allowed distance between an event timestamp and capture time
+    pub timestamp_tolerance_ms: u64,
+    /// Abort on failures that are otherwise logged and skipped, such as a
+    /// bad event inside a batch or a failed sync ingestion
+    pub strict_mode: bool,
+    /// Samples retained per histogram metric
+    pub max_histogram_samples: usize,
+    /// Merkle root reported while no events are indexed
+    pub empty_merkle_root: EmptyRoot,
+    /// Rebuild the Merkle index from scratch after this many mutations;
+    /// `None` leaves rebuilds to `RebuildMerkleIndex`
+    pub merkle_rebuild_threshold: Option<u64>,
+    /// Source log levels to severities, for events ingested from raw logs
+    pub severity_mapping: SeverityMapping,
+    /// Capture anomalous metric values as alert events; off when `None`
+    pub anomaly_alerts: Option<AnomalyAlertConfig>,
+    /// How to aggregate a metric whose values have different types
+    pub mixed_metric_types: MixedMetricPolicy,
+    /// Stored events kept before evicting by app priority; `None` keeps all
+    pub max_total_events: Option<usize>,
+    /// How new events get their IDs
+    pub event_id_strategy: EventIdStrategy,
+    /// Recent checkpoints whose roots `verify_proof_with_tolerance` accepts
+    /// besides the current root
+    pub proof_checkpoint_tolerance: usize,
+    /// Store event data with object keys sorted, so the same logical data
+    /// always hashes the same
+    pub canonical_event_data: bool,
+    /// Tags allowed per monitored application, after deduplication
+    pub max_tags: usize,
+    /// Longest tag allowed, in characters
+    pub max_tag_len: usize,
+    /// Most events sent in one `SyncBatch` message
+    pub sync_batch_size: usize,
+    /// Blocks an unpinned event is kept after the block it was captured
+    /// in; `None` keeps events indefinitely
+    pub retention_blocks: Option<u64>,
+    /// Maintain `event_type_counts` for stored events
+    pub track_event_type_counts: bool,
+    /// Operation log entries kept, dropping the oldest beyond it; `None`
+    /// keeps the whole log
+    pub max_operation_log_entries: Option<usize>,
+    /// Blocks back from the current one that historical Merkle roots are
+    /// kept for; `None` keeps them all
+    pub root_history_blocks: Option<u64>,
+}
+
+/// What `import_events` does with an event whose ID is already stored
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum ImportConflictPolicy {
+    /// Keep the stored event and drop the imported one
+    #[default]
+    Skip,
+    /// Replace the stored event with the imported one
+    Overwrite,
+    /// Reject the whole import
+    Fail,
+}
+
+/// What happened to one imported event
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ImportOutcome {
+    Imported,
+    Skipped,
+    Overwritten,
+}
+
+/// Per-event outcomes of an import, in input order
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct ImportReport {
+    pub outcomes: Vec<(EventId, ImportOutcome)>,
+}
+
+/// How event IDs are assigned at capture
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum EventIdStrategy {
+    /// Auto-increment counter; IDs follow capture order
+    #[default]
+    Sequential,
+    /// `CapturedEvent::content_id`, so the same event gets the same ID on
+    /// every instance. IDs do not follow capture order, which ID cursors
+    /// such as `after_event_id` rely on; a collision with a stored event
+    /// takes the next free ID. Merkle leaves are ordered by ID, so each
+    /// capture lands mid-tree and rebuilds it: O(n) per event instead of
+    /// O(log n), which suits small or slow-growing stores.
+    ContentHash,
+}
+
+/// Policy for aggregating metric values of different types together
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum MixedMetricPolicy {
+    /// Coerce every value with `MetricValue::as_f64` (counters by count,
+    /// gauges by value, histograms by mean, summaries by average) and flag
+    /// the result as mixed
+    #[default]
+    Coerce,
+    /// Fail the aggregation
+    Reject,
+}
+
+/// Event type of alerts emitted for anomalous metric values
+pub const ANOMALY_EVENT_TYPE: &str = "Anomaly";
+
+/// Eviction at capacity frees this fraction (1/n) of `max_total_events` at
+/// once, so the sort and index rebuild run once per batch of captures
+pub const EVICTION_BATCH_DIVISOR: usize = 10;
+
+/// Thresholds for emitting anomaly alert events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnomalyAlertConfig {
+    /// Minimum |z-score| that raises an alert
+    pub sensitivity: f64,
+    /// |z-score| at or above which alerts are `Error` rather than `Warning`
+    pub error_z_score: f64,
+}
+
+impl Default for AnalyticsConfig {
+    fn default() -> Self {
+        Self {
+            max_monitored_apps: 1000,
+            timestamp_policy: TimestampPolicy::default(),
+            timestamp_tolerance_ms: 300_000,
+            strict_mode: false,
+            max_histogram_samples: 1000,
+            empty_merkle_root: EmptyRoot::default(),
+            merkle_rebuild_threshold: None,
+            severity_mapping: SeverityMapping::default(),
+            anomaly_alerts: None,
+            mixed_metric_types: MixedMetricPolicy::default(),
+            max_total_events: None,
+            event_id_strategy: EventIdStrategy::default(),
+            proof_checkpoint_tolerance: 0,
+            canonical_event_data: true,
+            max_tags: 32,
+            max_tag_len: 64,
+            sync_batch_size: 100,
+            retention_blocks: None,
+            track_event_type_counts: false,
+            max_operation_log_entries: Some(10_000),
+            root_history_blocks: None,
+        }
     }
 }
 
+/// Policy for event timestamps outside the accepted window
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum TimestampPolicy {
+    /// Reject the event
+    Reject,
+    /// Pin the timestamp to the nearest edge of the window
+    Clamp,
+    /// Store the timestamp unchanged
+    #[default]
+    Accept,
+}
+
 /// Application configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
@@ -143,10 +1680,74 @@ pub struct AppConfig {
     pub graphql_endpoint: String,
     pub enabled: bool,
     pub custom_metrics: Vec<MetricDefinition>,
-    /// Priority level for rate limiting (higher = more leniency)
+    /// Priority level for rate limiting and eviction (higher = more
+    /// leniency); scales the default limit by `priority_factor`
     pub priority: u8,
     /// Tags for categorization
     pub tags: Vec<String>,
+    /// Expected shape of each event type, keyed by event type
+    #[serde(default)]
+    pub event_schemas: BTreeMap<String, EventSchema>,
+    /// Event types this app may emit; `None` allows any type
+    #[serde(default)]
+    pub allowed_event_types: Option<Vec<String>>,
+    /// Offset of the app's local time from UTC, in minutes; `None` is UTC
+    #[serde(default)]
+    pub utc_offset_minutes: Option<i32>,
+    /// Fraction of events to store, chosen by transaction hash; `None`
+    /// stores every event
+    #[serde(default)]
+    pub sample_rate: Option<f64>,
+}
+
+impl AppConfig {
+    /// Whether the allow list permits `event_type`
+    pub fn allows_event_type(&self, event_type: &str) -> bool {
+        self.allowed_event_types
+            .as_ref()
+            .is_none_or(|allowed| allowed.iter().any(|t| t == event_type))
+    }
+
+    /// Whether the event with `tx_hash` falls in the stored sample.
+    ///
+    /// The same hash is always kept or always dropped, whichever validator
+    /// sees it.
+    pub fn samples(&self, tx_hash: &str) -> bool {
+        let Some(rate) = self.sample_rate else {
+            return true;
+        };
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in tx_hash.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+        // FNV leaves the high bits of short inputs poorly mixed
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        hash ^= hash >> 33;
+        rate >= 1.0 || (hash as f64 / u64::MAX as f64) < rate
+    }
+}
+
+/// Expected shape of an event's data
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct EventSchema {
+    /// Fields that must be present: JSON pointers if they start with `/`,
+    /// top-level keys otherwise
+    pub required_fields: Vec<String>,
+}
+
+impl EventSchema {
+    /// Whether `data` contains every required field
+    pub fn validate(&self, data: &serde_json::Value) -> bool {
+        self.required_fields.iter().all(|field| {
+            if field.starts_with('/') {
+                data.pointer(field).is_some()
+            } else {
+                data.get(field).is_some()
+            }
+        })
+    }
 }
 
 impl AppConfig {
@@ -160,12 +1761,16 @@ impl AppConfig {
             custom_metrics: vec![],
             priority: 0,
             tags: vec![],
+            event_schemas: BTreeMap::new(),
+            allowed_event_types: None,
+            utc_offset_minutes: None,
+            sample_rate: None,
         }
     }
 }
 
 /// Captured event from monitored applications
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CapturedEvent {
     pub id: EventId,
     pub source_app: ApplicationId,
@@ -178,6 +1783,24 @@ pub struct CapturedEvent {
     pub block_height: Option<u64>,
     /// Event severity/priority
     pub severity: EventSeverity,
+    /// Event this one responds to or was caused by
+    #[serde(default)]
+    pub parent_event_id: Option<EventId>,
+    /// Whether the data matched the app's schema for this event type;
+    /// `None` when no schema applies
+    #[serde(default)]
+    pub schema_valid: Option<bool>,
+    /// Primary numeric quantity, read as the metric named after `event_type`
+    #[serde(default)]
+    pub value: Option<f64>,
+    /// Capture time minus the claimed `timestamp`, in milliseconds;
+    /// negative when the event claims a time in the future
+    #[serde(default)]
+    pub ingestion_lag_ms: Option<i64>,
+    /// Kept through eviction; set only through `PinEvent`, and not part of
+    /// the hashed event content
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
 }
 
 impl CapturedEvent {
@@ -200,12 +1823,39 @@ impl CapturedEvent {
             transaction_hash,
             block_height: None,
             severity: EventSeverity::Info,
+            parent_event_id: None,
+            schema_valid: None,
+            value: None,
+            ingestion_lag_ms: None,
+            pinned: false,
+        }
+    }
+
+    /// Stable 64-bit FNV-1a hash of the transaction hash, event type and
+    /// data, used as the ID under `EventIdStrategy::ContentHash`
+    pub fn content_id(&self) -> EventId {
+        let data = self.data.to_string();
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for part in [&self.transaction_hash, &self.event_type, &data] {
+            for byte in part.bytes().chain(std::iter::once(0)) {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
         }
+        hash
     }
 
     /// Get hash of event data for Merkle tree
     pub fn data_hash(&self) -> [u8; 32] {
-        let json = serde_json::to_string(self).unwrap_or_default();
+        let json = if self.pinned {
+            serde_json::to_string(&Self {
+                pinned: false,
+                ..self.clone()
+            })
+        } else {
+            serde_json::to_string(self)
+        }
+        .unwrap_or_default();
         let mut hash = [0u8; 32];
         for (i, byte) in json.bytes().enumerate() {
             hash[i % 32] ^= byte;
@@ -214,8 +1864,8 @@ impl CapturedEvent {
     }
 }
 
-/// Event severity levels
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+/// Event severity levels, ordered from least to most severe
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Default)]
 pub enum EventSeverity {
     Debug,
     #[default]
@@ -225,6 +1875,58 @@ pub enum EventSeverity {
     Critical,
 }
 
+/// Table translating source log levels into `EventSeverity`.
+///
+/// Keys are lowercase; source levels are trimmed and lowercased before
+/// lookup. The default table covers syslog numeric levels (0-7) and common
+/// level names.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SeverityMapping {
+    pub levels: BTreeMap<String, EventSeverity>,
+}
+
+impl Default for SeverityMapping {
+    fn default() -> Self {
+        let levels = [
+            ("0", EventSeverity::Critical),
+            ("1", EventSeverity::Critical),
+            ("2", EventSeverity::Critical),
+            ("3", EventSeverity::Error),
+            ("4", EventSeverity::Warning),
+            ("5", EventSeverity::Info),
+            ("6", EventSeverity::Info),
+            ("7", EventSeverity::Debug),
+            ("trace", EventSeverity::Debug),
+            ("debug", EventSeverity::Debug),
+            ("info", EventSeverity::Info),
+            ("notice", EventSeverity::Info),
+            ("warn", EventSeverity::Warning),
+            ("warning", EventSeverity::Warning),
+            ("error", EventSeverity::Error),
+            ("err", EventSeverity::Error),
+            ("critical", EventSeverity::Critical),
+            ("crit", EventSeverity::Critical),
+            ("fatal", EventSeverity::Critical),
+            ("panic", EventSeverity::Critical),
+        ]
+        .into_iter()
+        .map(|(level, severity)| (level.to_string(), severity))
+        .collect();
+        Self { levels }
+    }
+}
+
+impl SeverityMapping {
+    /// Severity for a source level; unknown levels map to `Info`
+    pub fn map(&self, level: &str) -> EventSeverity {
+        self.levels
+            .get(&level.trim().to_lowercase())
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
 /// Transaction record
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionRecord {
@@ -235,6 +1937,72 @@ pub struct TransactionRecord {
     pub gas_used: Option<u64>,
 }
 
+/// Filters for monitored application queries
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct AppFilters {
+    pub enabled: Option<bool>,
+    /// Only apps carrying this tag
+    pub tag: Option<String>,
+}
+
+/// Merkle root and leaf set recorded at a point in time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MerkleCheckpoint {
+    pub root: Option<CryptoHash>,
+    pub block_height: u64,
+    /// Events indexed when the checkpoint was taken, in ID order
+    pub event_ids: Vec<EventId>,
+}
+
+/// Filters for metric snapshots
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct MetricFilter {
+    /// Only metrics owned by this app
+    pub application_id: Option<ApplicationId>,
+    /// Only metric keys containing this name
+    pub name: Option<String>,
+    /// Only metrics owned by apps carrying this tag
+    pub tag: Option<String>,
+}
+
+/// A page of monitored application configs
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ApplicationPage {
+    pub applications: Vec<AppConfig>,
+    /// Number of applications matching the filters (before pagination)
+    pub total_matching: usize,
+}
+
+/// Filters for transaction queries
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TransactionFilters {
+    pub chain_id: Option<ChainId>,
+    pub hash: Option<String>,
+    pub time_range: Option<TimeRange>,
+}
+
+/// A page of transaction query results
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TransactionPage {
+    pub transactions: Vec<TransactionRecord>,
+    /// Number of transactions matching the filters (before pagination)
+    pub total_matching: usize,
+    /// Gas used summed over all matching transactions
+    pub total_gas_used: u64,
+}
+
+/// Gas usage statistics over captured transactions
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct GasStats {
+    pub total_gas_used: u64,
+    pub average_gas_used: f64,
+    pub max_gas_used: u64,
+    /// Transactions in range, including those without gas data
+    pub transaction_count: usize,
+    /// Transactions that reported `gas_used`
+    pub metered_count: usize,
+}
+
 /// Metric value types
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum MetricValue {
@@ -260,6 +2028,16 @@ impl MetricValue {
             MetricValue::Summary { avg, .. } => *avg,
         }
     }
+
+    /// The metric type this value belongs to
+    pub fn metric_type(&self) -> MetricType {
+        match self {
+            MetricValue::Counter(_) => MetricType::Counter,
+            MetricValue::Gauge(_) => MetricType::Gauge,
+            MetricValue::Histogram(_) => MetricType::Histogram,
+            MetricValue::Summary { .. } => MetricType::Summary,
+        }
+    }
 }
 
 /// Metric definition for custom metrics
@@ -274,7 +2052,7 @@ pub struct MetricDefinition {
 }
 
 /// Metric types
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum MetricType {
     Counter,
     Gauge,
@@ -283,7 +2061,7 @@ pub enum MetricType {
 }
 
 /// Aggregation methods for metrics
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
 pub enum AggregationMethod {
     #[default]
     Sum,
@@ -293,6 +2071,22 @@ pub enum AggregationMethod {
     Last,
 }
 
+impl AggregationMethod {
+    /// Combine values given oldest first; `None` when there are none
+    pub fn apply(&self, values: &[f64]) -> Option<f64> {
+        let last = *values.last()?;
+        Some(match self {
+            AggregationMethod::Sum => AggregationEngine::aggregate(values, &AggregationType::Sum),
+            AggregationMethod::Average => {
+                AggregationEngine::aggregate(values, &AggregationType::Average)
+            }
+            AggregationMethod::Min => AggregationEngine::aggregate(values, &AggregationType::Min),
+            AggregationMethod::Max => AggregationEngine::aggregate(values, &AggregationType::Max),
+            AggregationMethod::Last => last,
+        })
+    }
+}
+
 /// Event filters for queries
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct EventFilters {
@@ -301,6 +2095,53 @@ pub struct EventFilters {
     pub time_range: Option<TimeRange>,
     pub severity: Option<EventSeverity>,
     pub search_text: Option<String>,
+    /// Only events validated against a schema with this outcome
+    #[serde(default)]
+    pub schema_valid: Option<bool>,
+}
+
+impl EventFilters {
+    /// Whether an event passes every filter
+    pub fn matches(&self, event: &CapturedEvent) -> bool {
+        // Filter by application IDs
+        if let Some(ref app_ids) = self.application_ids {
+            if !app_ids.contains(&event.source_app) {
+                return false;
+            }
+        }
+        // Filter by event types
+        if let Some(ref event_types) = self.event_types {
+            if !event_types.contains(&event.event_type) {
+                return false;
+            }
+        }
+        // Filter by time range
+        if let Some(ref range) = self.time_range {
+            if !range.contains(event.timestamp) {
+                return false;
+            }
+        }
+        // Filter by severity
+        if let Some(ref severity) = self.severity {
+            if &event.severity != severity {
+                return false;
+            }
+        }
+        // Filter by search text
+        if let Some(ref text) = self.search_text {
+            let data_str = serde_json::to_string(&event.data).unwrap_or_default();
+            if !data_str.to_lowercase().contains(&text.to_lowercase()) {
+                return false;
+            }
+        }
+        // Filter by schema validation outcome
+        if let Some(valid) = self.schema_valid {
+            if event.schema_valid != Some(valid) {
+                return false;
+            }
+        }
+        true
+    }
 }
 
 /// Time range for queries
@@ -336,6 +2177,14 @@ impl Default for Pagination {
     }
 }
 
+/// A page of events and whether more matches follow it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventPage {
+    pub events: Vec<CapturedEvent>,
+    /// True when matches beyond the page were left out
+    pub truncated: bool,
+}
+
 /// Time series data point
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TimeSeriesPoint {
@@ -0,0 +1,481 @@
+//! Persistent Storage for Pine Analytics
+//!
+//! Keeps `AnalyticsState` in Linera views so it survives across blocks.
+
+use linera_sdk::linera_base_types::CryptoHash;
+use linera_sdk::views::linera_views::context::Context;
+use linera_sdk::views::{linera_views, MapView, RootView, SetView, View, ViewError};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::audit::AuditEntry;
+use crate::error::{AnalyticsError, Result};
+use crate::oplog::OperationLogEntry;
+use crate::state::{
+    AnalyticsState, CapturedEvent, EventId, MerkleCheckpoint, TransactionRecord,
+};
+
+/// A value stored CBOR-encoded.
+///
+/// Event payloads are arbitrary JSON, which the views' own BCS encoding
+/// cannot decode, so values holding them are wrapped in this.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cbor<T>(pub T);
+
+impl<T: Serialize> Serialize for Cbor<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&self.0, &mut bytes).map_err(serde::ser::Error::custom)?;
+        bytes.serialize(serializer)
+    }
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for Cbor<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        ciborium::from_reader(bytes.as_slice())
+            .map(Cbor)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Root view of the application's storage.
+///
+/// Every collection that grows with use is stored one entry per key, and
+/// saving writes only the entries that changed. The remaining top-level
+/// state fields are small and stored as one CBOR part each, as encoded by
+/// `AnalyticsState::snapshot_parts`; indexes derived from events and
+/// transactions are not stored but rebuilt on load.
+#[derive(RootView)]
+pub struct AnalyticsView<C> {
+    /// Encoded state fields by field name, without the collections below
+    pub parts: MapView<C, String, Vec<u8>>,
+    /// Stored events by storage sequence number, which follows their order
+    /// in `AnalyticsState::events`
+    pub events: MapView<C, u64, Cbor<CapturedEvent>>,
+    /// Merkle leaf hashes by event ID
+    pub merkle_leaves: MapView<C, EventId, CryptoHash>,
+    /// Merkle tree nodes by node key
+    pub merkle_nodes: MapView<C, u64, CryptoHash>,
+    /// Operation log entries by log index
+    pub operation_log: MapView<C, u64, Cbor<OperationLogEntry>>,
+    /// Historical Merkle roots by block
+    pub root_history: MapView<C, u64, CryptoHash>,
+    /// Transaction hashes seen, for deduplication
+    pub tx_hashes: SetView<C, String>,
+    /// Audit log entries by position
+    pub audit_log: MapView<C, u64, AuditEntry>,
+    /// Merkle checkpoints by checkpoint ID
+    pub checkpoints: MapView<C, u64, MerkleCheckpoint>,
+    /// Transaction records by position
+    pub transactions: MapView<C, u64, TransactionRecord>,
+}
+
+/// Names reported in `unloaded_parts` for collections that fail to load
+/// from their own views
+const COLLECTION_PARTS: [&str; 9] = [
+    "events",
+    "merkle_leaves",
+    "merkle_nodes",
+    "operation_log",
+    "root_history",
+    "tx_hash_index",
+    "audit_log",
+    "checkpoints",
+    "transactions",
+];
+
+/// What the views hold as of the last load or save, so that saving writes
+/// only what changed since
+#[derive(Debug, Clone, Default)]
+struct Stored {
+    parts: BTreeMap<String, Vec<u8>>,
+    /// Storage sequence number and content of each stored event
+    events: BTreeMap<EventId, (u64, CapturedEvent)>,
+    next_sequence: u64,
+    merkle_leaves: BTreeMap<EventId, CryptoHash>,
+    merkle_nodes: BTreeMap<u64, CryptoHash>,
+    operation_log: BTreeSet<u64>,
+    root_history: BTreeMap<u64, CryptoHash>,
+    tx_hashes: BTreeSet<String>,
+    /// Lengths of the append-only collections
+    audit_log: usize,
+    checkpoints: usize,
+    transactions: usize,
+}
+
+/// The state collections kept in their own views, taken out of the state
+/// while the rest of it is encoded into parts
+#[derive(Default)]
+struct Collections {
+    events: Vec<CapturedEvent>,
+    merkle_leaves: BTreeMap<EventId, CryptoHash>,
+    merkle_nodes: BTreeMap<u64, CryptoHash>,
+    operation_log: Vec<OperationLogEntry>,
+    root_history: BTreeMap<u64, CryptoHash>,
+    tx_hashes: BTreeSet<String>,
+    audit_log: Vec<AuditEntry>,
+    checkpoints: Vec<MerkleCheckpoint>,
+    transactions: Vec<TransactionRecord>,
+}
+
+impl Collections {
+    fn take(state: &mut AnalyticsState) -> Self {
+        Self {
+            events: std::mem::take(&mut state.events),
+            merkle_leaves: std::mem::take(&mut state.merkle_index.leaves),
+            merkle_nodes: std::mem::take(&mut state.merkle_index.internal_nodes),
+            operation_log: std::mem::take(&mut state.operation_log),
+            root_history: std::mem::take(&mut state.merkle_index.root_history),
+            tx_hashes: std::mem::take(&mut state.tx_hash_index),
+            audit_log: std::mem::take(&mut state.audit_log),
+            checkpoints: std::mem::take(&mut state.checkpoints),
+            transactions: std::mem::take(&mut state.transactions),
+        }
+    }
+
+    fn restore(self, state: &mut AnalyticsState) {
+        state.events = self.events;
+        state.merkle_index.leaves = self.merkle_leaves;
+        state.merkle_index.internal_nodes = self.merkle_nodes;
+        state.operation_log = self.operation_log;
+        state.merkle_index.root_history = self.root_history;
+        state.tx_hash_index = self.tx_hashes;
+        state.audit_log = self.audit_log;
+        state.checkpoints = self.checkpoints;
+        state.transactions = self.transactions;
+    }
+}
+
+/// The application's storage, tracking what is already stored
+pub struct AnalyticsStorage<C> {
+    view: AnalyticsView<C>,
+    stored: Stored,
+}
+
+impl<C> AnalyticsStorage<C>
+where
+    C: Context + Clone + Send + Sync + 'static,
+{
+    /// Load the storage views
+    pub async fn load(context: C) -> Result<Self> {
+        let view = AnalyticsView::load(context).await.map_err(storage_error)?;
+        Ok(Self {
+            view,
+            stored: Stored::default(),
+        })
+    }
+
+    /// Decode the stored state; nothing stored yet gives the default state.
+    ///
+    /// Fails if any stored part cannot be loaded: saving a state that fell
+    /// back to defaults would overwrite the stored data with them. Fields
+    /// added since the state was saved have no part yet and take their
+    /// defaults.
+    pub async fn read_state(&mut self) -> Result<AnalyticsState> {
+        let mut state = self.read_state_partial().await?;
+        let unreadable: Vec<&String> = state
+            .unloaded_parts
+            .iter()
+            .filter(|name| {
+                self.stored.parts.contains_key(*name) || COLLECTION_PARTS.contains(&name.as_str())
+            })
+            .collect();
+        if !unreadable.is_empty() {
+            return Err(AnalyticsError::StorageError(format!(
+                "Failed to load state parts {:?}",
+                unreadable
+            )));
+        }
+        state.unloaded_parts.clear();
+        Ok(state)
+    }
+
+    /// Decode the stored state for reading only.
+    ///
+    /// Parts that fail to load fall back to their defaults and are listed in
+    /// `unloaded_parts`; see `AnalyticsState::load_partial`.
+    pub async fn read_state_partial(&mut self) -> Result<AnalyticsState> {
+        let mut parts = BTreeMap::new();
+        for name in self.view.parts.indices().await.map_err(storage_error)? {
+            if let Some(bytes) = self.view.parts.get(&name).await.map_err(storage_error)? {
+                parts.insert(name, bytes);
+            }
+        }
+        // State saved before the collections moved into their own views
+        // still holds them in its parts; they are merged with the views and
+        // moved there on the next save
+        let mut state = AnalyticsState::from_parts(&parts);
+        state
+            .unloaded_parts
+            .retain(|name| parts.contains_key(name) || !COLLECTION_PARTS.contains(&name.as_str()));
+        self.stored = Stored {
+            parts,
+            ..Stored::default()
+        };
+        let mut failed = Vec::new();
+
+        match read_map(&self.view.events).await {
+            Ok(events) => {
+                for (sequence, Cbor(event)) in events {
+                    self.stored.events.insert(event.id, (sequence, event.clone()));
+                    self.stored.next_sequence = sequence + 1;
+                    state.events.push(event);
+                }
+            }
+            Err(_) => failed.push(0),
+        }
+        match read_map(&self.view.merkle_leaves).await {
+            Ok(leaves) => {
+                self.stored.merkle_leaves = leaves.clone();
+                state.merkle_index.leaves.extend(leaves);
+            }
+            Err(_) => failed.push(1),
+        }
+        match read_map(&self.view.merkle_nodes).await {
+            Ok(nodes) => {
+                self.stored.merkle_nodes = nodes.clone();
+                state.merkle_index.internal_nodes.extend(nodes);
+            }
+            Err(_) => failed.push(2),
+        }
+        match read_map(&self.view.operation_log).await {
+            Ok(entries) => {
+                for (index, Cbor(entry)) in entries {
+                    self.stored.operation_log.insert(index);
+                    state.operation_log.push(entry);
+                }
+            }
+            Err(_) => failed.push(3),
+        }
+        match read_map(&self.view.root_history).await {
+            Ok(roots) => {
+                self.stored.root_history = roots.clone();
+                state.merkle_index.root_history.extend(roots);
+            }
+            Err(_) => failed.push(4),
+        }
+        match self.view.tx_hashes.indices().await {
+            Ok(hashes) => {
+                self.stored.tx_hashes = hashes.iter().cloned().collect();
+                state.tx_hash_index.extend(hashes);
+            }
+            Err(_) => failed.push(5),
+        }
+        match read_map(&self.view.audit_log).await {
+            Ok(entries) => {
+                self.stored.audit_log = entries.len();
+                state.audit_log.extend(entries.into_values());
+            }
+            Err(_) => failed.push(6),
+        }
+        match read_map(&self.view.checkpoints).await {
+            Ok(checkpoints) => {
+                self.stored.checkpoints = checkpoints.len();
+                state.checkpoints.extend(checkpoints.into_values());
+            }
+            Err(_) => failed.push(7),
+        }
+        match read_map(&self.view.transactions).await {
+            Ok(transactions) => {
+                self.stored.transactions = transactions.len();
+                state.transactions.extend(transactions.into_values());
+            }
+            Err(_) => failed.push(8),
+        }
+
+        state
+            .unloaded_parts
+            .extend(failed.into_iter().map(|part| COLLECTION_PARTS[part].to_string()));
+        state.rebuild_indexes();
+        Ok(state)
+    }
+
+    /// Stage the changes in `state` since the last load or save
+    pub fn write_state(&mut self, state: &mut AnalyticsState) -> Result<()> {
+        // Encode the other fields without the collections stored apart
+        let collections = Collections::take(state);
+        let parts = state.snapshot_parts();
+        collections.restore(state);
+        let mut parts = parts?;
+        parts.retain(|name, _| !COLLECTION_PARTS.contains(&name.as_str()));
+
+        let obsolete: Vec<String> = self
+            .stored
+            .parts
+            .keys()
+            .filter(|name| !parts.contains_key(*name))
+            .cloned()
+            .collect();
+        for name in obsolete {
+            self.view.parts.remove(&name).map_err(storage_error)?;
+            self.stored.parts.remove(&name);
+        }
+        for (name, bytes) in parts {
+            if self.stored.parts.get(&name) != Some(&bytes) {
+                self.view.parts.insert(&name, bytes.clone()).map_err(storage_error)?;
+                self.stored.parts.insert(name, bytes);
+            }
+        }
+
+        self.write_events(&state.events)?;
+        write_map_changes(
+            &mut self.view.merkle_leaves,
+            &mut self.stored.merkle_leaves,
+            &state.merkle_index.leaves,
+        )?;
+        write_map_changes(
+            &mut self.view.merkle_nodes,
+            &mut self.stored.merkle_nodes,
+            &state.merkle_index.internal_nodes,
+        )?;
+        write_map_changes(
+            &mut self.view.root_history,
+            &mut self.stored.root_history,
+            &state.merkle_index.root_history,
+        )?;
+
+        for hash in self.stored.tx_hashes.difference(&state.tx_hash_index) {
+            self.view.tx_hashes.remove(hash).map_err(storage_error)?;
+        }
+        for hash in state.tx_hash_index.difference(&self.stored.tx_hashes) {
+            self.view.tx_hashes.insert(hash).map_err(storage_error)?;
+        }
+        self.stored.tx_hashes.clone_from(&state.tx_hash_index);
+
+        // Log entries never change once written, only get truncated
+        let current: BTreeSet<u64> = state.operation_log.iter().map(|entry| entry.index).collect();
+        for index in self.stored.operation_log.difference(&current) {
+            self.view.operation_log.remove(index).map_err(storage_error)?;
+        }
+        for entry in &state.operation_log {
+            if !self.stored.operation_log.contains(&entry.index) {
+                self.view
+                    .operation_log
+                    .insert(&entry.index, Cbor(entry.clone()))
+                    .map_err(storage_error)?;
+            }
+        }
+        self.stored.operation_log = current;
+
+        write_appended(&mut self.view.audit_log, &mut self.stored.audit_log, &state.audit_log)?;
+        write_appended(&mut self.view.checkpoints, &mut self.stored.checkpoints, &state.checkpoints)?;
+        write_appended(&mut self.view.transactions, &mut self.stored.transactions, &state.transactions)?;
+        Ok(())
+    }
+
+    /// Stage `state` and persist it
+    pub async fn persist(&mut self, state: &mut AnalyticsState) -> Result<()> {
+        self.write_state(state)?;
+        self.view.save().await.map_err(storage_error)
+    }
+
+    /// Write new and changed events and remove dropped ones.
+    ///
+    /// Stored events keep their relative order, so an event keeps its
+    /// sequence number unless it now sits after events stored later, as a
+    /// re-added event does.
+    fn write_events(&mut self, events: &[CapturedEvent]) -> Result<()> {
+        let current: BTreeSet<EventId> = events.iter().map(|event| event.id).collect();
+        let dropped: Vec<EventId> = self
+            .stored
+            .events
+            .keys()
+            .filter(|id| !current.contains(id))
+            .copied()
+            .collect();
+        for id in dropped {
+            if let Some((sequence, _)) = self.stored.events.remove(&id) {
+                self.view.events.remove(&sequence).map_err(storage_error)?;
+            }
+        }
+
+        let mut last_sequence = None;
+        for event in events {
+            let sequence = match self.stored.events.get(&event.id) {
+                Some((sequence, _)) if last_sequence < Some(*sequence) => *sequence,
+                stale => {
+                    if let Some((sequence, _)) = stale {
+                        self.view.events.remove(sequence).map_err(storage_error)?;
+                    }
+                    let sequence = self.stored.next_sequence;
+                    self.stored.next_sequence += 1;
+                    sequence
+                }
+            };
+            last_sequence = Some(sequence);
+            let unchanged = matches!(
+                self.stored.events.get(&event.id),
+                Some((stored_sequence, stored)) if *stored_sequence == sequence && stored == event
+            );
+            if !unchanged {
+                self.view
+                    .events
+                    .insert(&sequence, Cbor(event.clone()))
+                    .map_err(storage_error)?;
+                self.stored.events.insert(event.id, (sequence, event.clone()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Every entry of a map view, by index
+async fn read_map<C, I, V>(map: &MapView<C, I, V>) -> std::result::Result<BTreeMap<I, V>, ViewError>
+where
+    C: Context + Clone + Send + Sync + 'static,
+    I: Serialize + DeserializeOwned + Ord + Send + Sync,
+    V: Serialize + DeserializeOwned + Clone + Send + Sync,
+{
+    let mut entries = BTreeMap::new();
+    for index in map.indices().await? {
+        if let Some(value) = map.get(&index).await? {
+            entries.insert(index, value);
+        }
+    }
+    Ok(entries)
+}
+
+/// Stage the differences between `stored` and `current` in `map`
+fn write_map_changes<C, I, V>(
+    map: &mut MapView<C, I, V>,
+    stored: &mut BTreeMap<I, V>,
+    current: &BTreeMap<I, V>,
+) -> Result<()>
+where
+    C: Context + Clone + Send + Sync + 'static,
+    I: Serialize + DeserializeOwned + Ord + Clone + Send + Sync,
+    V: Serialize + DeserializeOwned + Clone + PartialEq + Send + Sync,
+{
+    for index in stored.keys().filter(|index| !current.contains_key(*index)) {
+        map.remove(index).map_err(storage_error)?;
+    }
+    for (index, value) in current {
+        if stored.get(index) != Some(value) {
+            map.insert(index, value.clone()).map_err(storage_error)?;
+        }
+    }
+    stored.clone_from(current);
+    Ok(())
+}
+
+/// Stage the entries of an append-only collection past the `stored` ones
+fn write_appended<C, V>(map: &mut MapView<C, u64, V>, stored: &mut usize, current: &[V]) -> Result<()>
+where
+    C: Context + Clone + Send + Sync + 'static,
+    V: Serialize + DeserializeOwned + Clone + Send + Sync,
+{
+    for position in current.len()..*stored {
+        map.remove(&(position as u64)).map_err(storage_error)?;
+    }
+    for (position, value) in current.iter().enumerate().skip(*stored) {
+        map.insert(&(position as u64), value.clone()).map_err(storage_error)?;
+    }
+    *stored = current.len();
+    Ok(())
+}
+
+fn storage_error(error: ViewError) -> AnalyticsError {
+    AnalyticsError::StorageError(error.to_string())
+}
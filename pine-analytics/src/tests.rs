@@ -2,9 +2,11 @@
 //! 
 //! These tests verify serialization, schema consistency, and edge cases.
 
+use crate::queries::*;
 use crate::state::*;
 use proptest::prelude::*;
 use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
 use std::hash::{Hash, Hasher};
 
 // Helper function to create a CapturedEvent with all required fields
@@ -27,9 +29,22 @@ fn create_test_event(
         transaction_hash,
         block_height: None,
         severity: EventSeverity::Info,
+        parent_event_id: None,
+        schema_valid: None,
+        value: None,
+        ingestion_lag_ms: None,
+        pinned: false,
     }
 }
 
+// Helper function to store transfer event `i` from app1 on chain1, with its
+// own timestamp, payload and transaction hash `tx_{i}`
+fn capture_test_event(state: &mut AnalyticsState, i: u64) -> EventId {
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+    state.insert_event(create_test_event(0, app, chain, 1000 + i, "transfer".to_string(), serde_json::json!({"n": i}), format!("tx_{}", i)))
+}
+
 // Helper function to create AppConfig with all required fields
 fn create_test_app_config(
     application_id: ApplicationId,
@@ -44,6 +59,10 @@ fn create_test_app_config(
         custom_metrics: vec![],
         priority: 0,
         tags: vec![],
+        event_schemas: BTreeMap::new(),
+        allowed_event_types: None,
+        utc_offset_minutes: None,
+        sample_rate: None,
     }
 }
 
@@ -379,3 +398,2889 @@ proptest! {
         }
     }
 }
+
+// **Feature: pine-analytics, Transaction store queries**
+#[test]
+fn test_transactions_queryable_by_chain_and_hash() {
+    let mut state = AnalyticsState::default();
+    let chain_a = str_to_chain_id("chain_a");
+    let chain_b = str_to_chain_id("chain_b");
+
+    for (i, chain) in [chain_a, chain_a, chain_b].iter().enumerate() {
+        let mut tx = create_test_tx_record(format!("tx{}", i), *chain, i as u64 * 10);
+        tx.gas_used = Some(100 * (i as u64 + 1));
+        state.record_transaction(tx).unwrap();
+    }
+
+    // Duplicate hashes are rejected
+    let dup = create_test_tx_record("tx0".to_string(), chain_b, 99);
+    assert!(state.record_transaction(dup).is_err());
+
+    let by_chain = state.query_transactions(
+        &TransactionFilters {
+            chain_id: Some(chain_a),
+            ..Default::default()
+        },
+        &Pagination::default(),
+    );
+    assert_eq!(by_chain.total_matching, 2);
+    assert_eq!(by_chain.total_gas_used, 300);
+    assert!(by_chain.transactions.iter().all(|tx| tx.chain_id == chain_a));
+
+    let by_hash = state.query_transactions(
+        &TransactionFilters {
+            hash: Some("tx2".to_string()),
+            ..Default::default()
+        },
+        &Pagination::default(),
+    );
+    assert_eq!(by_hash.transactions.len(), 1);
+    assert_eq!(by_hash.transactions[0].chain_id, chain_b);
+
+    let paged = state.query_transactions(
+        &TransactionFilters::default(),
+        &Pagination { offset: 1, limit: 1 },
+    );
+    assert_eq!(paged.total_matching, 3);
+    assert_eq!(paged.transactions.len(), 1);
+    assert_eq!(paged.transactions[0].hash, "tx1");
+}
+
+// **Feature: pine-analytics, Gas usage aggregation**
+#[test]
+fn test_gas_stats_excludes_unmetered_transactions() {
+    let mut state = AnalyticsState::default();
+    let chain_a = str_to_chain_id("chain_a");
+    let chain_b = str_to_chain_id("chain_b");
+
+    let gas = [Some(100), Some(300), None, Some(200)];
+    for (i, gas_used) in gas.iter().enumerate() {
+        let mut tx = create_test_tx_record(format!("tx{}", i), chain_a, 1000 + i as u64);
+        tx.gas_used = *gas_used;
+        state.record_transaction(tx).unwrap();
+    }
+    let mut other = create_test_tx_record("other".to_string(), chain_b, 1000);
+    other.gas_used = Some(10_000);
+    state.record_transaction(other).unwrap();
+
+    let stats = state.gas_stats(Some(chain_a), TimeRange::new(0, 2000));
+    assert_eq!(stats.total_gas_used, 600);
+    assert_eq!(stats.max_gas_used, 300);
+    assert!((stats.average_gas_used - 200.0).abs() < 0.001);
+    assert_eq!(stats.transaction_count, 4);
+    assert_eq!(stats.metered_count, 3);
+
+    let all = state.gas_stats(None, TimeRange::new(0, 2000));
+    assert_eq!(all.total_gas_used, 10_600);
+    assert_eq!(all.max_gas_used, 10_000);
+
+    let empty = state.gas_stats(Some(chain_a), TimeRange::new(5000, 6000));
+    assert_eq!(empty.transaction_count, 0);
+    assert_eq!(empty.max_gas_used, 0);
+}
+
+// **Feature: pine-analytics, Volume-to-metric correlation**
+#[test]
+fn test_volume_tracks_metric_correlation() {
+    let mut state = AnalyticsState::default();
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+
+    // Bucket i holds i+1 "Transfer" events and one "Error" event whose
+    // error count grows with the volume.
+    let mut n = 0;
+    for bucket in 0..5u64 {
+        for _ in 0..=bucket {
+            state.insert_event(create_test_event(
+                0,
+                app,
+                chain,
+                bucket * 100 + 10,
+                "Transfer".to_string(),
+                serde_json::json!({}),
+                format!("hash{}", n),
+            ));
+            n += 1;
+        }
+        state.insert_event(create_test_event(
+            0,
+            app,
+            chain,
+            bucket * 100 + 50,
+            "Error".to_string(),
+            serde_json::json!({ "errors": (bucket * 2) as f64 }),
+            format!("hash{}", n),
+        ));
+        n += 1;
+    }
+
+    let result = state
+        .correlate_volume_with_metric("Transfer", "errors", &TimeRange::new(0, 499), 100)
+        .unwrap();
+
+    assert_eq!(result.bucket_starts, vec![0, 100, 200, 300, 400]);
+    assert_eq!(result.volume_series, vec![1.0, 2.0, 3.0, 4.0, 5.0]);
+    assert_eq!(result.metric_series, vec![0.0, 2.0, 4.0, 6.0, 8.0]);
+    assert!(result.coefficient > 0.99);
+
+    assert!(state
+        .correlate_volume_with_metric("Transfer", "errors", &TimeRange::new(0, 499), 0)
+        .is_err());
+}
+
+// **Feature: pine-analytics, Monitored application cap**
+#[test]
+fn test_max_monitored_apps_enforced() {
+    let mut state = AnalyticsState::default();
+    state.config.max_monitored_apps = 2;
+    let chain = str_to_chain_id("chain1");
+
+    for name in ["app1", "app2"] {
+        let config = create_test_app_config(str_to_app_id(name), chain, "http://a.b".to_string());
+        state.upsert_app_config(config).unwrap();
+    }
+
+    let third = create_test_app_config(str_to_app_id("app3"), chain, "http://a.b".to_string());
+    assert!(matches!(
+        state.upsert_app_config(third),
+        Err(crate::AnalyticsError::ConfigError(_))
+    ));
+
+    // Updating an existing app does not count against the cap
+    let mut update = create_test_app_config(str_to_app_id("app1"), chain, "http://c.d".to_string());
+    update.enabled = false;
+    state.upsert_app_config(update).unwrap();
+    assert_eq!(state.monitored_applications.len(), 2);
+    assert!(!state.monitored_applications[&str_to_app_id("app1")].enabled);
+}
+
+// **Feature: pine-analytics, AppConfig merge patch**
+#[test]
+fn test_patch_app_config_preserves_other_fields() {
+    let mut state = AnalyticsState::default();
+    let app = str_to_app_id("app1");
+    let mut config = create_test_app_config(app, str_to_chain_id("chain1"), "http://a.b".to_string());
+    config.priority = 3;
+    config.tags = vec!["defi".to_string()];
+    state.upsert_app_config(config).unwrap();
+
+    state
+        .patch_app_config(&app, &serde_json::json!({ "enabled": false }))
+        .unwrap();
+    let patched = &state.monitored_applications[&app];
+    assert!(!patched.enabled);
+    assert_eq!(patched.priority, 3);
+    assert_eq!(patched.tags, vec!["defi".to_string()]);
+    assert_eq!(patched.graphql_endpoint, "http://a.b");
+
+    state
+        .patch_app_config(&app, &serde_json::json!({ "tags": ["nft"] }))
+        .unwrap();
+    let patched = &state.monitored_applications[&app];
+    assert_eq!(patched.tags, vec!["nft".to_string()]);
+    assert!(!patched.enabled);
+    assert_eq!(patched.priority, 3);
+
+    // Invalid results are rejected and leave the config untouched
+    assert!(state
+        .patch_app_config(&app, &serde_json::json!({ "priority": "high" }))
+        .is_err());
+    assert!(state
+        .patch_app_config(&app, &serde_json::json!({ "graphql_endpoint": null }))
+        .is_err());
+    assert_eq!(state.monitored_applications[&app].priority, 3);
+}
+
+#[test]
+fn test_apply_merge_patch_rfc7386() {
+    let mut target = serde_json::json!({ "a": "b", "c": { "d": "e", "f": "g" } });
+    apply_merge_patch(&mut target, &serde_json::json!({ "a": "z", "c": { "f": null } }));
+    assert_eq!(target, serde_json::json!({ "a": "z", "c": { "d": "e" } }));
+}
+
+// **Feature: pine-analytics, App rejection diagnostics**
+#[test]
+fn test_diagnose_app_reports_all_reasons() {
+    let mut state = AnalyticsState::default();
+    let app = str_to_app_id("app1");
+    let healthy_app = str_to_app_id("app2");
+    let chain = str_to_chain_id("chain1");
+
+    let mut config = create_test_app_config(app, chain, "http://a.b".to_string());
+    config.enabled = false;
+    state.upsert_app_config(config).unwrap();
+    state
+        .upsert_app_config(create_test_app_config(healthy_app, chain, "http://a.b".to_string()))
+        .unwrap();
+
+    state.set_block_height(10);
+    state.rate_limiter.blocked_apps.insert(app, 15);
+
+    let diagnosis = state.diagnose_app(&app);
+    assert!(!diagnosis.healthy);
+    assert_eq!(diagnosis.reasons.len(), 2);
+    assert!(matches!(diagnosis.reasons[0], RejectionReason::AppDisabled));
+    assert!(matches!(
+        diagnosis.reasons[1],
+        RejectionReason::RateLimited(crate::RateLimitError::AppBlocked { unblock_at: 15, .. })
+    ));
+
+    let diagnosis = state.diagnose_app(&healthy_app);
+    assert!(diagnosis.healthy);
+    assert!(diagnosis.reasons.is_empty());
+}
+
+// **Feature: pine-analytics, Operation log replication**
+#[test]
+fn test_operation_log_replicates_to_standby() {
+    use crate::{AdminOperation, Operation, OperationLogEntry, Role};
+
+    let admin = Owner::Address20([1; 20]);
+    let operator = Owner::Address20([2; 20]);
+    let mut primary = AnalyticsState::new(admin);
+    let mut standby = AnalyticsState::new(admin);
+    let source = str_to_chain_id("primary");
+    primary.replication_source = Some(source);
+    standby.replication_source = Some(source);
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+
+    let operations = vec![
+        Operation::AddMonitoredApp {
+            application_id: app,
+            chain_id: chain,
+            graphql_endpoint: "http://a.b".to_string(),
+        },
+        Operation::PatchAppConfig {
+            application_id: app,
+            patch: serde_json::json!({ "priority": 7, "tags": ["defi"] }),
+        },
+        Operation::AssignRole {
+            target: operator,
+            role: Role::Operator,
+        },
+        Operation::AdminAction {
+            action: AdminOperation::PauseIngestion,
+        },
+    ];
+    for (block, operation) in operations.into_iter().enumerate() {
+        primary.set_block_height(block as u64);
+        primary.apply_replicated_log(source, vec![OperationLogEntry {
+            index: primary.operation_log.len() as u64,
+            block_height: block as u64,
+            caller: admin,
+            operation,
+        }])
+        .unwrap();
+    }
+    assert_eq!(primary.operation_log.len(), 4);
+
+    // Replaying an overlapping batch is idempotent
+    let applied = standby
+        .apply_replicated_log(source, primary.operation_log_since(0))
+        .unwrap();
+    assert_eq!(applied, 4);
+    let applied = standby
+        .apply_replicated_log(source, primary.operation_log_since(2))
+        .unwrap();
+    assert_eq!(applied, 0);
+
+    assert_eq!(standby.rbac.get_role(&operator), Role::Operator);
+    assert!(standby.rate_limiter.paused);
+    let config = &standby.monitored_applications[&app];
+    assert_eq!(config.priority, 7);
+    assert_eq!(config.tags, vec!["defi".to_string()]);
+    assert_eq!(
+        serde_json::to_value(&standby.monitored_applications).unwrap(),
+        serde_json::to_value(&primary.monitored_applications).unwrap()
+    );
+
+    // Gaps are rejected
+    let mut gapped = AnalyticsState::new(admin);
+    gapped.replication_source = Some(source);
+    assert!(gapped
+        .apply_replicated_log(source, primary.operation_log_since(1))
+        .is_err());
+}
+
+// **Feature: pine-analytics, Timestamp policy**
+#[test]
+fn test_timestamp_policy_on_far_future_event() {
+    let mut state = AnalyticsState::default();
+    state.config.timestamp_tolerance_ms = 1_000;
+    let now = 1_000_000;
+    let future = create_test_event(
+        0,
+        str_to_app_id("app1"),
+        str_to_chain_id("chain1"),
+        now + 1_000_000,
+        "Transfer".to_string(),
+        serde_json::json!({}),
+        "hash".to_string(),
+    );
+
+    state.config.timestamp_policy = TimestampPolicy::Accept;
+    let mut event = future.clone();
+    state.apply_timestamp_policy(&mut event, now).unwrap();
+    assert_eq!(event.timestamp, now + 1_000_000);
+
+    state.config.timestamp_policy = TimestampPolicy::Clamp;
+    let mut event = future.clone();
+    state.apply_timestamp_policy(&mut event, now).unwrap();
+    assert_eq!(event.timestamp, now + 1_000);
+
+    state.config.timestamp_policy = TimestampPolicy::Reject;
+    let mut event = future.clone();
+    assert!(state.apply_timestamp_policy(&mut event, now).is_err());
+
+    // In-window timestamps pass under every policy
+    let mut event = future;
+    event.timestamp = now + 500;
+    state.apply_timestamp_policy(&mut event, now).unwrap();
+    assert_eq!(event.timestamp, now + 500);
+}
+
+// **Feature: pine-analytics, Top anomalous metrics**
+#[test]
+fn test_anomalous_metric_ranks_above_quiet_ones() {
+    let mut state = AnalyticsState::default();
+    let quiet: Vec<f64> = (0..20).map(|i| 10.0 + (i % 3) as f64).collect();
+    let mut noisy = quiet.clone();
+    noisy.push(500.0);
+    let mut mild = quiet.clone();
+    mild.push(16.0);
+
+    state
+        .aggregated_metrics
+        .insert("app_quiet".to_string(), MetricValue::Histogram(quiet));
+    state
+        .aggregated_metrics
+        .insert("app_noisy".to_string(), MetricValue::Histogram(noisy));
+    state
+        .aggregated_metrics
+        .insert("app_mild".to_string(), MetricValue::Histogram(mild));
+
+    let ranked = state.anomalous_metrics(2.0, 10);
+    assert_eq!(ranked[0].metric, "app_noisy");
+    assert!(ranked.iter().all(|score| score.metric != "app_quiet"));
+    assert!(ranked.windows(2).all(|w| w[0].z_score.abs() >= w[1].z_score.abs()));
+
+    let top_one = state.anomalous_metrics(0.0, 1);
+    assert_eq!(top_one.len(), 1);
+    assert_eq!(top_one[0].metric, "app_noisy");
+}
+
+// **Feature: pine-analytics, Metric owner lookup**
+#[test]
+fn test_metric_owner_resolves_namespaced_key() {
+    let mut state = AnalyticsState::default();
+    let chain = str_to_chain_id("chain1");
+    let app1 = str_to_app_id("app1");
+    let app2 = str_to_app_id("app2");
+    for app in [app1, app2] {
+        state
+            .upsert_app_config(create_test_app_config(app, chain, "http://a.b".to_string()))
+            .unwrap();
+    }
+
+    let key = format!("{}_event_count", app1);
+    assert_eq!(state.metric_owners(&key), vec![app1]);
+    assert!(state.metric_owners("unrelated_metric").is_empty());
+    // A bare prefix match without the separator does not count
+    assert!(state.metric_owners(&format!("{}x", app2)).is_empty());
+}
+
+// **Feature: pine-analytics, Event trees**
+#[test]
+fn test_event_tree_links_parent_and_children() {
+    let mut state = AnalyticsState::default();
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+
+    let request = create_test_event(0, app, chain, 1000, "request".to_string(), serde_json::json!({}), "tx_req".to_string());
+    let root_id = state.insert_event(request);
+
+    let mut children = Vec::new();
+    for i in 0..2 {
+        let mut response = create_test_event(0, app, chain, 1001 + i, "response".to_string(), serde_json::json!({}), format!("tx_resp_{}", i));
+        response.parent_event_id = Some(root_id);
+        state.validate_parent(&response).unwrap();
+        children.push(state.insert_event(response));
+    }
+
+    let mut retry = create_test_event(0, app, chain, 1003, "retry".to_string(), serde_json::json!({}), "tx_retry".to_string());
+    retry.parent_event_id = Some(children[0]);
+    let grandchild_id = state.insert_event(retry);
+
+    let tree = state.event_tree(root_id).unwrap();
+    assert_eq!(tree.event.id, root_id);
+    let child_ids: Vec<EventId> = tree.children.iter().map(|c| c.event.id).collect();
+    assert_eq!(child_ids, children);
+    assert_eq!(tree.children[0].children[0].event.id, grandchild_id);
+    assert!(tree.children[1].children.is_empty());
+
+    // Unknown parents are rejected at capture time
+    let mut orphan = create_test_event(0, app, chain, 1004, "response".to_string(), serde_json::json!({}), "tx_orphan".to_string());
+    orphan.parent_event_id = Some(999);
+    assert!(state.validate_parent(&orphan).is_err());
+    assert!(state.event_tree(999).is_none());
+}
+
+// **Feature: pine-analytics, Schema validation filter**
+#[test]
+fn test_filter_events_failing_schema_validation() {
+    let mut state = AnalyticsState::default();
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+
+    let mut config = create_test_app_config(app, chain, "http://a.b".to_string());
+    config.event_schemas.insert(
+        "transfer".to_string(),
+        EventSchema {
+            required_fields: vec!["amount".to_string(), "/meta/sender".to_string()],
+        },
+    );
+    state.upsert_app_config(config).unwrap();
+
+    let mut conforming = create_test_event(0, app, chain, 1000, "transfer".to_string(), serde_json::json!({"amount": 5, "meta": {"sender": "a"}}), "tx_ok".to_string());
+    let mut broken = create_test_event(0, app, chain, 1001, "transfer".to_string(), serde_json::json!({"amount": 5}), "tx_bad".to_string());
+    let mut unschematized = create_test_event(0, app, chain, 1002, "mint".to_string(), serde_json::json!({}), "tx_mint".to_string());
+    for event in [&mut conforming, &mut broken, &mut unschematized] {
+        state.apply_event_schema(event);
+    }
+    assert_eq!(conforming.schema_valid, Some(true));
+    assert_eq!(broken.schema_valid, Some(false));
+    assert_eq!(unschematized.schema_valid, None);
+
+    state.insert_event(conforming);
+    let broken_id = state.insert_event(broken);
+    state.insert_event(unschematized);
+
+    let filters = EventFilters {
+        schema_valid: Some(false),
+        ..Default::default()
+    };
+    let invalid: Vec<EventId> = state
+        .events
+        .iter()
+        .filter(|event| filters.matches(event))
+        .map(|event| event.id)
+        .collect();
+    assert_eq!(invalid, vec![broken_id]);
+}
+
+// **Feature: pine-analytics, Application merging**
+#[test]
+fn test_merge_applications_combines_event_histories() {
+    let mut state = AnalyticsState::default();
+    let chain = str_to_chain_id("chain1");
+    let old_app = str_to_app_id("old_app");
+    let new_app = str_to_app_id("new_app");
+    for app in [old_app, new_app] {
+        state
+            .upsert_app_config(create_test_app_config(app, chain, "http://a.b".to_string()))
+            .unwrap();
+    }
+
+    for i in 0..3 {
+        state.insert_event(create_test_event(0, old_app, chain, 1000 + i, "transfer".to_string(), serde_json::json!({}), format!("tx_old_{}", i)));
+    }
+    for i in 0..2 {
+        state.insert_event(create_test_event(0, new_app, chain, 2000 + i, "transfer".to_string(), serde_json::json!({}), format!("tx_new_{}", i)));
+    }
+    let root_before = state.merkle_index.get_root();
+
+    assert_eq!(state.merge_applications(&old_app, &new_app).unwrap(), 3);
+
+    assert_eq!(state.get_app_events(&new_app).len(), 5);
+    assert!(state.get_app_events(&old_app).is_empty());
+    assert!(!state.monitored_applications.contains_key(&old_app));
+    assert!(state.monitored_applications.contains_key(&new_app));
+    assert_eq!(state.merkle_index.get_root(), root_before);
+
+    assert!(state.merge_applications(&new_app, &new_app).is_err());
+}
+
+// **Feature: pine-analytics, Metric discovery ordering**
+#[test]
+fn test_metric_names_sorted_alphabetically_and_by_app() {
+    let mut state = AnalyticsState::default();
+    let chain = str_to_chain_id("chain1");
+    let app = str_to_app_id("app1");
+    state
+        .upsert_app_config(create_test_app_config(app, chain, "http://a.b".to_string()))
+        .unwrap();
+
+    let owned = format!("{}_volume", app);
+    for key in ["beta", "Zeta", "alpha", owned.as_str()] {
+        state
+            .aggregated_metrics
+            .insert(key.to_string(), MetricValue::Counter(1));
+    }
+    state.metric_definitions.insert(
+        "Gamma".to_string(),
+        MetricDefinition {
+            name: "Gamma".to_string(),
+            description: String::new(),
+            metric_type: MetricType::Gauge,
+            extraction_path: "/gamma".to_string(),
+            aggregation: AggregationMethod::Last,
+        },
+    );
+
+    // Byte-wise key order puts uppercase names first
+    let default_order = state.metric_names(MetricSort::KeyOrder);
+    let position = |names: &[String], name: &str| names.iter().position(|n| n == name).unwrap();
+    assert!(position(&default_order, "Zeta") < position(&default_order, "alpha"));
+
+    let alphabetical = state.metric_names(MetricSort::Alphabetical);
+    assert_ne!(alphabetical, default_order);
+    let lowered: Vec<String> = alphabetical.iter().map(|n| n.to_lowercase()).collect();
+    let mut expected = lowered.clone();
+    expected.sort();
+    assert_eq!(lowered, expected);
+    assert_eq!(alphabetical.len(), 5);
+    assert!(position(&alphabetical, "alpha") < position(&alphabetical, "Zeta"));
+
+    // The app-owned metric leads when grouping by app
+    let by_app = state.metric_names(MetricSort::ByApp);
+    assert_eq!(by_app[0], owned);
+    assert_eq!(&by_app[1..], ["alpha", "beta", "Gamma", "Zeta"]);
+
+    let definitions = state.metric_definitions_sorted(MetricSort::Alphabetical);
+    assert_eq!(definitions.len(), 1);
+    assert_eq!(definitions[0].name, "Gamma");
+}
+
+// **Feature: pine-analytics, Strict mode**
+#[test]
+fn test_strict_mode_fails_whole_batch() {
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+    let batch = || {
+        vec![
+            create_test_event(0, app, chain, 1000, "transfer".to_string(), serde_json::json!({}), "tx_a".to_string()),
+            create_test_event(0, app, chain, 1001, "transfer".to_string(), serde_json::json!({}), "tx_a".to_string()),
+            create_test_event(0, app, chain, 1002, "transfer".to_string(), serde_json::json!({}), "tx_b".to_string()),
+        ]
+    };
+
+    // Lenient: the duplicate is skipped and the rest are stored
+    let mut lenient = AnalyticsState::default();
+    let last_id = lenient.capture_event_batch(batch(), 1000).unwrap();
+    assert_eq!(last_id, Some(1));
+    assert_eq!(lenient.events.len(), 2);
+
+    // Strict: the duplicate aborts the batch and nothing is stored
+    let mut strict = AnalyticsState::default();
+    strict.config.strict_mode = true;
+    let result = strict.capture_event_batch(batch(), 1000);
+    assert!(matches!(
+        result,
+        Err(crate::AnalyticsError::BatchOperationFailed { processed: 1, total: 3 })
+    ));
+    assert!(strict.events.is_empty());
+    assert!(!strict.is_duplicate_tx("tx_a"));
+    assert_eq!(strict.merkle_index.get_root(), None);
+}
+
+// **Feature: pine-analytics, Strict sync ingestion**
+#[test]
+fn test_sync_batch_deduplicates_and_fails_whole_in_strict_mode() {
+    let mut source = AnalyticsState::default();
+    for i in 0..3 {
+        capture_test_event(&mut source, i);
+    }
+    let batches = source.sync_batches(0);
+    assert_eq!(batches.len(), 1);
+    let Some(crate::Message::SyncBatch { events, .. }) = batches.into_iter().next() else {
+        panic!("expected a sync batch");
+    };
+    // The replica already saw one of the transactions through a notification
+    let replica = || {
+        let mut state = AnalyticsState::default();
+        capture_test_event(&mut state, 1);
+        state
+    };
+
+    // Lenient: the event already seen is skipped, and so is a resent batch
+    let mut lenient = replica();
+    lenient.capture_event_batch(events.clone(), 1000).unwrap();
+    lenient.capture_event_batch(events.clone(), 1000).unwrap();
+    assert_eq!(lenient.events.len(), 3);
+
+    // Strict: the duplicate fails the whole batch
+    let mut strict = replica();
+    strict.config.strict_mode = true;
+    assert!(matches!(
+        strict.capture_event_batch(events, 1000),
+        Err(crate::AnalyticsError::BatchOperationFailed { processed: 1, total: 3 })
+    ));
+    assert_eq!(strict.events.len(), 1);
+    assert!(!strict.is_duplicate_tx("tx_0"));
+}
+
+// **Feature: pine-analytics, Verifiable aggregation**
+#[test]
+fn test_verifiable_aggregation_proves_contributing_events() {
+    let mut state = AnalyticsState::default();
+    let chain = str_to_chain_id("chain1");
+    let app1 = str_to_app_id("app1");
+    let app2 = str_to_app_id("app2");
+
+    let mut contributing = Vec::new();
+    for i in 0..3u64 {
+        let event = create_test_event(0, app1, chain, 1000 + i, "swap".to_string(), serde_json::json!({"volume": (i + 1) * 10}), format!("tx_{}", i));
+        contributing.push(state.insert_event(event));
+    }
+    // Outside the time range, another app, and no metric value
+    state.insert_event(create_test_event(0, app1, chain, 9000, "swap".to_string(), serde_json::json!({"volume": 99}), "tx_late".to_string()));
+    state.insert_event(create_test_event(0, app2, chain, 1001, "swap".to_string(), serde_json::json!({"volume": 99}), "tx_other".to_string()));
+    state.insert_event(create_test_event(0, app1, chain, 1002, "ping".to_string(), serde_json::json!({}), "tx_ping".to_string()));
+
+    let query = crate::AggregationQuery {
+        metric: "volume".to_string(),
+        aggregation: crate::AggregationType::Sum,
+        start_time: 1000,
+        end_time: 2000,
+        granularity_ms: 1000,
+        app_filter: Some(vec![app1]),
+    };
+    let verifiable = state.verifiable_aggregation(&query);
+
+    assert_eq!(verifiable.result.value, 60.0);
+    assert_eq!(verifiable.result.sample_count, 3);
+    assert_eq!(verifiable.event_ids, contributing);
+
+    let proof = verifiable.proof.unwrap();
+    assert_eq!(Some(proof.batch_root), state.merkle_index.get_root());
+    let proven: Vec<EventId> = proof.proofs.iter().map(|p| p.event_id).collect();
+    assert_eq!(proven, contributing);
+    for p in &proof.proofs {
+        assert!(crate::MerkleIndex::verify_proof(&proof.batch_root, p));
+    }
+}
+
+// **Feature: pine-analytics, Dedup index reset**
+#[test]
+fn test_reset_dedup_index_keeps_events() {
+    let mut state = AnalyticsState::default();
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+    let event = || create_test_event(0, app, chain, 1000, "transfer".to_string(), serde_json::json!({}), "tx_old".to_string());
+
+    state.capture_event_checked(event(), 1000).unwrap();
+    assert!(matches!(
+        state.capture_event_checked(event(), 1000),
+        Err(crate::AnalyticsError::DuplicateEvent(_))
+    ));
+
+    state
+        .execute_admin_action(crate::AdminOperation::ResetDedupIndex)
+        .unwrap();
+    assert_eq!(state.events.len(), 1);
+    assert!(state.tx_hash_index.is_empty());
+
+    state.capture_event_checked(event(), 1000).unwrap();
+    assert_eq!(state.events.len(), 2);
+}
+
+// **Feature: pine-analytics, Histogram sample cap**
+#[test]
+fn test_histogram_reservoir_stays_bounded() {
+    let mut state = AnalyticsState::default();
+    state.config.max_histogram_samples = 500;
+
+    // Samples arrive in sorted order, the worst case for naive truncation
+    for chunk in (0..100_000u32).collect::<Vec<_>>().chunks(1000) {
+        let samples = chunk.iter().map(|v| *v as f64).collect();
+        state.update_metric("latency".to_string(), MetricValue::Histogram(samples));
+    }
+
+    let Some(MetricValue::Histogram(samples)) = state.aggregated_metrics.get("latency") else {
+        panic!("latency should be a histogram");
+    };
+    assert_eq!(samples.len(), 500);
+    assert_eq!(state.histogram_samples_seen["latency"], 100_000);
+
+    for (p, expected) in [(0.5, 50_000.0), (0.9, 90_000.0)] {
+        let estimate = crate::AggregationEngine::percentile(samples, p);
+        assert!(
+            (estimate - expected).abs() < 7_500.0,
+            "p{} estimate {} too far from {}",
+            p * 100.0,
+            estimate,
+            expected
+        );
+    }
+
+    // Non-histogram values still replace
+    state.update_metric("latency".to_string(), MetricValue::Gauge(1.0));
+    assert!(!state.histogram_samples_seen.contains_key("latency"));
+}
+
+// **Feature: pine-analytics, Deterministic event ordering**
+#[test]
+fn test_events_ordered_by_timestamp_then_id() {
+    let mut state = AnalyticsState::default();
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+
+    // Captured out of timestamp order, with three events sharing 2000
+    for (i, timestamp) in [2000, 1000, 2000, 3000, 2000].into_iter().enumerate() {
+        state.insert_event(create_test_event(0, app, chain, timestamp, "tick".to_string(), serde_json::json!({}), format!("tx_{}", i)));
+    }
+    let expected: Vec<(Timestamp, EventId)> = vec![(1000, 1), (2000, 0), (2000, 2), (2000, 4), (3000, 3)];
+    let keys = |events: &[CapturedEvent]| -> Vec<(Timestamp, EventId)> {
+        events.iter().map(|e| (e.timestamp, e.id)).collect()
+    };
+
+    let all = Pagination { offset: 0, limit: 100 };
+    for _ in 0..3 {
+        assert_eq!(keys(&state.query_events(&EventFilters::default(), &all)), expected);
+    }
+
+    let page = Pagination { offset: 1, limit: 3 };
+    assert_eq!(keys(&state.query_events(&EventFilters::default(), &page)), expected[1..4]);
+
+    let in_range: Vec<CapturedEvent> = state.get_events_in_range(2000, 2000).into_iter().cloned().collect();
+    assert_eq!(keys(&in_range), expected[1..4]);
+    let by_app: Vec<CapturedEvent> = state.get_app_events(&app).into_iter().cloned().collect();
+    assert_eq!(keys(&by_app), expected);
+}
+
+// **Feature: pine-analytics, Event pre-validation**
+#[test]
+fn test_validate_event_reports_problems() {
+    let mut state = AnalyticsState::default();
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+    state
+        .upsert_app_config(create_test_app_config(app, chain, "http://a.b".to_string()))
+        .unwrap();
+
+    let valid = serde_json::to_value(create_test_event(0, app, chain, 1000, "transfer".to_string(), serde_json::json!({"amount": 1}), "tx_1".to_string())).unwrap();
+    let result = state.validate_event(&valid);
+    assert!(result.valid);
+    assert!(result.problems.is_empty());
+
+    assert_eq!(
+        state.validate_event(&serde_json::json!([1, 2])).problems,
+        vec![EventProblem::NotAnObject]
+    );
+
+    let mut missing = valid.clone();
+    let fields = missing.as_object_mut().unwrap();
+    fields.remove("transaction_hash");
+    fields.remove("severity");
+    fields.insert("data".to_string(), serde_json::json!("not an object"));
+    assert_eq!(
+        state.validate_event(&missing).problems,
+        vec![
+            EventProblem::MissingField("transaction_hash".to_string()),
+            EventProblem::MissingField("severity".to_string()),
+            EventProblem::DataNotObject,
+        ]
+    );
+
+    let mut wrong_type = valid.clone();
+    wrong_type["timestamp"] = serde_json::json!("yesterday");
+    assert!(matches!(
+        state.validate_event(&wrong_type).problems.as_slice(),
+        [EventProblem::Malformed(_)]
+    ));
+
+    let other_chain = str_to_chain_id("chain2");
+    let mut event = create_test_event(0, app, other_chain, 1000, String::new(), serde_json::json!({}), String::new());
+    event.parent_event_id = Some(42);
+    let problems = state.validate_event(&serde_json::to_value(event).unwrap()).problems;
+    assert_eq!(
+        problems,
+        vec![
+            EventProblem::EmptyEventType,
+            EventProblem::EmptyTransactionHash,
+            EventProblem::ChainMismatch { expected: chain },
+            EventProblem::UnknownParent(42),
+        ]
+    );
+
+    let unknown_app = create_test_event(0, str_to_app_id("app9"), chain, 1000, "transfer".to_string(), serde_json::json!({}), "tx_9".to_string());
+    assert_eq!(
+        state.validate_event(&serde_json::to_value(unknown_app).unwrap()).problems,
+        vec![EventProblem::UnknownApplication]
+    );
+}
+
+// **Feature: pine-analytics, Direct event values**
+#[test]
+fn test_direct_event_values_aggregate_without_definition() {
+    let mut state = AnalyticsState::default();
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+
+    for (i, value) in [10.0, 20.0, 60.0].into_iter().enumerate() {
+        let mut event = create_test_event(0, app, chain, 1000 + i as u64 * 500, "latency".to_string(), serde_json::json!({}), format!("tx_{}", i));
+        event.value = Some(value);
+        state.insert_event(event);
+    }
+    // Other event types do not feed the metric
+    let mut other = create_test_event(0, app, chain, 1100, "throughput".to_string(), serde_json::json!({}), "tx_other".to_string());
+    other.value = Some(1000.0);
+    state.insert_event(other);
+
+    let query = |aggregation| crate::AggregationQuery {
+        metric: "latency".to_string(),
+        aggregation,
+        start_time: 0,
+        end_time: 10_000,
+        granularity_ms: 1000,
+        app_filter: None,
+    };
+    let sum = state.verifiable_aggregation(&query(crate::AggregationType::Sum)).result;
+    assert_eq!(sum.value, 90.0);
+    assert_eq!(sum.sample_count, 3);
+    let average = state.verifiable_aggregation(&query(crate::AggregationType::Average)).result;
+    assert_eq!(average.value, 30.0);
+
+    let series = state.time_series("latency", &TimeRange::new(1000, 1999), 1000, None);
+    assert_eq!(series.len(), 1);
+    assert!(matches!(series[0].value, MetricValue::Gauge(v) if v == 30.0));
+
+    // Events without a value keep the count-based series
+    let counts = state.time_series("transfer", &TimeRange::new(1000, 1999), 1000, None);
+    assert!(matches!(counts[0].value, MetricValue::Counter(3)));
+
+    // The field is optional on the wire
+    let mut json = serde_json::to_value(&state.events[0]).unwrap();
+    json.as_object_mut().unwrap().remove("value");
+    let decoded: CapturedEvent = serde_json::from_value(json).unwrap();
+    assert_eq!(decoded.value, None);
+}
+
+// **Feature: pine-analytics, Severe event polling**
+#[test]
+fn test_severe_events_since_advances_cursor() {
+    let mut state = AnalyticsState::default();
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+    let capture = |state: &mut AnalyticsState, severity: EventSeverity, tag: &str| {
+        let mut event = create_test_event(0, app, chain, 1000, "alert".to_string(), serde_json::json!({}), format!("tx_{}", tag));
+        event.severity = severity;
+        state.insert_event(event)
+    };
+
+    capture(&mut state, EventSeverity::Info, "a");
+    let error = capture(&mut state, EventSeverity::Error, "b");
+    let critical = capture(&mut state, EventSeverity::Critical, "c");
+    let last = capture(&mut state, EventSeverity::Warning, "d");
+
+    let first = state.severe_events_since(None, &EventSeverity::Error);
+    let ids: Vec<EventId> = first.events.iter().map(|e| e.id).collect();
+    assert_eq!(ids, vec![error, critical]);
+    assert_eq!(first.cursor, Some(last));
+
+    // Nothing new since the last poll
+    let idle = state.severe_events_since(first.cursor, &EventSeverity::Error);
+    assert!(idle.events.is_empty());
+    assert_eq!(idle.cursor, Some(last));
+
+    capture(&mut state, EventSeverity::Debug, "e");
+    let newest = capture(&mut state, EventSeverity::Critical, "f");
+    let next = state.severe_events_since(idle.cursor, &EventSeverity::Error);
+    let ids: Vec<EventId> = next.events.iter().map(|e| e.id).collect();
+    assert_eq!(ids, vec![newest]);
+    assert_eq!(next.cursor, Some(newest));
+}
+
+// **Feature: pine-analytics, Automatic Merkle rebuild**
+#[test]
+fn test_merkle_rebuilds_at_threshold() {
+    let mut state = AnalyticsState::default();
+    state.config.merkle_rebuild_threshold = Some(3);
+
+    for i in 0..2u64 {
+        capture_test_event(&mut state, i);
+    }
+    // Simulate a stale leaf left behind for an event that no longer exists
+    state
+        .merkle_index
+        .insert_hash(99, linera_sdk::linera_base_types::CryptoHash::from([7u8; 32]));
+    assert_eq!(state.merkle_mutations, 2);
+
+    capture_test_event(&mut state, 2);
+    assert_eq!(state.merkle_mutations, 0);
+    assert!(!state.merkle_index.leaves.contains_key(&99));
+
+    let mut fresh = crate::MerkleIndex::new(16);
+    for event in &state.events {
+        fresh.insert_hash(event.id, linera_sdk::linera_base_types::CryptoHash::from(event.data_hash()));
+    }
+    assert_eq!(state.merkle_index.get_root(), fresh.get_root());
+}
+
+// **Feature: pine-analytics, Tag rollups**
+#[test]
+fn test_metrics_by_tag_sums_tagged_apps_only() {
+    let mut state = AnalyticsState::default();
+    let chain = str_to_chain_id("chain1");
+    let apps = [
+        (str_to_app_id("dex"), "defi", 10.0),
+        (str_to_app_id("lending"), "defi", 5.0),
+        (str_to_app_id("gallery"), "nft", 100.0),
+    ];
+    state.metric_definitions.insert(
+        "amount".to_string(),
+        crate::MetricDefinition {
+            name: "amount".to_string(),
+            description: String::new(),
+            metric_type: crate::MetricType::Gauge,
+            extraction_path: "amount".to_string(),
+            aggregation: crate::AggregationMethod::default(),
+        },
+    );
+    for (i, (app, tag, amount)) in apps.iter().enumerate() {
+        let mut config = create_test_app_config(*app, chain, "http://a.b".to_string());
+        config.tags = vec![tag.to_string()];
+        state.upsert_app_config(config).unwrap();
+        for j in 0..2u64 {
+            state.insert_event(create_test_event(0, *app, chain, 1000 + j, "swap".to_string(), serde_json::json!({"amount": amount}), format!("tx_{}_{}", i, j)));
+        }
+    }
+
+    let defi = state.tag_metrics("defi", None);
+    assert_eq!(defi.applications.len(), 2);
+    assert!(!defi.applications.contains(&apps[2].0));
+    assert_eq!(defi.total_events, 4);
+    assert_eq!(defi.events_by_type.get("swap"), Some(&4));
+    assert_eq!(defi.metric_totals.get("amount"), Some(&30.0));
+
+    let nft = state.tag_metrics("nft", Some(&TimeRange::new(1000, 1000)));
+    assert_eq!(nft.total_events, 1);
+    assert_eq!(nft.metric_totals.get("amount"), Some(&100.0));
+}
+
+// **Feature: pine-analytics, Maintenance mode**
+#[test]
+fn test_maintenance_mode_blocks_writes_not_reads() {
+    let mut state = AnalyticsState::default();
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+    let event = create_test_event(0, app, chain, 1000, "transfer".to_string(), serde_json::json!({}), "tx_1".to_string());
+    state.insert_event(event.clone());
+
+    let set_mode = |enabled| crate::Operation::AdminAction {
+        action: crate::AdminOperation::SetMaintenanceMode { enabled },
+    };
+    let capture = crate::Operation::CaptureEvent { event };
+
+    state
+        .execute_admin_action(crate::AdminOperation::SetMaintenanceMode { enabled: true })
+        .unwrap();
+    assert!(matches!(
+        state.check_maintenance(&capture),
+        Err(crate::AnalyticsError::Maintenance)
+    ));
+    assert!(state.check_maintenance(&crate::Operation::PauseIngestion).is_err());
+    assert_eq!(
+        state
+            .query_events(&EventFilters::default(), &Pagination::default())
+            .len(),
+        1
+    );
+
+    assert!(state.check_maintenance(&set_mode(false)).is_ok());
+    state
+        .execute_admin_action(crate::AdminOperation::SetMaintenanceMode { enabled: false })
+        .unwrap();
+    assert!(state.check_maintenance(&capture).is_ok());
+}
+
+// **Feature: pine-analytics, Ingestion lag**
+#[test]
+fn test_ingestion_lag_stats_reflect_delay() {
+    let mut state = AnalyticsState::default();
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+    let now = 100_000;
+
+    for i in 0..20u64 {
+        let event = create_test_event(0, app, chain, now - i * 100, "transfer".to_string(), serde_json::json!({}), format!("tx_{}", i));
+        state.capture_event_checked(event, now).unwrap();
+    }
+    // An event claiming a future time records negative lag
+    let early = create_test_event(0, app, chain, now + 500, "transfer".to_string(), serde_json::json!({}), "tx_future".to_string());
+    let early_id = state.capture_event_checked(early, now).unwrap().unwrap();
+    assert_eq!(state.get_event(early_id).unwrap().ingestion_lag_ms, Some(-500));
+    // Timestamps past the i64 range saturate the lag instead of overflowing
+    let far = create_test_event(0, app, chain, u64::MAX, "transfer".to_string(), serde_json::json!({}), "tx_far".to_string());
+    let far_id = state.capture_event_checked(far, now).unwrap().unwrap();
+    assert_eq!(state.get_event(far_id).unwrap().ingestion_lag_ms, Some(i64::MIN));
+
+    let stats = state.ingestion_lag_stats(&TimeRange::new(0, now));
+    assert_eq!(stats.sample_count, 20);
+    assert_eq!(stats.min_ms, 0.0);
+    assert_eq!(stats.max_ms, 1900.0);
+    assert_eq!(stats.avg_ms, 950.0);
+    assert_eq!(stats.p95_ms, 1800.0);
+
+    assert_eq!(state.ingestion_lag_stats(&TimeRange::new(0, 1)).sample_count, 0);
+}
+
+// **Feature: pine-analytics, Application paging**
+#[test]
+fn test_monitored_applications_paging_and_filters() {
+    let mut state = AnalyticsState::default();
+    let chain = str_to_chain_id("chain1");
+    for i in 0..25 {
+        let mut config = create_test_app_config(str_to_app_id(&format!("app{}", i)), chain, "http://a.b".to_string());
+        config.enabled = i % 5 != 0;
+        if i % 2 == 0 {
+            config.tags = vec!["defi".to_string()];
+        }
+        state.upsert_app_config(config).unwrap();
+    }
+
+    let mut seen = std::collections::BTreeSet::new();
+    let mut offset = 0;
+    loop {
+        let page = state.query_applications(&AppFilters::default(), &Pagination { offset, limit: 7 });
+        assert_eq!(page.total_matching, 25);
+        if page.applications.is_empty() {
+            break;
+        }
+        for config in &page.applications {
+            assert!(seen.insert(config.application_id));
+        }
+        offset += page.applications.len();
+    }
+    assert_eq!(seen.len(), 25);
+
+    let disabled = state.query_applications(
+        &AppFilters { enabled: Some(false), tag: None },
+        &Pagination::default(),
+    );
+    assert_eq!(disabled.total_matching, 5);
+
+    let enabled_defi = state.query_applications(
+        &AppFilters { enabled: Some(true), tag: Some("defi".to_string()) },
+        &Pagination { offset: 0, limit: 3 },
+    );
+    assert_eq!(enabled_defi.total_matching, 10);
+    assert_eq!(enabled_defi.applications.len(), 3);
+    assert!(enabled_defi
+        .applications
+        .iter()
+        .all(|config| config.enabled && config.tags.contains(&"defi".to_string())));
+}
+
+// **Feature: pine-analytics, Orphan events**
+#[test]
+fn test_orphan_events_lack_transaction_records() {
+    let mut state = AnalyticsState::default();
+    let chain = str_to_chain_id("chain1");
+    for i in 0..4u64 {
+        capture_test_event(&mut state, i);
+    }
+    for hash in ["tx_0", "tx_2"] {
+        state
+            .record_transaction(create_test_tx_record(hash.to_string(), chain, 1000))
+            .unwrap();
+    }
+
+    let orphans = state.orphan_events(&TimeRange::new(0, 2000));
+    let hashes: Vec<&str> = orphans.iter().map(|e| e.transaction_hash.as_str()).collect();
+    assert_eq!(hashes, vec!["tx_1", "tx_3"]);
+
+    assert_eq!(state.orphan_events(&TimeRange::new(1003, 2000)).len(), 1);
+}
+
+// **Feature: pine-analytics, Source severity mapping**
+#[test]
+fn test_severity_mapping_translates_source_levels() {
+    let mut config = AnalyticsConfig::default();
+    let mapping = &config.severity_mapping;
+    assert_eq!(mapping.map("3"), EventSeverity::Error);
+    assert_eq!(mapping.map("warn"), EventSeverity::Warning);
+    assert_eq!(mapping.map(" FATAL "), EventSeverity::Critical);
+    assert_eq!(mapping.map("verbose"), EventSeverity::Info);
+
+    // Custom entries override or extend the defaults
+    config
+        .severity_mapping
+        .levels
+        .insert("verbose".to_string(), EventSeverity::Debug);
+    assert_eq!(config.severity_mapping.map("Verbose"), EventSeverity::Debug);
+
+    // Configs stored before the mapping existed get the default table
+    let legacy: AnalyticsConfig = serde_json::from_str(r#"{"strict_mode": true}"#).unwrap();
+    assert_eq!(legacy.severity_mapping, SeverityMapping::default());
+}
+
+// **Feature: pine-analytics, Bulk event proofs**
+#[test]
+fn test_event_proofs_verify_against_returned_root() {
+    let mut state = AnalyticsState::default();
+    for i in 0..6u64 {
+        capture_test_event(&mut state, i);
+    }
+
+    let batch = state.event_proofs(&[0, 2, 5, 42]).unwrap();
+    let root = batch.root.unwrap();
+    let proof = batch.proof.unwrap();
+    assert_eq!(proof.batch_root, root);
+    assert_eq!(proof.proofs.len(), 3);
+    for event_proof in &proof.proofs {
+        assert!(crate::MerkleIndex::verify_proof(&root, event_proof));
+    }
+    assert_eq!(batch.missing, vec![42]);
+
+    let too_many: Vec<EventId> = (0..=crate::MAX_PROOFS_PER_QUERY as u64).collect();
+    assert!(matches!(
+        state.event_proofs(&too_many),
+        Err(crate::AnalyticsError::QueryError(_))
+    ));
+}
+
+// **Feature: pine-analytics, No-data aggregations**
+#[test]
+fn test_aggregation_over_no_metrics_reports_no_data() {
+    let mut state = AnalyticsState::default();
+    state.update_metric("app_volume".to_string(), MetricValue::Gauge(0.0));
+    let query = |metric: &str| crate::AggregationQuery {
+        metric: metric.to_string(),
+        aggregation: crate::AggregationType::Min,
+        start_time: 0,
+        end_time: u64::MAX,
+        granularity_ms: 1000,
+        app_filter: None,
+    };
+
+    let missing = state.aggregate_metric(&query("unknown")).unwrap();
+    assert_eq!(missing.sample_count, 0);
+    assert_eq!(missing.observed_value(), None);
+
+    let zero = state.aggregate_metric(&query("volume")).unwrap();
+    assert_eq!(zero.observed_value(), Some(0.0));
+}
+
+// **Feature: pine-analytics, Event field projection**
+#[test]
+fn test_projected_events_omit_unrequested_fields() {
+    let mut state = AnalyticsState::default();
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+    for i in 0..3u64 {
+        let data = serde_json::json!({"payload": "x".repeat(200), "n": i});
+        state.insert_event(create_test_event(0, app, chain, 1000 + i, "transfer".to_string(), data, format!("tx_{}", i)));
+    }
+    let filters = EventFilters::default();
+    let pagination = Pagination::default();
+    let fields = vec!["id".to_string(), "timestamp".to_string()];
+
+    let projected = state
+        .query_events_projected(&filters, &pagination, &fields)
+        .unwrap();
+    assert_eq!(projected.len(), 3);
+    assert_eq!(projected[1], serde_json::json!({"id": 1, "timestamp": 1001}));
+    assert!(projected.iter().all(|event| event.get("data").is_none()));
+
+    let full = state.query_events(&filters, &pagination);
+    assert!(serde_json::to_vec(&projected).unwrap().len() < serde_json::to_vec(&full).unwrap().len());
+
+    let bad = vec!["id".to_string(), "payload".to_string()];
+    assert!(matches!(
+        state.query_events_projected(&filters, &pagination, &bad),
+        Err(crate::AnalyticsError::QueryError(_))
+    ));
+}
+
+// **Feature: pine-analytics, Anomaly alert events**
+#[test]
+fn test_spike_emits_single_anomaly_event() {
+    let mut state = AnalyticsState::default();
+    state.config.anomaly_alerts = Some(AnomalyAlertConfig {
+        sensitivity: 3.0,
+        error_z_score: 4.0,
+    });
+    state.metric_definitions.insert(
+        "amount".to_string(),
+        crate::MetricDefinition {
+            name: "amount".to_string(),
+            description: String::new(),
+            metric_type: crate::MetricType::Gauge,
+            extraction_path: "amount".to_string(),
+            aggregation: crate::AggregationMethod::default(),
+        },
+    );
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+    let capture = |state: &mut AnalyticsState, i: u64, amount: f64| {
+        let event = create_test_event(0, app, chain, 1000 + i, "transfer".to_string(), serde_json::json!({"amount": amount}), format!("tx_{}", i));
+        state.capture_event_checked(event, 1000 + i).unwrap().unwrap()
+    };
+
+    for i in 0..20 {
+        capture(&mut state, i, 10.0 + (i % 2) as f64);
+    }
+    assert_eq!(state.events.len(), 20);
+
+    let spike_id = capture(&mut state, 20, 1000.0);
+    let alerts: Vec<&CapturedEvent> = state
+        .events
+        .iter()
+        .filter(|e| e.event_type == ANOMALY_EVENT_TYPE)
+        .collect();
+    assert_eq!(alerts.len(), 1);
+    let alert = alerts[0];
+    assert_eq!(alert.parent_event_id, Some(spike_id));
+    let z_score = alert.data["z_score"].as_f64().unwrap();
+    assert!(z_score >= 4.0);
+    assert_eq!(alert.severity, EventSeverity::Error);
+
+    // Without the opt-in no alerts are emitted
+    let mut quiet = AnalyticsState {
+        metric_definitions: state.metric_definitions.clone(),
+        ..AnalyticsState::default()
+    };
+    for i in 0..20 {
+        capture(&mut quiet, i, 10.0 + (i % 2) as f64);
+    }
+    capture(&mut quiet, 20, 1000.0);
+    assert_eq!(quiet.events.len(), 21);
+}
+
+// **Feature: pine-analytics, Heartbeat uptime**
+#[test]
+fn test_app_uptime_from_heartbeats() {
+    let mut state = AnalyticsState::default();
+    let steady = str_to_app_id("steady");
+    let flaky = str_to_app_id("flaky");
+    let silent = str_to_app_id("silent");
+    let chain = str_to_chain_id("chain1");
+    let range = TimeRange::new(0, 10_000);
+
+    for t in (0..=10_000u64).step_by(1000) {
+        state.insert_event(create_test_event(0, steady, chain, t, "heartbeat".to_string(), serde_json::json!({}), format!("tx_s_{}", t)));
+        // The flaky app goes quiet between 2s and 8s
+        if !(3000..8000).contains(&t) {
+            state.insert_event(create_test_event(0, flaky, chain, t, "heartbeat".to_string(), serde_json::json!({}), format!("tx_f_{}", t)));
+        }
+    }
+
+    let up = state.app_uptime(&steady, "heartbeat", 1000, &range).unwrap();
+    assert_eq!(up.heartbeat_count, 11);
+    assert_eq!(up.missed_gaps, 0);
+    assert_eq!(up.availability, 1.0);
+
+    let down = state.app_uptime(&flaky, "heartbeat", 1000, &range).unwrap();
+    assert_eq!(down.missed_gaps, 1);
+    assert_eq!(down.downtime_ms, 5000);
+    assert_eq!(down.availability, 0.5);
+
+    let none = state.app_uptime(&silent, "heartbeat", 1000, &range).unwrap();
+    assert_eq!(none.heartbeat_count, 0);
+    assert_eq!(none.availability, 0.0);
+
+    assert!(state.app_uptime(&steady, "heartbeat", 0, &range).is_err());
+}
+
+// **Feature: pine-analytics, Event type allow list**
+#[test]
+fn test_allowed_event_types_enforced_at_capture() {
+    let mut state = AnalyticsState::default();
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+    let mut config = create_test_app_config(app, chain, "http://a.b".to_string());
+    config.allowed_event_types = Some(vec!["transfer".to_string(), "mint".to_string()]);
+    state.upsert_app_config(config).unwrap();
+
+    let allowed = create_test_event(0, app, chain, 1000, "mint".to_string(), serde_json::json!({}), "tx_1".to_string());
+    assert!(state.capture_event_checked(allowed, 1000).is_ok());
+
+    let disallowed = create_test_event(0, app, chain, 1000, "burn".to_string(), serde_json::json!({}), "tx_2".to_string());
+    assert!(matches!(
+        state.capture_event_checked(disallowed, 1000),
+        Err(crate::AnalyticsError::EventProcessingError(_))
+    ));
+    assert_eq!(state.events.len(), 1);
+
+    // Without an allow list every type is accepted
+    state.monitored_applications.get_mut(&app).unwrap().allowed_event_types = None;
+    let any = create_test_event(0, app, chain, 1000, "burn".to_string(), serde_json::json!({}), "tx_3".to_string());
+    assert!(state.capture_event_checked(any, 1000).is_ok());
+}
+
+// **Feature: pine-analytics, Flatline detection**
+#[test]
+fn test_detect_flatlines_flags_stuck_metric() {
+    let mut state = AnalyticsState::default();
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+    for name in ["queue_depth", "queue_latency"] {
+        state.metric_definitions.insert(
+            name.to_string(),
+            crate::MetricDefinition {
+                name: name.to_string(),
+                description: String::new(),
+                metric_type: crate::MetricType::Gauge,
+                extraction_path: name.to_string(),
+                aggregation: crate::AggregationMethod::default(),
+            },
+        );
+    }
+    for i in 0..20u64 {
+        // Depth varies early on, then sticks at 42
+        let depth = if i < 10 { i as f64 } else { 42.0 };
+        let latency = 100.0 + (i % 3) as f64;
+        let data = serde_json::json!({"queue_depth": depth, "queue_latency": latency});
+        state.insert_event(create_test_event(0, app, chain, 1000 + i, "sample".to_string(), data, format!("tx_{}", i)));
+    }
+    let range = TimeRange::new(0, 5000);
+
+    let flat = state.detect_flatlines("queue", 10, &range, DEFAULT_FLATLINE_THRESHOLD);
+    assert_eq!(flat.len(), 1);
+    assert_eq!(flat[0].metric, "queue_depth");
+    assert_eq!(flat[0].value, 42.0);
+
+    // A wider window reaches back into the varying values
+    assert!(state.detect_flatlines("queue", 12, &range, DEFAULT_FLATLINE_THRESHOLD).is_empty());
+    // Too few samples to judge
+    assert!(state.detect_flatlines("queue", 50, &range, DEFAULT_FLATLINE_THRESHOLD).is_empty());
+}
+
+// **Feature: pine-analytics, Saved queries**
+#[test]
+fn test_saved_query_matches_inline_request() {
+    let mut state = AnalyticsState::default();
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+    for i in 0..5u64 {
+        state.insert_event(create_test_event(0, app, chain, 1000 + i * 100, "transfer".to_string(), serde_json::json!({}), format!("tx_{}", i)));
+    }
+
+    let template = serde_json::json!({
+        "GetEvents": {
+            "filters": {
+                "application_ids": null,
+                "event_types": ["transfer"],
+                "time_range": "$time_range",
+                "severity": null,
+                "search_text": null
+            },
+            "pagination": {"offset": 0, "limit": 10}
+        }
+    });
+    state.save_query("recent_transfers".to_string(), template).unwrap();
+
+    let range = TimeRange::new(1100, 1300);
+    let resolved = state
+        .resolve_saved_query("recent_transfers", Some(&range))
+        .unwrap();
+    let inline_filters = EventFilters {
+        event_types: Some(vec!["transfer".to_string()]),
+        time_range: Some(range.clone()),
+        ..EventFilters::default()
+    };
+    let inline = crate::Request::GetEvents {
+        filters: inline_filters.clone(),
+        pagination: Pagination { offset: 0, limit: 10 },
+        fields: None,
+    };
+    assert_eq!(
+        serde_json::to_value(&resolved).unwrap(),
+        serde_json::to_value(&inline).unwrap()
+    );
+    let crate::Request::GetEvents { filters, pagination, .. } = resolved else {
+        panic!("expected GetEvents");
+    };
+    let saved_ids: Vec<EventId> = state.query_events(&filters, &pagination).iter().map(|e| e.id).collect();
+    assert_eq!(saved_ids, vec![1, 2, 3]);
+
+    // Missing time range, unknown names and invalid templates are errors
+    assert!(state.resolve_saved_query("recent_transfers", None).is_err());
+    assert!(state.resolve_saved_query("missing", Some(&range)).is_err());
+    assert!(state
+        .save_query("bad".to_string(), serde_json::json!({"NoSuchQuery": {}}))
+        .is_err());
+    assert!(state
+        .save_query("loop".to_string(), serde_json::json!({"RunSavedQuery": {"name": "loop"}}))
+        .is_err());
+}
+
+// **Feature: pine-analytics, Event ID range**
+#[test]
+fn test_event_id_range_tracks_stored_events() {
+    let mut state = AnalyticsState::default();
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+    assert_eq!(state.event_id_range(), None);
+
+    for i in 0..4 {
+        capture_test_event(&mut state, i);
+    }
+    assert_eq!(
+        state.event_id_range(),
+        Some(EventIdRange { min_id: 0, max_id: 3, count: 4 })
+    );
+
+    // Pruning everything empties the range; later captures continue the IDs
+    let token = Some(state.clear_events_token());
+    state
+        .execute_admin_action(crate::AdminOperation::ClearEvents { token })
+        .unwrap();
+    assert_eq!(state.event_id_range(), None);
+    for i in 4..6 {
+        capture_test_event(&mut state, i);
+    }
+    assert_eq!(
+        state.event_id_range(),
+        Some(EventIdRange { min_id: 4, max_id: 5, count: 2 })
+    );
+
+    // Imports can store a lower ID after a higher one
+    let event = |id: EventId| {
+        create_test_event(id, app, chain, 2000 + id, "transfer".to_string(), serde_json::json!({}), format!("import_{}", id))
+    };
+    for id in [50, 20] {
+        state.import_events(vec![event(id)], ImportConflictPolicy::Fail).unwrap();
+    }
+    assert_eq!(
+        state.event_id_range(),
+        Some(EventIdRange { min_id: 4, max_id: 50, count: 4 })
+    );
+}
+
+// **Feature: pine-analytics, Mixed metric types**
+#[test]
+fn test_mixed_metric_types_are_flagged_or_rejected() {
+    let mut state = AnalyticsState::default();
+    state.update_metric("app1_requests".to_string(), MetricValue::Counter(10));
+    state.update_metric("app2_requests".to_string(), MetricValue::Gauge(4.0));
+    state.update_metric("app1_latency".to_string(), MetricValue::Gauge(2.0));
+    let query = |metric: &str| crate::AggregationQuery {
+        metric: metric.to_string(),
+        aggregation: crate::AggregationType::Sum,
+        start_time: 0,
+        end_time: u64::MAX,
+        granularity_ms: 1000,
+        app_filter: None,
+    };
+
+    // Coercion is the default: counters count, gauges keep their value
+    let coerced = state.aggregate_metric(&query("requests")).unwrap();
+    assert!(coerced.mixed_types);
+    assert_eq!(coerced.value, 14.0);
+    assert!(!state.aggregate_metric(&query("latency")).unwrap().mixed_types);
+
+    state.config.mixed_metric_types = crate::state::MixedMetricPolicy::Reject;
+    assert!(matches!(
+        state.aggregate_metric(&query("requests")),
+        Err(crate::AnalyticsError::QueryError(_))
+    ));
+    assert_eq!(state.aggregate_metric(&query("latency")).unwrap().value, 2.0);
+}
+
+// **Feature: pine-analytics, ClearEvents confirmation**
+#[test]
+fn test_clear_events_requires_current_token() {
+    let mut state = AnalyticsState::default();
+    let clear = |state: &mut AnalyticsState, token: Option<String>| {
+        state.check_clear_events_token(token.as_deref())?;
+        state.execute_admin_action(crate::AdminOperation::ClearEvents { token })
+    };
+    for i in 0..3 {
+        capture_test_event(&mut state, i);
+    }
+
+    assert!(matches!(
+        clear(&mut state, None),
+        Err(crate::AnalyticsError::InvalidOperation(_))
+    ));
+    let stale = state.clear_events_token();
+    capture_test_event(&mut state, 3);
+    assert!(clear(&mut state, Some(stale)).is_err());
+    assert_eq!(state.events.len(), 4);
+
+    let token = state.clear_events_token();
+    clear(&mut state, Some(token)).unwrap();
+    assert!(state.events.is_empty());
+}
+
+// **Feature: pine-analytics, ClearEvents confirmation**
+#[test]
+fn test_clear_events_replicates_without_token() {
+    use crate::{AdminOperation, Operation, OperationResponse};
+
+    let admin = Owner::Address20([1; 20]);
+    let source = str_to_chain_id("primary");
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+    let mut primary = AnalyticsState::new(admin);
+    let mut standby = AnalyticsState::new(admin);
+    standby.replication_source = Some(source);
+    // Events sync separately, so the standby's store differs
+    for i in 0..3 {
+        capture_test_event(&mut primary, i);
+    }
+    capture_test_event(&mut standby, 0);
+
+    let token = primary.clear_events_token();
+    assert!(standby.check_clear_events_token(Some(&token)).is_err());
+    primary.check_clear_events_token(Some(&token)).unwrap();
+    let clear = Operation::AdminAction { action: AdminOperation::ClearEvents { token: Some(token) } };
+    primary.execute_admin_action(AdminOperation::ClearEvents { token: None }).unwrap();
+    let success = OperationResponse { success: true, event_id: None, error: None, results: Vec::new() };
+    primary.log_operation(admin, clear.logged_part(&success).unwrap());
+    primary.log_operation(admin, Operation::AddMonitoredApp {
+        application_id: app,
+        chain_id: chain,
+        graphql_endpoint: "http://a.b".to_string(),
+    });
+    assert!(matches!(
+        &primary.operation_log[0].operation,
+        Operation::AdminAction { action: AdminOperation::ClearEvents { token: None } }
+    ));
+
+    // The standby clears its own store and carries on with later entries
+    assert_eq!(standby.apply_replicated_log(source, primary.operation_log_since(0)).unwrap(), 2);
+    assert!(standby.events.is_empty());
+    assert!(standby.merkle_index.leaves.is_empty());
+    assert!(standby.monitored_applications.contains_key(&app));
+}
+
+// **Feature: pine-analytics, Metric snapshots**
+#[test]
+fn test_all_metrics_snapshot_respects_filter() {
+    let mut state = AnalyticsState::default();
+    let chain = str_to_chain_id("chain1");
+    let dex = str_to_app_id("dex");
+    let gallery = str_to_app_id("gallery");
+    for (app, tag) in [(dex, "defi"), (gallery, "nft")] {
+        let mut config = create_test_app_config(app, chain, "http://a.b".to_string());
+        config.tags = vec![tag.to_string()];
+        state.upsert_app_config(config).unwrap();
+    }
+    state.update_metric(format!("{}_volume", dex), MetricValue::Counter(7));
+    state.update_metric(format!("{}_latency", dex), MetricValue::Gauge(1.5));
+    state.update_metric(format!("{}_volume", gallery), MetricValue::Histogram(vec![1.0, 3.0]));
+
+    let all = state.all_metrics(None);
+    assert_eq!(all.len(), 3);
+    let gallery_volume = &all[&format!("{}_volume", gallery)];
+    assert_eq!(gallery_volume.numeric, 2.0);
+    assert_eq!(gallery_volume.metric_type, crate::MetricType::Histogram);
+
+    let by_app = crate::MetricFilter { application_id: Some(dex), ..Default::default() };
+    assert_eq!(state.all_metrics(Some(&by_app)).len(), 2);
+    let by_name = crate::MetricFilter { name: Some("volume".to_string()), ..Default::default() };
+    assert_eq!(state.all_metrics(Some(&by_name)).len(), 2);
+    let by_tag = crate::MetricFilter { tag: Some("nft".to_string()), ..Default::default() };
+    let nft = state.all_metrics(Some(&by_tag));
+    assert_eq!(nft.keys().collect::<Vec<_>>(), vec![&format!("{}_volume", gallery)]);
+}
+
+// **Feature: pine-analytics, Priority eviction**
+#[test]
+fn test_capacity_evicts_low_priority_events_first() {
+    let mut state = AnalyticsState::default();
+    state.config.max_total_events = Some(4);
+    let chain = str_to_chain_id("chain1");
+    let low = str_to_app_id("low");
+    let high = str_to_app_id("high");
+    for (app, priority) in [(low, 1), (high, 9)] {
+        let mut config = create_test_app_config(app, chain, "http://a.b".to_string());
+        config.priority = priority;
+        state.upsert_app_config(config).unwrap();
+    }
+    for i in 0..2u64 {
+        state.insert_event(create_test_event(0, high, chain, 1000 + i, "t".to_string(), serde_json::json!({}), format!("high_{}", i)));
+        state.insert_event(create_test_event(0, low, chain, 1000 + i, "t".to_string(), serde_json::json!({}), format!("low_{}", i)));
+    }
+    state.insert_event(create_test_event(0, high, chain, 2000, "t".to_string(), serde_json::json!({}), "high_new".to_string()));
+
+    // The oldest low-priority event makes room; the equally old
+    // high-priority event stays
+    assert_eq!(state.events.len(), 4);
+    let kept: Vec<&str> = state.events.iter().map(|e| e.transaction_hash.as_str()).collect();
+    assert_eq!(kept, vec!["high_0", "high_1", "low_1", "high_new"]);
+    assert_eq!(state.get_app_events(&low).len(), 1);
+    assert_eq!(state.get_events_in_range(1000, 1000).len(), 1);
+
+    // The Merkle index covers exactly the retained events
+    let root = state.merkle_index.get_root().unwrap();
+    for event in &state.events {
+        let proof = state.merkle_index.generate_proof(event.id).unwrap();
+        assert!(crate::MerkleIndex::verify_proof(&root, &proof));
+    }
+    assert!(state.merkle_index.generate_proof(1).is_none());
+}
+
+// **Feature: pine-analytics, Priority eviction**
+#[test]
+fn test_capacity_evicts_in_batches() {
+    let mut state = AnalyticsState::default();
+    state.config.max_total_events = Some(20);
+    for i in 0..20 {
+        capture_test_event(&mut state, i);
+    }
+    assert_eq!(state.events.len(), 20);
+
+    // Going over capacity frees a tenth of it, oldest first
+    capture_test_event(&mut state, 20);
+    assert_eq!(state.events.len(), 18);
+    assert_eq!(state.events[0].transaction_hash, "tx_3");
+    assert_eq!(state.merkle_index.leaves.len(), 18);
+
+    // The next captures fit without evicting
+    capture_test_event(&mut state, 21);
+    capture_test_event(&mut state, 22);
+    assert_eq!(state.events.len(), 20);
+    assert_eq!(state.events[0].transaction_hash, "tx_3");
+}
+
+// **Feature: pine-analytics, Checkpoint deltas**
+#[test]
+fn test_checkpoint_delta_lists_events_added_between() {
+    let mut state = AnalyticsState::default();
+    capture_test_event(&mut state, 0);
+    state.execute_admin_action(crate::AdminOperation::CreateCheckpoint).unwrap();
+    let added: Vec<_> = (1..4).map(|i| capture_test_event(&mut state, i)).collect();
+    state.execute_admin_action(crate::AdminOperation::CreateCheckpoint).unwrap();
+
+    let delta = state.checkpoint_delta(0, 1).unwrap();
+    assert_eq!(delta.added, 3);
+    assert_eq!(delta.event_ids, added);
+    assert_eq!(state.checkpoint_delta(1, 1).unwrap().added, 0);
+    assert_eq!(state.checkpoints[1].root, state.merkle_index.get_root());
+    assert!(matches!(
+        state.checkpoint_delta(0, 2),
+        Err(crate::AnalyticsError::QueryError(_))
+    ));
+}
+
+// **Feature: pine-analytics, Event ID strategies**
+#[test]
+fn test_content_hash_ids_match_across_instances() {
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+    let event = |tx: &str, amount: u64| {
+        create_test_event(0, app, chain, 1000, "transfer".to_string(), serde_json::json!({"amount": amount}), tx.to_string())
+    };
+    let hashed = || {
+        let mut state = AnalyticsState::default();
+        state.config.event_id_strategy = crate::EventIdStrategy::ContentHash;
+        state
+    };
+
+    let mut first = hashed();
+    let mut second = hashed();
+    second.insert_event(event("tx_other", 1));
+    let id = first.insert_event(event("tx_a", 5));
+    assert_eq!(second.insert_event(event("tx_a", 5)), id);
+    assert_eq!(id, event("tx_a", 5).content_id());
+    assert_ne!(first.insert_event(event("tx_a", 6)), id);
+
+    // A colliding ID steps to the next free one
+    let repeat = first.insert_event(event("tx_a", 5));
+    assert_eq!(repeat, id.wrapping_add(1));
+    assert_eq!(first.merkle_index.leaves.len(), 3);
+    let range = first.event_id_range().unwrap();
+    assert_eq!(range.count, 3);
+    assert!(range.min_id <= id && id <= range.max_id);
+
+    let mut sequential = AnalyticsState::default();
+    assert_eq!(sequential.insert_event(event("tx_a", 5)), 0);
+    assert_eq!(sequential.insert_event(event("tx_a", 5)), 1);
+
+    // Switching back to sequential IDs skips those already taken
+    let mut mixed = hashed();
+    let hashed_id = mixed.insert_event(event("tx_a", 5));
+    mixed.config.event_id_strategy = crate::EventIdStrategy::Sequential;
+    mixed.next_event_id = hashed_id;
+    let next = mixed.insert_event(event("tx_b", 5));
+    assert_eq!(next, hashed_id + 1);
+    assert_eq!(mixed.get_event(hashed_id).unwrap().transaction_hash, "tx_a");
+    assert_eq!(mixed.merkle_index.leaves.len(), 2);
+    assert_eq!(mixed.events.len(), 2);
+}
+
+// **Feature: pine-analytics, Degraded reads**
+#[test]
+fn test_partial_load_degrades_queries_gracefully() {
+    let mut state = AnalyticsState::default();
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+    state.upsert_app_config(create_test_app_config(app, chain, "http://a.b".to_string())).unwrap();
+    state.insert_event(create_test_event(0, app, chain, 1000, "transfer".to_string(), serde_json::json!({}), "tx_0".to_string()));
+    state.update_metric("app1_volume".to_string(), MetricValue::Counter(3));
+
+    let mut parts = state.snapshot_parts().unwrap();
+    let reloaded = AnalyticsState::load_partial(&parts);
+    assert!(reloaded.unloaded_parts.is_empty());
+    assert_eq!(reloaded.events.len(), 1);
+
+    // A missing sub-state and a corrupt one both fall back to defaults
+    parts.remove("aggregated_metrics");
+    parts.insert("events".to_string(), vec![0xff, 0x00]);
+    let loaded = AnalyticsState::load_partial(&parts);
+    assert_eq!(loaded.unloaded_parts, vec!["events".to_string(), "aggregated_metrics".to_string()]);
+
+    assert!(loaded.events.is_empty());
+    assert!(loaded.all_metrics(None).is_empty());
+    assert_eq!(loaded.monitored_applications.len(), 1);
+    assert_eq!(loaded.total_events_captured, 1);
+    assert!(loaded.query_events(&EventFilters::default(), &Pagination::default()).is_empty());
+}
+
+// **Feature: pine-analytics, Source chains**
+#[test]
+fn test_source_chains_sorted_by_event_count() {
+    let mut state = AnalyticsState::default();
+    let app = str_to_app_id("app1");
+    let chains = [
+        (str_to_chain_id("chain_a"), 1),
+        (str_to_chain_id("chain_b"), 3),
+        (str_to_chain_id("chain_c"), 2),
+    ];
+    for (chain, count) in chains {
+        for i in 0..count {
+            state.insert_event(create_test_event(0, app, chain, 1000 + i, "transfer".to_string(), serde_json::json!({}), format!("tx_{}_{}", chain, i)));
+        }
+    }
+
+    let sources = state.source_chains();
+    let summary: Vec<_> = sources.iter().map(|s| (s.chain_id, s.event_count)).collect();
+    assert_eq!(summary, vec![(chains[1].0, 3), (chains[2].0, 2), (chains[0].0, 1)]);
+}
+
+// **Feature: pine-analytics, Stale root tolerance**
+#[test]
+fn test_proof_verifies_against_checkpointed_stale_root() {
+    let mut state = AnalyticsState::default();
+    state.config.proof_checkpoint_tolerance = 2;
+    let id = capture_test_event(&mut state, 0);
+    capture_test_event(&mut state, 1);
+    state.execute_admin_action(crate::AdminOperation::CreateCheckpoint).unwrap();
+    let stale_root = state.merkle_index.get_root().unwrap();
+    let stale_proof = state.merkle_index.generate_proof(id).unwrap();
+    capture_test_event(&mut state, 2);
+    assert_ne!(state.merkle_index.get_root(), Some(stale_root));
+
+    let checked = state.verify_proof_with_tolerance(&stale_proof, &stale_root);
+    assert!(checked.valid);
+    assert_eq!(checked.matched, Some(crate::MatchedRoot::Checkpoint(0)));
+
+    let current_root = state.merkle_index.get_root().unwrap();
+    let current_proof = state.merkle_index.generate_proof(id).unwrap();
+    let current = state.verify_proof_with_tolerance(&current_proof, &current_root);
+    assert_eq!(current.matched, Some(crate::MatchedRoot::Current));
+    assert!(current.valid);
+
+    let unknown = state.verify_proof_with_tolerance(&stale_proof, &linera_sdk::linera_base_types::CryptoHash::from([7u8; 32]));
+    assert_eq!(unknown, crate::ToleratedProofVerification { valid: false, matched: None });
+
+    // Outside the tolerance the checkpoint no longer counts
+    state.config.proof_checkpoint_tolerance = 0;
+    assert!(!state.verify_proof_with_tolerance(&stale_proof, &stale_root).valid);
+}
+
+// **Feature: pine-analytics, Operation batches**
+#[test]
+fn test_mixed_batch_logs_and_replicates_successful_entries() {
+    use crate::{Operation, OperationLogEntry, OperationResponse, Role};
+
+    let admin = Owner::Address20([1; 20]);
+    let operator = Owner::Address20([2; 20]);
+    let app = str_to_app_id("app1");
+    let ok = || OperationResponse { success: true, ..Default::default() };
+    let failed = || OperationResponse { success: false, error: Some("Unauthorized".to_string()), ..Default::default() };
+    let batch = Operation::Batch {
+        operations: vec![
+            Operation::AddMonitoredApp {
+                application_id: app,
+                chain_id: str_to_chain_id("chain1"),
+                graphql_endpoint: "http://a.b".to_string(),
+            },
+            Operation::DefineMetric {
+                definition: crate::MetricDefinition {
+                    name: "volume".to_string(),
+                    description: String::new(),
+                    metric_type: crate::MetricType::Counter,
+                    extraction_path: "amount".to_string(),
+                    aggregation: crate::AggregationMethod::default(),
+                },
+            },
+            Operation::AssignRole { target: operator, role: Role::Operator },
+            Operation::PauseIngestion,
+        ],
+        stop_on_error: false,
+    };
+
+    // One failed entry fails the batch but keeps the others' results
+    let response = OperationResponse::from_batch(vec![ok(), ok(), ok(), failed()]);
+    assert!(!response.success);
+    assert_eq!(response.error.as_deref(), Some("1 of 4 operations failed"));
+    let successes: Vec<bool> = response.results.iter().map(|r| r.success).collect();
+    assert_eq!(successes, vec![true, true, true, false]);
+
+    // Only the applied entries are logged and replicated
+    let logged = batch.logged_part(&response).unwrap();
+    let Operation::Batch { operations, .. } = &logged else {
+        panic!("expected a batch");
+    };
+    assert_eq!(operations.len(), 3);
+    let mut standby = AnalyticsState::new(admin);
+    let source = str_to_chain_id("primary");
+    standby.replication_source = Some(source);
+    standby
+        .apply_replicated_log(source, vec![OperationLogEntry { index: 0, block_height: 0, caller: admin, operation: logged }])
+        .unwrap();
+    assert!(standby.monitored_applications.contains_key(&app));
+    assert!(standby.metric_definitions.contains_key("volume"));
+    assert_eq!(standby.rbac.get_role(&operator), Role::Operator);
+    assert!(!standby.rate_limiter.paused);
+
+    let empty = Operation::Batch { operations: vec![], stop_on_error: false };
+    let nested = Operation::Batch { operations: vec![empty], stop_on_error: false };
+    assert!(standby
+        .apply_replicated_log(source, vec![OperationLogEntry { index: 1, block_height: 0, caller: admin, operation: nested }])
+        .is_err());
+}
+
+// **Feature: pine-analytics, Batch stop-on-error**
+#[test]
+fn test_batch_stop_on_error_halts_after_failure() {
+    use crate::{BatchRun, Operation, OperationResponse};
+
+    let operations = || {
+        vec![
+            Operation::PauseIngestion,
+            Operation::ResumeIngestion,
+            Operation::ClearRateLimitOverrides,
+        ]
+    };
+    // The second operation fails, the others succeed
+    let run_batch = |stop_on_error: bool| {
+        let mut run = BatchRun::new(operations(), stop_on_error);
+        let mut dispatched = 0;
+        while let Some(operation) = run.next_operation() {
+            dispatched += 1;
+            let success = !matches!(operation, Operation::ResumeIngestion);
+            run.record(OperationResponse {
+                success,
+                error: (!success).then(|| "Unauthorized".to_string()),
+                ..Default::default()
+            });
+        }
+        (dispatched, run.finish())
+    };
+
+    let (dispatched, stopped) = run_batch(true);
+    assert_eq!(dispatched, 2);
+    assert!(!stopped.success);
+    assert_eq!(stopped.results.len(), 2);
+    assert_eq!(stopped.error.as_deref(), Some("Stopped at operation 1 of 3: Unauthorized"));
+
+    let (dispatched, best_effort) = run_batch(false);
+    assert_eq!(dispatched, 3);
+    assert_eq!(best_effort.results.len(), 3);
+    assert!(best_effort.results[2].success);
+    assert_eq!(best_effort.error.as_deref(), Some("1 of 3 operations failed"));
+
+    // Nested batches fail without being dispatched
+    let nested = Operation::Batch { operations: vec![], stop_on_error: false };
+    let mut run = BatchRun::new(vec![nested, Operation::PauseIngestion], true);
+    assert!(run.next_operation().is_none());
+    assert_eq!(run.finish().results.len(), 1);
+}
+
+// **Feature: pine-analytics, Per-app time zones**
+#[test]
+fn test_daily_buckets_follow_app_utc_offset() {
+    const DAY: u64 = 86_400_000;
+    const OFFSET: u64 = 19_800_000; // +5:30
+    let mut state = AnalyticsState::default();
+    let chain = str_to_chain_id("chain1");
+    let app = str_to_app_id("mumbai");
+    let other = str_to_app_id("other");
+    let mut config = create_test_app_config(app, chain, "http://a.b".to_string());
+    config.utc_offset_minutes = Some(330);
+    state.upsert_app_config(config).unwrap();
+
+    // Local midnight of day 10 is 18:30 UTC on day 9
+    let local_midnight = 10 * DAY - OFFSET;
+    for (i, timestamp) in [local_midnight - 1, local_midnight, local_midnight + DAY - 1, local_midnight + DAY]
+        .into_iter()
+        .enumerate()
+    {
+        state.insert_event(create_test_event(0, app, chain, timestamp, "transfer".to_string(), serde_json::json!({}), format!("tx_{}", i)));
+    }
+    state.insert_event(create_test_event(0, other, chain, local_midnight, "transfer".to_string(), serde_json::json!({}), "tx_other".to_string()));
+
+    let range = TimeRange::new(9 * DAY, 11 * DAY);
+    let series = state.time_series("transfer", &range, DAY, Some(&app));
+    let starts: Vec<u64> = series.iter().map(|point| point.timestamp).collect();
+    assert_eq!(starts, vec![local_midnight - DAY, local_midnight, local_midnight + DAY]);
+    let counts: Vec<MetricValue> = series.into_iter().map(|point| point.value).collect();
+    assert_eq!(
+        counts,
+        vec![MetricValue::Counter(1), MetricValue::Counter(2), MetricValue::Counter(1)]
+    );
+
+    // Without an offset buckets fall on UTC midnight
+    state.monitored_applications.get_mut(&app).unwrap().utc_offset_minutes = None;
+    let utc = state.time_series("transfer", &range, DAY, Some(&app));
+    assert_eq!(utc[0].timestamp, 9 * DAY);
+    assert_eq!(utc[0].value, MetricValue::Counter(2));
+}
+
+// **Feature: pine-analytics, Historical Merkle roots**
+#[test]
+fn test_proof_verifies_against_root_at_its_block() {
+    let mut state = AnalyticsState::default();
+    let chain = str_to_chain_id("chain1");
+    let app = str_to_app_id("app1");
+
+    state.set_block_height(1);
+    let id = state
+        .insert_event(create_test_event(0, app, chain, 1000, "transfer".to_string(), serde_json::json!({}), "tx_1".to_string()));
+    let proof = state.merkle_index.generate_proof(id).unwrap();
+    let block_one_root = state.merkle_index.get_root().unwrap();
+    assert_eq!(state.merkle_root_at(1), Some(block_one_root));
+
+    state.set_block_height(2);
+    state.insert_event(create_test_event(0, app, chain, 2000, "transfer".to_string(), serde_json::json!({}), "tx_2".to_string()));
+    state.set_block_height(5);
+
+    let current_root = state.merkle_index.get_root().unwrap();
+    assert_ne!(current_root, block_one_root);
+    assert!(!crate::MerkleIndex::verify_proof(&current_root, &proof));
+
+    let historical = state.merkle_root_at(1).unwrap();
+    assert_eq!(historical, block_one_root);
+    assert!(crate::MerkleIndex::verify_proof(&historical, &proof));
+
+    // Blocks without a snapshot of their own report the latest earlier root
+    assert_eq!(state.merkle_root_at(4), Some(current_root));
+    assert_eq!(state.merkle_root_at(0), None);
+    assert_eq!(state.merkle_root_at(9), Some(current_root));
+
+    // Blocks that leave the root unchanged add no snapshot
+    assert_eq!(state.merkle_index.root_history.len(), 2);
+    for block in 6..50 {
+        state.set_block_height(block);
+    }
+    assert_eq!(state.merkle_index.root_history.len(), 2);
+    assert_eq!(state.merkle_root_at(30), Some(current_root));
+
+    // Roots past the retention window are dropped, keeping the one still
+    // in effect at its start
+    state.config.root_history_blocks = Some(40);
+    state.insert_event(create_test_event(0, app, chain, 3000, "transfer".to_string(), serde_json::json!({}), "tx_3".to_string()));
+    let latest_root = state.merkle_index.get_root().unwrap();
+    state.set_block_height(60);
+    assert_eq!(state.merkle_index.root_history.keys().copied().collect::<Vec<_>>(), vec![2, 49]);
+    assert_eq!(state.merkle_root_at(1), None);
+    assert_eq!(state.merkle_root_at(20), Some(current_root));
+    assert_eq!(state.merkle_root_at(59), Some(latest_root));
+}
+
+// **Feature: pine-analytics, Metrics changed since a block**
+#[test]
+fn test_metrics_changed_since_block() {
+    let mut state = AnalyticsState::default();
+    state.set_block_height(1);
+    state.update_metric("volume".to_string(), MetricValue::Counter(1));
+    state.update_metric("latency".to_string(), MetricValue::Gauge(2.0));
+    state.update_metric("sizes".to_string(), MetricValue::Histogram(vec![1.0]));
+
+    state.set_block_height(4);
+    state.update_metric("volume".to_string(), MetricValue::Counter(5));
+    state.set_block_height(6);
+    state.update_metric("sizes".to_string(), MetricValue::Histogram(vec![2.0]));
+
+    let changed = state.metrics_changed_since(4);
+    assert_eq!(changed.keys().collect::<Vec<_>>(), vec!["sizes", "volume"]);
+    assert_eq!(changed["volume"].value, MetricValue::Counter(5));
+    assert_eq!(changed["sizes"].value, MetricValue::Histogram(vec![1.0, 2.0]));
+
+    assert_eq!(state.metrics_changed_since(5).keys().collect::<Vec<_>>(), vec!["sizes"]);
+    assert_eq!(state.metrics_changed_since(0).len(), 3);
+    assert!(state.metrics_changed_since(7).is_empty());
+}
+
+// **Feature: pine-analytics, Canonical event data**
+#[test]
+fn test_event_data_stored_canonically() {
+    let chain = str_to_chain_id("chain1");
+    let app = str_to_app_id("app1");
+    let orderings = [
+        r#"{"b": {"d": 3, "c": [ {"y": 1, "x": 2} ]}, "a": 1}"#,
+        r#"{"a":1,"b":{"c":[{"x":2,"y":1}],"d":3}}"#,
+    ];
+
+    let stored: Vec<(String, [u8; 32])> = orderings
+        .iter()
+        .map(|raw| {
+            let mut state = AnalyticsState::default();
+            let data: serde_json::Value = serde_json::from_str(raw).unwrap();
+            let id = state.insert_event(create_test_event(0, app, chain, 1000, "transfer".to_string(), data, "tx_1".to_string()));
+            let event = state.get_event(id).unwrap();
+            (event.data.to_string(), event.data_hash())
+        })
+        .collect();
+
+    assert_eq!(stored[0].0, r#"{"a":1,"b":{"c":[{"x":2,"y":1}],"d":3}}"#);
+    assert_eq!(stored[0], stored[1]);
+}
+
+// **Feature: pine-analytics, Caller authorization**
+#[test]
+fn test_viewer_caller_rejected_from_capture_event() {
+    use crate::{Operation, RBACError, Role};
+
+    let mut state = AnalyticsState::default();
+    let viewer = Owner::Address20([1; 20]);
+    let ingester = Owner::Address20([2; 20]);
+    state.rbac.assign_role(viewer, Role::Viewer).unwrap();
+    state.rbac.assign_role(ingester, Role::DataIngester).unwrap();
+
+    let capture = Operation::CaptureEvent {
+        event: create_test_event(0, str_to_app_id("app1"), str_to_chain_id("chain1"), 1000, "transfer".to_string(), serde_json::json!({}), "tx_1".to_string()),
+    };
+    assert!(matches!(
+        state.rbac.authorize(&viewer, &capture),
+        Err(RBACError::InsufficientPermissions)
+    ));
+    assert!(state.rbac.authorize(&ingester, &capture).is_ok());
+    assert!(state.rbac.authorize(&state.admin_owner, &capture).is_ok());
+
+    // A batch is authorized operation by operation
+    let batch = Operation::Batch { operations: vec![capture], stop_on_error: false };
+    assert!(state.rbac.authorize(&viewer, &batch).is_ok());
+}
+
+// **Feature: pine-analytics, Pinned events**
+#[test]
+fn test_pinned_event_survives_eviction() {
+    let mut state = AnalyticsState::default();
+    let chain = str_to_chain_id("chain1");
+    let app = str_to_app_id("app1");
+    let governance = capture_test_event(&mut state, 0);
+    let old_peer = capture_test_event(&mut state, 1);
+    let leaf_before = state.merkle_index.leaves[&governance];
+    state.pin_event(governance, true).unwrap();
+    assert!(state.get_event(governance).unwrap().pinned);
+    assert_eq!(linera_sdk::linera_base_types::CryptoHash::from(state.get_event(governance).unwrap().data_hash()), leaf_before);
+    assert!(matches!(state.pin_event(99, true), Err(crate::AnalyticsError::EventNotFound(99))));
+
+    state.config.max_total_events = Some(2);
+    let recent = capture_test_event(&mut state, 1000);
+    let newest = capture_test_event(&mut state, 2000);
+
+    let ids: Vec<EventId> = state.events.iter().map(|event| event.id).collect();
+    assert_eq!(ids, vec![governance, newest]);
+    assert!(state.get_event(old_peer).is_none());
+    assert!(state.get_event(recent).is_none());
+    assert!(state.merkle_index.leaves.contains_key(&governance));
+
+    // Captured events cannot pin themselves
+    let mut self_pinned = create_test_event(0, app, chain, 4000, "vote".to_string(), serde_json::json!({}), "tx_self".to_string());
+    self_pinned.pinned = true;
+    let id = state.insert_event(self_pinned);
+    assert!(!state.get_event(id).unwrap().pinned);
+    assert!(state.get_event(newest).is_none());
+}
+
+// **Feature: pine-analytics, Persistence across blocks**
+#[test]
+fn test_captured_event_visible_after_reload() {
+    assert!(AnalyticsState::from_parts(&BTreeMap::new()).unloaded_parts.is_empty());
+
+    let mut state = AnalyticsState::default();
+    let chain = str_to_chain_id("chain1");
+    let app = str_to_app_id("app1");
+    state.set_block_height(3);
+    let id = state.insert_event(create_test_event(0, app, chain, 1000, "transfer".to_string(), serde_json::json!({"amount": 5}), "tx_1".to_string()));
+    state.update_metric("volume".to_string(), MetricValue::Counter(5));
+
+    // What `store` writes is what the next block's `load` reads
+    let stored = state.snapshot_parts().unwrap();
+    let reloaded = AnalyticsState::from_parts(&stored);
+    assert!(reloaded.unloaded_parts.is_empty());
+    let event = reloaded.get_event(id).unwrap();
+    assert_eq!(event.data, serde_json::json!({"amount": 5}));
+    assert_eq!(reloaded.get_app_events(&app).len(), 1);
+    assert_eq!(reloaded.merkle_index.get_root(), state.merkle_index.get_root());
+    assert_eq!(reloaded.aggregated_metrics.get("volume"), Some(&MetricValue::Counter(5)));
+    assert_eq!(reloaded.current_block, 3);
+    assert!(reloaded.tx_hash_index.contains("tx_1"));
+}
+
+// **Feature: pine-analytics, Persistence across blocks**
+#[test]
+fn test_state_persisted_in_views_across_blocks() {
+    use crate::{AnalyticsStorage, AnalyticsView, Operation, Owner};
+    use linera_sdk::views::linera_views::context::MemoryContext;
+    use linera_sdk::views::{RootView, View};
+
+    tokio_test::block_on(async {
+        let context = MemoryContext::new_for_testing(());
+        let chain = str_to_chain_id("chain1");
+        let app = str_to_app_id("app1");
+        let admin = Owner::Address20([1; 20]);
+        let mut storage = AnalyticsStorage::load(context.clone()).await.unwrap();
+        let mut state = storage.read_state().await.unwrap();
+        assert!(state.events.is_empty());
+        state.set_block_height(1);
+        let first = capture_test_event(&mut state, 0);
+        let second = capture_test_event(&mut state, 1000);
+        state.update_metric("volume".to_string(), MetricValue::Counter(5));
+        state.log_operation(admin, Operation::PinEvent { event_id: first, pinned: true });
+        state.record_transaction(create_test_tx_record("tx_a".to_string(), chain, 1000)).unwrap();
+        state.record_audit(admin, &Operation::RemoveRole { target: admin });
+        state.execute_admin_action(crate::AdminOperation::CreateCheckpoint).unwrap();
+        state.set_block_height(2);
+        storage.persist(&mut state).await.unwrap();
+        // Persisting leaves the state itself intact
+        assert_eq!(state.events.len(), 2);
+        assert_eq!(state.operation_log.len(), 1);
+
+        // The next block sees the same events, tree and log
+        let mut storage = AnalyticsStorage::load(context.clone()).await.unwrap();
+        let mut reloaded = storage.read_state().await.unwrap();
+        assert_eq!(reloaded.events, state.events);
+        assert_eq!(reloaded.get_event(second).unwrap().data, serde_json::json!({"n": 1000}));
+        assert_eq!(reloaded.merkle_index.get_root(), state.merkle_index.get_root());
+        assert_eq!(reloaded.merkle_index.internal_nodes, state.merkle_index.internal_nodes);
+        assert_eq!(reloaded.operation_log.len(), 1);
+        assert!(matches!(reloaded.operation_log[0].operation, Operation::PinEvent { event_id, .. } if event_id == first));
+        assert_eq!(reloaded.aggregated_metrics.get("volume"), Some(&MetricValue::Counter(5)));
+        assert_eq!(reloaded.merkle_index.root_history, state.merkle_index.root_history);
+        assert_eq!(reloaded.tx_hash_index, state.tx_hash_index);
+        assert_eq!(reloaded.audit_log, state.audit_log);
+        assert_eq!(reloaded.checkpoints.len(), 1);
+        assert_eq!(reloaded.get_transaction("tx_a").unwrap().timestamp, 1000);
+        // Indexes are rebuilt from the events rather than stored
+        assert_eq!(reloaded.get_app_events(&app).len(), 2);
+        let view = AnalyticsView::load(context.clone()).await.unwrap();
+        assert!(view.parts.get(&"app_index".to_string()).await.unwrap().is_none());
+        assert!(view.parts.get(&"audit_log".to_string()).await.unwrap().is_none());
+
+        // Pinning, evicting and capturing only rewrite the affected entries,
+        // and the order of events survives the reload
+        reloaded.pin_event(first, true).unwrap();
+        reloaded.config.max_total_events = Some(2);
+        let third = capture_test_event(&mut reloaded, 2000);
+        assert_eq!(reloaded.events.iter().map(|event| event.id).collect::<Vec<_>>(), vec![first, third]);
+        reloaded.record_audit(admin, &Operation::RemoveRole { target: admin });
+        storage.persist(&mut reloaded).await.unwrap();
+
+        let mut storage = AnalyticsStorage::load(context.clone()).await.unwrap();
+        let again = storage.read_state().await.unwrap();
+        assert_eq!(again.events, reloaded.events);
+        assert!(again.get_event(first).unwrap().pinned);
+        assert!(again.get_event(second).is_none());
+        assert_eq!(again.merkle_index.leaves, reloaded.merkle_index.leaves);
+        assert_eq!(again.merkle_index.get_root(), reloaded.merkle_index.get_root());
+        assert_eq!(again.audit_log.len(), 2);
+        assert_eq!(again.get_app_events(&app).len(), 2);
+        // The evicted event's hash stays in the dedup index
+        assert_eq!(again.tx_hash_index, reloaded.tx_hash_index);
+        assert!(again.tx_hash_index.contains("tx_1000"));
+
+        // A field added since the last save loads with its default
+        let mut view = AnalyticsView::load(context.clone()).await.unwrap();
+        view.parts.remove(&"operation_log_start".to_string()).unwrap();
+        view.save().await.unwrap();
+        let mut storage = AnalyticsStorage::load(context.clone()).await.unwrap();
+        let upgraded = storage.read_state().await.unwrap();
+        assert!(upgraded.unloaded_parts.is_empty());
+        assert_eq!(upgraded.events, reloaded.events);
+
+        // A part that fails to decode stops the contract's load, while
+        // reads fall back to its default
+        let mut view = AnalyticsView::load(context.clone()).await.unwrap();
+        view.parts.insert(&"aggregated_metrics".to_string(), vec![0xff]).unwrap();
+        view.save().await.unwrap();
+        let mut storage = AnalyticsStorage::load(context).await.unwrap();
+        assert!(storage.read_state().await.is_err());
+        let partial = storage.read_state_partial().await.unwrap();
+        assert_eq!(partial.unloaded_parts, vec!["aggregated_metrics".to_string(), "operation_log_start".to_string()]);
+        assert_eq!(partial.events, reloaded.events);
+    });
+}
+
+// **Feature: pine-analytics, Cross-chain aggregation responses**
+#[test]
+fn test_aggregation_request_answered_with_proof() {
+    let mut state = AnalyticsState::default();
+    let chain = str_to_chain_id("chain1");
+    let app = str_to_app_id("app1");
+    let mut volume_ids = Vec::new();
+    for (i, event_type) in ["volume", "volume", "login"].into_iter().enumerate() {
+        let data = serde_json::json!({ "volume": 10 * (i + 1) });
+        let id = state.insert_event(create_test_event(0, app, chain, 1000 + i as u64, event_type.to_string(), data, format!("tx_{}", i)));
+        volume_ids.push(id);
+    }
+    state.update_metric(format!("{}_volume", app), MetricValue::Gauge(30.0));
+
+    let query = crate::AggregationQuery {
+        metric: "volume".to_string(),
+        aggregation: crate::AggregationType::Sum,
+        start_time: 1000,
+        end_time: 1001,
+        granularity_ms: 1000,
+        app_filter: None,
+    };
+    let crate::Message::AggregationResponse { request_id, results, proof } =
+        state.answer_aggregation_request(42, &[query])
+    else {
+        panic!("expected an aggregation response");
+    };
+    assert_eq!(request_id, 42);
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].metric, "volume");
+
+    let proof = proof.unwrap();
+    assert_eq!(proof.batch_id, 42);
+    let proven: Vec<EventId> = proof.proofs.iter().map(|proof| proof.event_id).collect();
+    assert_eq!(proven, volume_ids[..2].to_vec());
+    let root = state.merkle_index.get_root().unwrap();
+    assert!(crate::MerkleIndex::verify_batch_proof(&root, &proof));
+
+    let crate::Message::AggregationResponse { proof, .. } = state.answer_aggregation_request(7, &[]) else {
+        panic!("expected an aggregation response");
+    };
+    assert!(proof.is_none());
+}
+
+// **Feature: pine-analytics, Tag limits**
+#[test]
+fn test_tag_count_and_length_limits() {
+    let mut state = AnalyticsState::default();
+    state.config.max_tags = 3;
+    state.config.max_tag_len = 5;
+    let chain = str_to_chain_id("chain1");
+    let app = str_to_app_id("app1");
+    let with_tags = |tags: &[&str]| {
+        let mut config = create_test_app_config(app, chain, "http://a.b".to_string());
+        config.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        config
+    };
+
+    // At the limits, after deduplication
+    state.upsert_app_config(with_tags(&["defi", "nft", "defi", "dao12"])).unwrap();
+    assert_eq!(state.monitored_applications[&app].tags, vec!["defi", "nft", "dao12"]);
+
+    assert!(matches!(
+        state.upsert_app_config(with_tags(&["a", "b", "c", "d"])),
+        Err(crate::AnalyticsError::ConfigError(_))
+    ));
+    assert!(matches!(
+        state.upsert_app_config(with_tags(&["toolong"])),
+        Err(crate::AnalyticsError::ConfigError(_))
+    ));
+    assert!(matches!(
+        state.patch_app_config(&app, &serde_json::json!({"tags": ["a", "b", "c", "d"]})),
+        Err(crate::AnalyticsError::ConfigError(_))
+    ));
+    assert_eq!(state.monitored_applications[&app].tags, vec!["defi", "nft", "dao12"]);
+}
+
+// **Feature: pine-analytics, Anomalies with series**
+#[test]
+fn test_anomaly_indices_line_up_with_series() {
+    let mut state = AnalyticsState::default();
+    let chain = str_to_chain_id("chain1");
+    let app = str_to_app_id("app1");
+    for bucket in 0..10u64 {
+        let mut event = create_test_event(0, app, chain, bucket * 1000, "latency".to_string(), serde_json::json!({}), format!("tx_{}", bucket));
+        event.value = Some(if bucket == 6 { 500.0 } else { 10.0 + bucket as f64 % 2.0 });
+        state.insert_event(event);
+    }
+
+    let result = state.anomalies_with_series("latency", 2.0, &TimeRange::new(0, 9999), 1000);
+    assert_eq!(result.series.len(), 10);
+    assert_eq!(result.anomalies.len(), 1);
+    for anomaly in &result.anomalies {
+        let point = &result.series[anomaly.index];
+        assert_eq!(point.timestamp, anomaly.timestamp);
+        assert_eq!(point.value.as_f64(), anomaly.value);
+    }
+    assert_eq!(result.anomalies[0].index, 6);
+    assert_eq!(result.anomalies[0].value, 500.0);
+}
+
+// **Feature: pine-analytics, Sync batches**
+#[test]
+fn test_sync_batches_carry_events_from_requested_id() {
+    let mut state = AnalyticsState::default();
+    state.config.sync_batch_size = 2;
+    for i in 0..6u64 {
+        capture_test_event(&mut state, i);
+    }
+    let root = state.merkle_index.get_root().unwrap();
+
+    let batches = state.sync_batches(1);
+    assert_eq!(batches.len(), 3);
+    let mut synced = Vec::new();
+    for (index, batch) in batches.into_iter().enumerate() {
+        let crate::Message::SyncBatch { events, batch_proof } = batch else {
+            panic!("expected a sync batch");
+        };
+        assert!(events.len() <= 2);
+        let proof = batch_proof.unwrap();
+        assert_eq!(proof.batch_id, index as u64);
+        assert!(crate::MerkleIndex::verify_batch_proof(&root, &proof));
+        synced.extend(events.iter().map(|event| event.id));
+    }
+    assert_eq!(synced, vec![1, 2, 3, 4, 5]);
+
+    assert!(state.sync_batches(6).is_empty());
+}
+
+// **Feature: pine-analytics, Retention eviction**
+#[test]
+fn test_events_past_retention_evicted() {
+    let mut state = AnalyticsState::default();
+    state.config.retention_blocks = Some(10);
+    let chain = str_to_chain_id("chain1");
+    let app = str_to_app_id("app1");
+    let capture = |state: &mut AnalyticsState, block: u64, tx: &str| {
+        state.set_block_height(block);
+        state.insert_event(create_test_event(0, app, chain, block * 1000, "transfer".to_string(), serde_json::json!({}), tx.to_string()))
+    };
+
+    let old = capture(&mut state, 1, "tx_old");
+    let pinned = capture(&mut state, 2, "tx_pinned");
+    state.pin_event(pinned, true).unwrap();
+    let recent = capture(&mut state, 8, "tx_recent");
+
+    // Capturing at block 13 expires events from before block 3
+    let newest = capture(&mut state, 13, "tx_newest");
+    let ids: Vec<EventId> = state.events.iter().map(|event| event.id).collect();
+    assert_eq!(ids, vec![pinned, recent, newest]);
+    assert!(!state.merkle_index.leaves.contains_key(&old));
+    assert!(!state.is_duplicate_tx("tx_old"));
+    assert_eq!(state.total_events_captured, 4);
+    assert_eq!(state.get_events_in_range(0, u64::MAX).len(), 3);
+
+    // On demand, without a capture
+    state.set_block_height(30);
+    state
+        .execute_admin_action(crate::AdminOperation::EvictExpiredEvents)
+        .unwrap();
+    let ids: Vec<EventId> = state.events.iter().map(|event| event.id).collect();
+    assert_eq!(ids, vec![pinned]);
+    assert_eq!(state.merkle_index.event_count(), 1);
+    assert_eq!(state.total_events_captured, 4);
+}
+
+// **Feature: pine-analytics, Import conflict policies**
+#[test]
+fn test_import_conflict_policies() {
+    use crate::OperationResponse;
+
+    let chain = str_to_chain_id("chain1");
+    let app = str_to_app_id("app1");
+    let event = |id: EventId, amount: u64| {
+        create_test_event(id, app, chain, 1000 + id, "transfer".to_string(), serde_json::json!({ "amount": amount }), format!("tx_{}_{}", id, amount))
+    };
+    let setup = || {
+        let mut state = AnalyticsState::default();
+        state.insert_event(event(0, 1));
+        state
+    };
+
+    // Skip keeps the stored event
+    let mut state = setup();
+    let root = state.merkle_index.get_root();
+    let report = state
+        .import_events(vec![event(0, 2), event(5, 3)], ImportConflictPolicy::Skip)
+        .unwrap();
+    assert_eq!(report.outcomes, vec![(0, ImportOutcome::Skipped), (5, ImportOutcome::Imported)]);
+    assert_eq!(state.get_event(0).unwrap().data["amount"], 1);
+    assert_ne!(state.merkle_index.get_root(), root);
+    assert_eq!(state.next_event_id, 6);
+    let response = OperationResponse::from_import(&report);
+    assert!(response.success);
+    assert!(!response.results[0].success && response.results[1].success);
+
+    // Overwrite replaces the event and its Merkle leaf
+    let mut state = setup();
+    let report = state
+        .import_events(vec![event(0, 2)], ImportConflictPolicy::Overwrite)
+        .unwrap();
+    assert_eq!(report.outcomes, vec![(0, ImportOutcome::Overwritten)]);
+    assert_eq!(state.events.len(), 1);
+    let stored = state.get_event(0).unwrap();
+    assert_eq!(stored.data["amount"], 2);
+    let leaf = linera_sdk::linera_base_types::CryptoHash::from(stored.data_hash());
+    assert_eq!(state.merkle_index.leaves[&0], leaf);
+    let proof = state.merkle_index.generate_proof(0).unwrap();
+    assert!(crate::MerkleIndex::verify_proof(&state.merkle_index.get_root().unwrap(), &proof));
+    // The replaced transaction hash can be captured again
+    assert!(!state.tx_hash_index.contains("tx_0_1"));
+    assert!(state.tx_hash_index.contains("tx_0_2"));
+
+    // Imports are held to the store's capacity and retention
+    let mut state = setup();
+    state.config.max_total_events = Some(2);
+    state
+        .import_events((1..4).map(|id| event(id, 1)).collect(), ImportConflictPolicy::Skip)
+        .unwrap();
+    assert_eq!(state.events.len(), 2);
+    assert_eq!(state.merkle_index.leaves.len(), 2);
+    let mut state = setup();
+    state.config.retention_blocks = Some(5);
+    state.set_block_height(20);
+    let mut expired = event(1, 1);
+    expired.block_height = Some(2);
+    state.import_events(vec![expired, event(2, 1)], ImportConflictPolicy::Skip).unwrap();
+    assert!(state.get_event(1).is_none());
+    assert!(state.get_event(2).is_some());
+
+    // Fail rejects the whole import
+    let mut state = setup();
+    assert!(state
+        .import_events(vec![event(7, 1), event(0, 2)], ImportConflictPolicy::Fail)
+        .is_err());
+    assert_eq!(state.events.len(), 1);
+    assert_eq!(state.get_event(0).unwrap().data["amount"], 1);
+    assert!(state
+        .import_events(vec![event(7, 1), event(7, 2)], ImportConflictPolicy::Fail)
+        .is_err());
+    assert!(state.get_event(7).is_none());
+}
+
+// **Feature: pine-analytics, Storage estimate**
+#[test]
+fn test_storage_estimate_scales_with_events() {
+    let capture = |count: u64| {
+        let mut state = AnalyticsState::default();
+        let app = str_to_app_id("app1");
+        let chain = str_to_chain_id("chain1");
+        for i in 0..count {
+            state.insert_event(create_test_event(
+                0,
+                app,
+                chain,
+                i * 1000,
+                "transfer".to_string(),
+                serde_json::json!({"amount": i}),
+                format!("tx_{}", i),
+            ));
+        }
+        state.storage_estimate()
+    };
+
+    assert_eq!(AnalyticsState::default().storage_estimate().events_bytes, 0);
+    let small = capture(10);
+    let large = capture(100);
+    assert!(small.events_bytes > 0 && small.indexes_bytes > 0 && small.merkle_bytes > 0);
+    // Ten times the events takes roughly ten times the space
+    let ratio = large.events_bytes as f64 / small.events_bytes as f64;
+    assert!((8.0..=12.0).contains(&ratio), "events ratio {}", ratio);
+    assert!(large.indexes_bytes > small.indexes_bytes * 5);
+    assert!(large.merkle_bytes > small.merkle_bytes * 5);
+    assert_eq!(large.rbac_bytes, small.rbac_bytes);
+    assert_eq!(
+        large.total_bytes,
+        large.events_bytes + large.metrics_bytes + large.indexes_bytes + large.merkle_bytes + large.rbac_bytes
+    );
+}
+
+// **Feature: pine-analytics, Audit log**
+#[test]
+fn test_role_assignment_audited_once() {
+    use crate::{Operation, OperationResponse, Role};
+
+    let mut state = AnalyticsState::default();
+    let admin = state.admin_owner;
+    let operator = Owner::Address20([2; 20]);
+    let assign = Operation::AssignRole { target: operator, role: Role::Operator };
+
+    // As in the contract: apply, then audit what was applied
+    state.rbac.authorize(&admin, &assign).unwrap();
+    state.rbac.assign_role(operator, Role::Operator).unwrap();
+    let response = OperationResponse { success: true, event_id: None, error: None, results: Vec::new() };
+    let applied = assign.logged_part(&response).unwrap();
+    state.record_audit(admin, &applied);
+
+    let all = state.audit_entries(&Pagination::default(), None);
+    assert_eq!(all.len(), 1);
+    assert_eq!(all[0].actor, admin);
+    assert_eq!(all[0].action, "AssignRole");
+
+    // Captures are not audited, and the actor filter applies
+    let capture = Operation::CaptureEvent {
+        event: create_test_event(0, str_to_app_id("app1"), str_to_chain_id("chain1"), 1000, "transfer".to_string(), serde_json::json!({}), "tx_1".to_string()),
+    };
+    state.record_audit(operator, &capture);
+    state.record_audit(operator, &Operation::RemoveRole { target: Owner::Address20([3; 20]) });
+    assert_eq!(state.audit_log.len(), 2);
+    assert_eq!(state.audit_entries(&Pagination::default(), Some(&operator)).len(), 1);
+    assert!(state.audit_entries(&Pagination::default(), Some(&Owner::Address20([9; 20]))).is_empty());
+}
+
+// **Feature: pine-analytics, Event type counts**
+#[test]
+fn test_event_type_counts_follow_stored_events() {
+    let mut state = AnalyticsState::default();
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+    let capture = |state: &mut AnalyticsState, ts: u64, event_type: &str| {
+        state.insert_event(create_test_event(0, app, chain, ts, event_type.to_string(), serde_json::json!({}), format!("tx_{}", ts)))
+    };
+
+    // Off by default
+    capture(&mut state, 1, "transfer");
+    assert!(state.event_type_counts.is_empty());
+
+    // Enabling counts events already stored
+    let mut config = state.config.clone();
+    config.track_event_type_counts = true;
+    config.max_total_events = Some(4);
+    state.apply_config(config);
+    assert_eq!(state.event_type_counts["transfer"], 1);
+
+    capture(&mut state, 2, "swap");
+    capture(&mut state, 3, "transfer");
+    capture(&mut state, 4, "mint");
+    let counts: Vec<(&str, u64)> = state.event_type_counts.iter().map(|(t, c)| (t.as_str(), *c)).collect();
+    assert_eq!(counts, vec![("mint", 1), ("swap", 1), ("transfer", 2)]);
+
+    // Pruning at capacity evicts the oldest transfer
+    capture(&mut state, 5, "swap");
+    assert_eq!(state.events.len(), 4);
+    assert_eq!(state.event_type_counts["transfer"], 1);
+    assert_eq!(state.event_type_counts["swap"], 2);
+
+    // Clearing empties the counts
+    let token = state.clear_events_token();
+    state.execute_admin_action(crate::AdminOperation::ClearEvents { token: Some(token) }).unwrap();
+    assert!(state.event_type_counts.is_empty());
+}
+
+// **Feature: pine-analytics, Invariant checks**
+#[test]
+fn test_broken_invariants_reported() {
+    let mut state = AnalyticsState::default();
+    let app = str_to_app_id("app1");
+    for i in 0..3 {
+        state.insert_event(create_test_event(0, app, str_to_chain_id("chain1"), i * 1000, "transfer".to_string(), serde_json::json!({}), format!("tx_{}", i)));
+    }
+    assert!(state.check_invariants().healthy);
+
+    // An app index entry for an event that is not stored
+    state.app_index.get_mut(&app).unwrap().push(42);
+    let report = state.check_invariants();
+    assert!(!report.healthy);
+    assert_eq!(
+        report.violations,
+        vec![crate::InvariantViolation::DanglingIndexEntry { index: "app_index".to_string(), event_id: 42 }]
+    );
+    state.app_index.get_mut(&app).unwrap().pop();
+
+    // A Merkle leaf lost for a stored event
+    state.merkle_index.leaves.remove(&1);
+    let violations = state.check_invariants().violations;
+    assert!(violations.contains(&crate::InvariantViolation::MissingMerkleLeaf(1)));
+    assert!(violations.contains(&crate::InvariantViolation::MerkleLeafCountMismatch { leaves: 2, events: 3 }));
+    state.rebuild_merkle_index();
+
+    // A rate-limiter counter past its limit
+    state.rate_limiter.global_counter.count = state.rate_limiter.global_limit() + 1;
+    assert!(matches!(
+        state.check_invariants().violations.as_slice(),
+        [crate::InvariantViolation::GlobalCounterOverLimit { .. }]
+    ));
+}
+
+// **Feature: pine-analytics, Event page truncation**
+#[test]
+fn test_event_page_reports_truncation() {
+    let mut state = AnalyticsState::default();
+    for i in 0..5 {
+        let event_type = if i < 3 { "swap" } else { "transfer" };
+        state.insert_event(create_test_event(0, str_to_app_id("app1"), str_to_chain_id("chain1"), i * 1000, event_type.to_string(), serde_json::json!({"kind": event_type}), format!("tx_{}", i)));
+    }
+    let filters = EventFilters {
+        search_text: Some("swap".to_string()),
+        ..EventFilters::default()
+    };
+    let page = |limit| state.query_event_page(&filters, &Pagination { offset: 0, limit });
+
+    // Below the limit: complete
+    let below = page(4);
+    assert_eq!(below.events.len(), 3);
+    assert!(!below.truncated);
+
+    // Exactly at the limit: complete, since no further match exists
+    let exact = page(3);
+    assert_eq!(exact.events.len(), 3);
+    assert!(!exact.truncated);
+
+    // One short of the matches: truncated
+    let short = page(2);
+    assert_eq!(short.events.len(), 2);
+    assert!(short.truncated);
+    let ids = |events: &[CapturedEvent]| events.iter().map(|event| event.id).collect::<Vec<_>>();
+    assert_eq!(ids(&short.events), ids(&state.query_events(&filters, &Pagination { offset: 0, limit: 2 })));
+}
+
+// **Feature: pine-analytics, Metric definition updates**
+#[test]
+fn test_update_metric_definition_patches_aggregation() {
+    let mut state = AnalyticsState::default();
+    state.metric_definitions.insert(
+        "volume".to_string(),
+        crate::MetricDefinition {
+            name: "volume".to_string(),
+            description: "Transfer volume".to_string(),
+            metric_type: crate::MetricType::Gauge,
+            extraction_path: "/amount".to_string(),
+            aggregation: crate::AggregationMethod::Sum,
+        },
+    );
+    for (i, amount) in [5, 2, 8].into_iter().enumerate() {
+        state.insert_event(create_test_event(0, str_to_app_id("app1"), str_to_chain_id("chain1"), 1000 + i as u64, "transfer".to_string(), serde_json::json!({"amount": amount}), format!("tx_{}", i)));
+    }
+    let range = TimeRange { start: 0, end: 5000 };
+    assert_eq!(state.defined_metric_value("volume", &range), Some(15.0));
+
+    state
+        .update_metric_definition("volume", &serde_json::json!({"aggregation": "Max"}))
+        .unwrap();
+    let definition = &state.metric_definitions["volume"];
+    assert_eq!(definition.aggregation, crate::AggregationMethod::Max);
+    assert_eq!(definition.description, "Transfer volume");
+    assert_eq!(definition.metric_type, crate::MetricType::Gauge);
+    assert_eq!(definition.extraction_path, "/amount");
+    assert_eq!(state.defined_metric_value("volume", &range), Some(8.0));
+
+    // Renames, unusable paths and unknown metrics are rejected unchanged
+    for patch in [serde_json::json!({"name": "other"}), serde_json::json!({"extraction_path": ""}), serde_json::json!({"extraction_path": "/a~2"})] {
+        assert!(state.update_metric_definition("volume", &patch).is_err());
+    }
+    assert_eq!(state.metric_definitions["volume"].extraction_path, "/amount");
+    assert!(state.update_metric_definition("missing", &serde_json::json!({})).is_err());
+}
+
+// **Feature: pine-analytics, Event sampling**
+#[test]
+fn test_sample_rate_stores_about_a_tenth_but_counts_all() {
+    let mut state = AnalyticsState::default();
+    state.rate_limiter.config.enabled = false;
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+    let mut config = create_test_app_config(app, chain, "http://localhost".to_string());
+    config.sample_rate = Some(0.1);
+    state.upsert_app_config(config).unwrap();
+
+    let mut stored = 0;
+    for i in 0..2000u64 {
+        let event = create_test_event(0, app, chain, 1000, "tick".to_string(), serde_json::json!({}), format!("tx_{}", i));
+        if state.capture_event_checked(event, 1000).unwrap().is_some() {
+            stored += 1;
+        }
+    }
+    assert_eq!(state.total_events_seen, 2000);
+    assert_eq!(state.events.len(), stored);
+    assert!((120..=280).contains(&stored), "stored {} of 2000", stored);
+
+    // The sample is deterministic: a replayed hash gets the same decision
+    let config = &state.monitored_applications[&app];
+    let kept: Vec<bool> = (0..50).map(|i| config.samples(&format!("tx_{}", i))).collect();
+    assert_eq!(kept, (0..50).map(|i| config.samples(&format!("tx_{}", i))).collect::<Vec<_>>());
+
+    // Out-of-range rates are rejected
+    for rate in [0.0, 1.5, f64::NAN] {
+        let mut config = create_test_app_config(app, chain, "http://localhost".to_string());
+        config.sample_rate = Some(rate);
+        assert!(state.upsert_app_config(config).is_err());
+    }
+}
+
+// **Feature: pine-analytics, Event size stats**
+#[test]
+fn test_event_size_stats_reflect_large_payloads() {
+    let mut state = AnalyticsState::default();
+    let app1 = str_to_app_id("app1");
+    let app2 = str_to_app_id("app2");
+    let chain = str_to_chain_id("chain1");
+    assert_eq!(state.event_size_stats(None).sample_count, 0);
+
+    // 18 small payloads of 7 bytes, and two bloated ones
+    for i in 0..18u64 {
+        state.insert_event(create_test_event(0, app1, chain, 1000 + i, "tick".to_string(), serde_json::json!({"n": i % 10}), format!("tx_{}", i)));
+    }
+    let blob = "x".repeat(10_000);
+    for i in 0..2u64 {
+        state.insert_event(create_test_event(0, app2, chain, 2000 + i, "dump".to_string(), serde_json::json!({"blob": blob}), format!("tx_big_{}", i)));
+    }
+
+    let all = state.event_size_stats(None);
+    assert_eq!(all.sample_count, 20);
+    assert_eq!(all.min_bytes, 7.0);
+    assert_eq!(all.max_bytes, 10_011.0);
+    assert!((all.avg_bytes - (18.0 * 7.0 + 2.0 * 10_011.0) / 20.0).abs() < 0.001);
+    assert_eq!(all.p95_bytes, 10_011.0);
+
+    let small = state.event_size_stats(Some(&app1));
+    assert_eq!(small.sample_count, 18);
+    assert_eq!((small.min_bytes, small.max_bytes, small.p95_bytes), (7.0, 7.0, 7.0));
+}
+
+// **Feature: pine-analytics, EWMA**
+#[test]
+fn test_metric_ewma_smooths_in_range_and_validates_alpha() {
+    let mut state = AnalyticsState::default();
+    let app = str_to_app_id("app1");
+    let chain = str_to_chain_id("chain1");
+    for (i, amount) in [10.0, 20.0, 20.0, 99.0].into_iter().enumerate() {
+        let timestamp = 1000 + i as u64 * 1000;
+        state.insert_event(create_test_event(0, app, chain, timestamp, "transfer".to_string(), serde_json::json!({"amount": amount}), format!("tx_{}", i)));
+    }
+
+    // The last event falls outside the range
+    let range = TimeRange::new(0, 3500);
+    let points = state.metric_ewma("amount", 0.5, &range).unwrap();
+    let values: Vec<f64> = points.iter().map(|p| p.value).collect();
+    assert_eq!(values, vec![10.0, 15.0, 17.5]);
+    assert_eq!(points[2].timestamp, 3000);
+
+    for alpha in [0.0, -0.5, 1.5, f64::NAN] {
+        assert!(matches!(
+            state.metric_ewma("amount", alpha, &range),
+            Err(crate::AnalyticsError::QueryError(_))
+        ));
+    }
+    assert_eq!(state.metric_ewma("amount", 1.0, &range).unwrap().len(), 3);
+}
+
+// **Feature: pine-analytics, Replication source**
+#[test]
+fn test_replicated_log_from_foreign_chain_is_refused() {
+    use crate::{Operation, OperationLogEntry, Role};
+
+    let admin = Owner::Address20([1; 20]);
+    let attacker = Owner::Address20([9; 20]);
+    let source = str_to_chain_id("primary");
+    let foreign = str_to_chain_id("foreign");
+    let forged = || {
+        vec![OperationLogEntry {
+            index: 0,
+            block_height: 0,
+            caller: attacker,
+            operation: Operation::AssignRole { target: attacker, role: Role::Admin },
+        }]
+    };
+
+    // No source configured refuses every chain
+    let mut standby = AnalyticsState::new(admin);
+    assert!(standby.apply_replicated_log(source, forged()).is_err());
+
+    standby.replication_source = Some(source);
+    assert!(matches!(
+        standby.apply_replicated_log(foreign, forged()),
+        Err(crate::AnalyticsError::CrossChainError(_))
+    ));
+    assert_eq!(standby.rbac.get_role(&attacker), Role::Viewer);
+    assert!(standby.operation_log.is_empty());
+
+    assert_eq!(standby.apply_replicated_log(source, forged()).unwrap(), 1);
+    assert_eq!(standby.rbac.get_role(&attacker), Role::Admin);
+}
+
+// **Feature: pine-analytics, Operation log truncation**
+#[test]
+fn test_operation_log_is_capped_and_truncated() {
+    use crate::{Operation, Permission};
+
+    let admin = Owner::Address20([1; 20]);
+    let source = str_to_chain_id("primary");
+    let limit = |name: &str| Operation::DefineCustomRole {
+        name: name.to_string(),
+        permissions: [Permission::ViewData].into_iter().collect(),
+    };
+
+    let mut primary = AnalyticsState::new(admin);
+    primary.config.max_operation_log_entries = Some(3);
+    for i in 0..5 {
+        primary.log_operation(admin, limit(&format!("role_{}", i)));
+    }
+    // The oldest entries are dropped and the rest keep their indexes
+    assert_eq!(primary.operation_log.len(), 3);
+    assert_eq!(primary.operation_log_start, 2);
+    assert_eq!(primary.next_log_index(), 5);
+    assert_eq!(primary.operation_log_since(3).iter().map(|entry| entry.index).collect::<Vec<_>>(), vec![3, 4]);
+
+    // A standby that fell behind the kept entries sees the gap
+    let mut standby = AnalyticsState::new(admin);
+    standby.replication_source = Some(source);
+    assert!(standby.apply_replicated_log(source, primary.operation_log_since(0)).is_err());
+
+    // One that is caught up checkpoints and truncates its own copy
+    let mut standby = AnalyticsState::new(admin);
+    standby.replication_source = Some(source);
+    standby.operation_log_start = 2;
+    assert_eq!(standby.apply_replicated_log(source, primary.operation_log_since(2)).unwrap(), 3);
+    assert_eq!(standby.truncate_operation_log(4), 2);
+    assert_eq!(standby.operation_log_start, 4);
+    assert_eq!(standby.truncate_operation_log(1), 0);
+    primary.log_operation(admin, limit("role_5"));
+    assert_eq!(standby.apply_replicated_log(source, primary.operation_log_since(standby.next_log_index())).unwrap(), 1);
+    assert_eq!(standby.operation_log.iter().map(|entry| entry.index).collect::<Vec<_>>(), vec![4, 5]);
+    assert!(!Operation::TruncateOperationLog { before_index: 0 }.is_logged());
+}
+
+// **Feature: pine-analytics, Operation log replication**
+#[test]
+fn test_failed_replay_leaves_no_partial_effects() {
+    use crate::{AdminOperation, Operation, OperationLogEntry, RBACState, Role};
+
+    let primary_admin = Owner::Address20([1; 20]);
+    let standby_admin = Owner::Address20([2; 20]);
+    let operator = Owner::Address20([3; 20]);
+    let source = str_to_chain_id("primary");
+    let mut standby = AnalyticsState::new(standby_admin);
+    standby.replication_source = Some(source);
+    let entry = |index: u64, operation: Operation| OperationLogEntry {
+        index,
+        block_height: 0,
+        caller: primary_admin,
+        operation,
+    };
+
+    // The pin fails until the event has synced; the sample before it must
+    // not be recorded by the failed attempt
+    let batch = entry(0, Operation::Batch {
+        operations: vec![
+            Operation::UpdateMetric { key: "latency".to_string(), value: MetricValue::Histogram(vec![5.0]) },
+            Operation::PinEvent { event_id: 0, pinned: true },
+        ],
+        stop_on_error: true,
+    });
+    assert!(standby.apply_replicated_log(source, vec![batch.clone()]).is_err());
+    assert!(!standby.aggregated_metrics.contains_key("latency"));
+    assert!(standby.operation_log.is_empty());
+
+    capture_test_event(&mut standby, 0);
+    assert_eq!(standby.apply_replicated_log(source, vec![batch]).unwrap(), 1);
+    assert_eq!(standby.aggregated_metrics.get("latency"), Some(&MetricValue::Histogram(vec![5.0])));
+    assert!(standby.get_event(0).unwrap().pinned);
+
+    // A role snapshot from a primary with another super admin keeps the
+    // standby's own
+    let mut snapshot = RBACState::new(primary_admin);
+    snapshot.assign_role(operator, Role::Operator).unwrap();
+    let import = entry(1, Operation::ImportRBAC { snapshot });
+    assert_eq!(standby.apply_replicated_log(source, vec![import]).unwrap(), 1);
+    assert_eq!(standby.rbac.get_role(&standby_admin), Role::SuperAdmin);
+    assert_eq!(standby.rbac.get_role(&primary_admin), Role::Viewer);
+    assert_eq!(standby.rbac.get_role(&operator), Role::Operator);
+    let pause = entry(2, Operation::AdminAction { action: AdminOperation::PauseIngestion });
+    assert_eq!(standby.apply_replicated_log(source, vec![pause]).unwrap(), 1);
+}